@@ -0,0 +1,10 @@
+use cosmwasm_schema::write_api;
+use merkle_airdrop::contract::sv::{ContractExecMsg, ContractQueryMsg, InstantiateMsg};
+
+fn main() {
+    write_api! {
+        instantiate: InstantiateMsg,
+        query: ContractQueryMsg,
+        execute: ContractExecMsg,
+    }
+}
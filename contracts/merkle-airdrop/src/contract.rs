@@ -0,0 +1,313 @@
+use cosmwasm_std::{ensure, ensure_eq, Addr, Response, StdResult, Uint128};
+use cw2::set_contract_version;
+use cw20::{BalanceResponse, Cw20QueryMsg};
+use cw_storage_plus::{Item, Map};
+use cw_utils::{maybe_addr, nonpayable, Expiration};
+use sylvia::{
+    contract,
+    types::{ExecCtx, InstantiateCtx, QueryCtx},
+};
+use uju_cw2_common::{address::address_or, error::CommonError};
+
+use crate::{
+    error::ContractError,
+    events::{ClaimEvent, ConfigEvent},
+    helpers::{decode_hash, send_asset_message, verify_proof},
+    state::{AirdropAsset, Config},
+};
+
+pub const CONTRACT_NAME: &str = env!("CARGO_PKG_NAME");
+pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+pub struct MerkleAirdropContract {
+    pub config: Item<Config>,
+    pub claimed: Map<Addr, Uint128>,
+    pub total_claimed: Item<Uint128>,
+}
+
+impl Default for MerkleAirdropContract {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(not(feature = "library"))]
+use sylvia::entry_points;
+
+#[cfg_attr(not(feature = "library"), entry_points)]
+#[contract]
+#[sv::error(ContractError)]
+impl MerkleAirdropContract {
+    pub const fn new() -> Self {
+        Self {
+            config: Item::new("C"),
+            claimed: Map::new("A"),
+            total_claimed: Item::new("T"),
+        }
+    }
+
+    /// Native drops may be funded along with the instantiation, cw20 drops by a transfer
+    #[sv::msg(instantiate)]
+    pub fn instantiate(
+        &self,
+        ctx: InstantiateCtx,
+        merkle_root: String,
+        asset: AirdropAsset,
+        expiration: Option<Expiration>,
+        owner: Option<String>,
+    ) -> Result<Response, ContractError> {
+        set_contract_version(ctx.deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+        decode_hash(&merkle_root)?;
+
+        if let Some(expiration) = &expiration {
+            ensure!(
+                !expiration.is_expired(&ctx.env.block),
+                CommonError::InvalidInput("expiration is in the past".to_string())
+            );
+        }
+
+        let asset = match asset {
+            AirdropAsset::Native(denom) => AirdropAsset::Native(denom),
+            AirdropAsset::Cw20(cw20) => {
+                AirdropAsset::Cw20(ctx.deps.api.addr_validate(cw20.as_str())?)
+            }
+        };
+
+        let config = Config {
+            owner: address_or(&ctx.info.sender, maybe_addr(ctx.deps.api, owner)?.as_ref()),
+            merkle_root,
+            asset,
+            expiration,
+        };
+        self.config.save(ctx.deps.storage, &config)?;
+
+        self.total_claimed
+            .save(ctx.deps.storage, &Uint128::zero())?;
+
+        let response = Response::new().add_event(ConfigEvent {
+            ty: "set-config",
+            config: &config,
+        });
+
+        Ok(response)
+    }
+
+    #[sv::msg(exec)]
+    pub fn claim(
+        &self,
+        ctx: ExecCtx,
+        amount: Uint128,
+        proof: Vec<String>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let config = self.config.load(ctx.deps.storage)?;
+
+        if let Some(expiration) = &config.expiration {
+            ensure!(
+                !expiration.is_expired(&ctx.env.block),
+                ContractError::Expired
+            );
+        }
+
+        ensure!(
+            !self.claimed.has(ctx.deps.storage, ctx.info.sender.clone()),
+            ContractError::AlreadyClaimed
+        );
+
+        verify_proof(&config.merkle_root, &ctx.info.sender, amount, &proof)?;
+
+        self.claimed
+            .save(ctx.deps.storage, ctx.info.sender.clone(), &amount)?;
+        self.total_claimed
+            .update(ctx.deps.storage, |total_claimed| {
+                Ok::<_, ContractError>(total_claimed.checked_add(amount)?)
+            })?;
+
+        let response = Response::new()
+            .add_event(ClaimEvent {
+                ty: "claim",
+                recipient: &ctx.info.sender,
+                amount,
+            })
+            .add_message(send_asset_message(&config.asset, &ctx.info.sender, amount)?);
+
+        Ok(response)
+    }
+
+    /// Returns what is left of the drop to the owner once claims have expired
+    #[sv::msg(exec)]
+    pub fn clawback(
+        &self,
+        ctx: ExecCtx,
+        recipient: Option<String>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let config = self.config.load(ctx.deps.storage)?;
+        ensure_eq!(
+            config.owner,
+            ctx.info.sender,
+            CommonError::Unauthorized("only the owner can claw back".to_string())
+        );
+
+        ensure!(
+            config
+                .expiration
+                .as_ref()
+                .is_some_and(|expiration| expiration.is_expired(&ctx.env.block)),
+            CommonError::InvalidInput("claims have not expired".to_string())
+        );
+
+        let recipient = address_or(
+            &ctx.info.sender,
+            maybe_addr(ctx.deps.api, recipient)?.as_ref(),
+        );
+
+        let amount = match &config.asset {
+            AirdropAsset::Native(denom) => {
+                ctx.deps
+                    .querier
+                    .query_balance(&ctx.env.contract.address, denom)?
+                    .amount
+            }
+            AirdropAsset::Cw20(cw20) => {
+                let balance_response: BalanceResponse = ctx.deps.querier.query_wasm_smart(
+                    cw20.to_string(),
+                    &Cw20QueryMsg::Balance {
+                        address: ctx.env.contract.address.to_string(),
+                    },
+                )?;
+                balance_response.balance
+            }
+        };
+        ensure!(
+            !amount.is_zero(),
+            CommonError::InvalidInput("nothing to claw back".to_string())
+        );
+
+        let response = Response::new()
+            .add_event(ClaimEvent {
+                ty: "clawback",
+                recipient: &recipient,
+                amount,
+            })
+            .add_message(send_asset_message(&config.asset, &recipient, amount)?);
+
+        Ok(response)
+    }
+
+    #[sv::msg(query)]
+    pub fn config(&self, ctx: QueryCtx) -> StdResult<Config> {
+        self.config.load(ctx.deps.storage)
+    }
+
+    #[sv::msg(query)]
+    pub fn claimed(&self, ctx: QueryCtx, address: String) -> StdResult<Option<Uint128>> {
+        let address = ctx.deps.api.addr_validate(&address)?;
+        self.claimed.may_load(ctx.deps.storage, address)
+    }
+
+    #[sv::msg(query)]
+    pub fn total_claimed(&self, ctx: QueryCtx) -> StdResult<Uint128> {
+        self.total_claimed.load(ctx.deps.storage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        contract::sv::mt::{CodeId, MerkleAirdropContractProxy},
+        error::ContractError,
+        state::AirdropAsset,
+    };
+
+    use cosmwasm_std::{coins, Uint128};
+    use cw_utils::Expiration;
+    use sha2::{Digest, Sha256};
+    use sylvia::{
+        cw_multi_test::{App as CwApp, IntoAddr},
+        multitest::App,
+    };
+
+    #[test]
+    fn test_claim_and_clawback() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+        let user1 = "user1".into_addr();
+        let user2 = "user2".into_addr();
+
+        app.app_mut().init_modules(|router, _, storage| {
+            router
+                .bank
+                .init_balance(storage, &owner, coins(1000, "ujuno"))
+                .unwrap();
+        });
+
+        let leaf = |address: &str, amount: u128| -> [u8; 32] {
+            Sha256::digest(format!("{}{}", address, amount).as_bytes()).into()
+        };
+        let leaf1 = leaf(user1.as_str(), 100);
+        let leaf2 = leaf(user2.as_str(), 200);
+        let mut pair = [leaf1, leaf2];
+        pair.sort();
+        let merkle_root = hex::encode(Sha256::digest(pair.concat()));
+
+        let expiration = Expiration::AtHeight(app.block_info().height + 10);
+        let merkle_airdrop = CodeId::store_code(&app)
+            .instantiate(
+                merkle_root,
+                AirdropAsset::Native("ujuno".to_string()),
+                Some(expiration),
+                None,
+            )
+            .with_funds(&coins(1000, "ujuno"))
+            .call(&owner)
+            .unwrap();
+
+        // Amounts must match the snapshot
+        let err = merkle_airdrop
+            .claim(Uint128::new(200), vec![hex::encode(leaf2)])
+            .call(&user1)
+            .unwrap_err();
+        assert_eq!(err, ContractError::InvalidProof);
+
+        merkle_airdrop
+            .claim(Uint128::new(100), vec![hex::encode(leaf2)])
+            .call(&user1)
+            .unwrap();
+
+        let err = merkle_airdrop
+            .claim(Uint128::new(100), vec![hex::encode(leaf2)])
+            .call(&user1)
+            .unwrap_err();
+        assert_eq!(err, ContractError::AlreadyClaimed);
+
+        let balance = app.app().wrap().query_balance(&user1, "ujuno").unwrap();
+        assert_eq!(balance.amount, Uint128::new(100));
+        assert_eq!(
+            merkle_airdrop.claimed(user1.to_string()).unwrap(),
+            Some(Uint128::new(100))
+        );
+
+        // Leftovers stay claimable until expiry
+        assert!(merkle_airdrop.clawback(None).call(&owner).is_err());
+
+        app.update_block(|block| block.height += 10);
+
+        let err = merkle_airdrop
+            .claim(Uint128::new(200), vec![hex::encode(leaf1)])
+            .call(&user2)
+            .unwrap_err();
+        assert_eq!(err, ContractError::Expired);
+
+        assert!(merkle_airdrop.clawback(None).call(&user1).is_err());
+        merkle_airdrop.clawback(None).call(&owner).unwrap();
+
+        let balance = app.app().wrap().query_balance(&owner, "ujuno").unwrap();
+        assert_eq!(balance.amount, Uint128::new(900));
+        assert_eq!(merkle_airdrop.total_claimed().unwrap(), Uint128::new(100));
+    }
+}
@@ -0,0 +1,40 @@
+use cosmwasm_std::{attr, Addr, Event, Uint128};
+use std::vec;
+
+use crate::state::Config;
+
+pub struct ConfigEvent<'a> {
+    pub ty: &'a str,
+    pub config: &'a Config,
+}
+
+impl<'a> From<ConfigEvent<'a>> for Event {
+    fn from(ce: ConfigEvent) -> Self {
+        Event::new(ce.ty.to_string()).add_attributes(vec![
+            attr("owner", ce.config.owner.to_string()),
+            attr("merkle_root", ce.config.merkle_root.to_string()),
+            attr("asset", ce.config.asset.to_string()),
+            attr(
+                "expiration",
+                ce.config
+                    .expiration
+                    .map_or("none".to_string(), |expiration| expiration.to_string()),
+            ),
+        ])
+    }
+}
+
+pub struct ClaimEvent<'a> {
+    pub ty: &'a str,
+    pub recipient: &'a Addr,
+    pub amount: Uint128,
+}
+
+impl<'a> From<ClaimEvent<'a>> for Event {
+    fn from(ce: ClaimEvent) -> Self {
+        Event::new(ce.ty.to_string()).add_attributes(vec![
+            attr("recipient", ce.recipient.to_string()),
+            attr("amount", ce.amount.to_string()),
+        ])
+    }
+}
@@ -0,0 +1,120 @@
+use cosmwasm_std::{coins, to_json_binary, Addr, BankMsg, CosmosMsg, StdResult, Uint128, WasmMsg};
+use cw20::Cw20ExecuteMsg;
+use sha2::{Digest, Sha256};
+use uju_cw2_common::error::CommonError;
+
+use crate::{error::ContractError, state::AirdropAsset};
+
+/// Decodes a hex encoded sha256 hash
+pub fn decode_hash(hash: &str) -> Result<[u8; 32], ContractError> {
+    let mut buf = [0u8; 32];
+    hex::decode_to_slice(hash, &mut buf)
+        .map_err(|_| CommonError::InvalidInput(format!("invalid hash {}", hash)))?;
+    Ok(buf)
+}
+
+/// Checks the proof of the `{address}{amount}` leaf against the merkle root, pairs are
+/// hashed in sorted order
+pub fn verify_proof(
+    merkle_root: &str,
+    address: &Addr,
+    amount: Uint128,
+    proof: &[String],
+) -> Result<(), ContractError> {
+    let leaf: [u8; 32] = Sha256::digest(format!("{}{}", address, amount).as_bytes()).into();
+
+    let hash = proof.iter().try_fold(leaf, |hash, sibling| {
+        let sibling = decode_hash(sibling)?;
+
+        let (first, second) = if hash <= sibling {
+            (hash, sibling)
+        } else {
+            (sibling, hash)
+        };
+
+        Ok::<_, ContractError>(Sha256::digest([first, second].concat()).into())
+    })?;
+
+    if hash != decode_hash(merkle_root)? {
+        return Err(ContractError::InvalidProof);
+    }
+
+    Ok(())
+}
+
+pub fn send_asset_message(
+    asset: &AirdropAsset,
+    recipient: &Addr,
+    amount: Uint128,
+) -> StdResult<CosmosMsg> {
+    Ok(match asset {
+        AirdropAsset::Native(denom) => BankMsg::Send {
+            to_address: recipient.to_string(),
+            amount: coins(amount.u128(), denom),
+        }
+        .into(),
+        AirdropAsset::Cw20(cw20) => WasmMsg::Execute {
+            contract_addr: cw20.to_string(),
+            msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: recipient.to_string(),
+                amount,
+            })?,
+            funds: vec![],
+        }
+        .into(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn leaf(address: &str, amount: u128) -> [u8; 32] {
+        Sha256::digest(format!("{}{}", address, amount).as_bytes()).into()
+    }
+
+    #[test]
+    fn test_verify_proof() {
+        let leaf1 = leaf("user1", 100);
+        let leaf2 = leaf("user2", 200);
+
+        let (first, second) = if leaf1 <= leaf2 {
+            (leaf1, leaf2)
+        } else {
+            (leaf2, leaf1)
+        };
+        let root = hex::encode(Sha256::digest([first, second].concat()));
+
+        verify_proof(
+            &root,
+            &Addr::unchecked("user1"),
+            Uint128::new(100),
+            &[hex::encode(leaf2)],
+        )
+        .unwrap();
+        verify_proof(
+            &root,
+            &Addr::unchecked("user2"),
+            Uint128::new(200),
+            &[hex::encode(leaf1)],
+        )
+        .unwrap();
+
+        assert_eq!(
+            verify_proof(
+                &root,
+                &Addr::unchecked("user1"),
+                Uint128::new(200),
+                &[hex::encode(leaf2)],
+            ),
+            Err(ContractError::InvalidProof)
+        );
+        assert!(verify_proof(
+            &root,
+            &Addr::unchecked("user1"),
+            Uint128::new(100),
+            &["zz".to_string()],
+        )
+        .is_err());
+    }
+}
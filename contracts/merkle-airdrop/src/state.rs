@@ -0,0 +1,30 @@
+use std::fmt;
+
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::Addr;
+use cw_utils::Expiration;
+
+#[cw_serde]
+pub enum AirdropAsset {
+    Native(String),
+    Cw20(Addr),
+}
+
+impl fmt::Display for AirdropAsset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AirdropAsset::Native(denom) => write!(f, "{}", denom),
+            AirdropAsset::Cw20(cw20) => write!(f, "{}", cw20),
+        }
+    }
+}
+
+#[cw_serde]
+pub struct Config {
+    pub owner: Addr,
+    /// Hex encoded sha256 merkle root of the `{address}{amount}` leaves
+    pub merkle_root: String,
+    pub asset: AirdropAsset,
+    /// After expiry claims close and the owner can claw back what is left
+    pub expiration: Option<Expiration>,
+}
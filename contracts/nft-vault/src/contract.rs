@@ -1,22 +1,31 @@
 use cosmwasm_std::{
-    attr, ensure, to_json_binary, Addr, Env, Event, Response, StdResult, Storage, SubMsg,
-    Timestamp, Uint128, WasmMsg,
+    attr, coin, coins, ensure, from_json, to_json_binary, Addr, BankMsg, Binary, Coin, Deps,
+    DepsMut, Empty, Env, Event, MessageInfo, Order, Reply, Response, StdError, StdResult, Storage,
+    SubMsg, SubMsgResult, Timestamp, Uint128, WasmMsg,
 };
 use cw2::set_contract_version;
-use cw20::{BalanceResponse, Cw20ExecuteMsg, Cw20QueryMsg};
-use cw_storage_plus::{IndexedMap, Item, Map, MultiIndex, SnapshotItem, Strategy};
+use cw20::Cw20ExecuteMsg;
+use cw_storage_plus::{
+    Bound, IndexedMap, Item, Map, MultiIndex, SnapshotItem, SnapshotMap, Strategy,
+};
 use cw_utils::{maybe_addr, must_pay, nonpayable, Expiration};
+use stake_derivative::contract::sv::InstantiateMsg as StakeDerivativeInstantiateMsg;
+use stake_receipt::contract::sv::InstantiateMsg as StakeReceiptInstantiateMsg;
 use stake_rewards::contract::sv::{
     ExecMsg as PassageRewardsExecuteMsg, InstantiateMsg as StakeRewardsInstantiateMsg,
+    QueryMsg as StakeRewardsQueryMsg,
+};
+use stake_rewards::{
+    curve::EmissionCurve,
+    msg::ReceiveMsg as StakeRewardsReceiveMsg,
+    state::{Accounting, Distribution, EmissionSegment, RewardAsset, UndistributedMode, Vesting},
 };
-use stake_rewards::state::RewardAsset;
 use std::cmp::min;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use sylvia::{
     contract,
-    types::{ExecCtx, InstantiateCtx, QueryCtx},
+    types::{ExecCtx, InstantiateCtx, QueryCtx, ReplyCtx, SudoCtx},
 };
-use uju_cw2_common::admin::only_contract_admin;
 use uju_cw2_common::{
     address::address_or,
     error::CommonError,
@@ -28,9 +37,28 @@ use uju_index_query::{QueryOptions, QueryOptionsInternal};
 use crate::{
     claim::{Claim, Claims},
     error::ContractError,
-    events::{ConfigEvent, RewardAccountEvent},
-    helpers::{setup_stake_change_messages, UpdateStakeResult},
-    state::{Config, Nft, StakedNft, StakedNftId, StakedNftIndices},
+    events::{
+        ActivateNftsEvent, AirdropEvent, BoostEvent, ConfigEvent, DerivativeTokenEvent,
+        DerivativeTransferEvent, FeeEvent, ForceUnstakeEvent, ListingEvent, NftUserEvent,
+        ReceiptCollectionEvent, ReleaseEscrowEvent, RewardAccountEvent, RewardTokenEvent,
+        RewardWeightEvent, SaleEvent, StatusEvent,
+    },
+    helpers::{
+        burn_receipt_message, fee_messages, mint_derivative_message, mint_receipt_message,
+        redeem_derivative_message, reply_on_reward_account_error, send_asset_message,
+        setup_stake_change_messages, LoyaltyPromotions, StakerNfts, UpdateRewardWeightResult,
+        UpdateStakeResult,
+    },
+    msg::ReceiveMsg,
+    ownership::{Ownership, OwnershipEvent, OwnershipInfo},
+    roles::{Role, RoleEvent, Roles},
+    state::{
+        validate_boost_collections, validate_collection_unstaking_durations,
+        validate_loyalty_tiers, Airdrop, AirdropShare, BoostCollection, CollectedFee,
+        CollectionUnstakingDuration, Config, ConfigResponse, EscrowedNft, Fee, FeeAsset, FeeKind,
+        FeeRecipient, Listing, Loyalty, LoyaltyPromotion, LoyaltyTier, Nft, NftUser, PendingNftsId,
+        RewardWeight, SaleFee, StakedNft, StakedNftId, StakedNftIndices, StakingPower, Status,
+    },
 };
 
 pub const CONTRACT_NAME: &str = env!("CARGO_PKG_NAME");
@@ -39,13 +67,40 @@ pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 pub const MAX_CLAIMS: usize = 100;
 pub const MAX_NFTS: usize = 20;
 
+/// Reward weight of a staked set without any bonus
+pub const BASIS_POINTS: u64 = 10_000;
+
+/// Reply id of the reward account checkpoints allowed to fail on emergency unstakes
+pub const EMERGENCY_STAKE_CHANGE_REPLY_ID: u64 = 1;
+
 pub struct NftVaultContract {
     pub config: Item<Config<Addr>>,
     pub reward_accounts: Item<Vec<Addr>>,
     pub users_staked_nfts: IndexedMap<StakedNftId, StakedNft, StakedNftIndices>,
     pub users_collection_staked_amounts: Map<(Addr, Addr), u64>,
     pub total_staked_amount: SnapshotItem<Uint128>,
+    pub users_staked_amounts: SnapshotMap<Addr, Uint128>,
     pub claims: Claims,
+    pub escrowed_nfts: Map<StakedNftId, EscrowedNft>,
+    pub receipt_collection: Item<Addr>,
+    pub derivative_token: Item<Addr>,
+    pub derivative_balances: Map<Addr, Uint128>,
+    pub derivative_supply: Item<Uint128>,
+    pub derivative_debts: Map<Addr, Uint128>,
+    pub ownership: Ownership,
+    pub roles: Roles,
+    pub status: Item<Status>,
+    pub users_pending_nfts: Map<PendingNftsId, Vec<Nft<Addr>>>,
+    pub users_reward_weights: Map<Addr, Uint128>,
+    pub total_reward_weight: Item<Uint128>,
+    pub collected_fees: Map<String, CollectedFee>,
+    pub users_boosts: Map<Addr, Vec<Nft<Addr>>>,
+    pub airdrops: Map<u64, Airdrop>,
+    pub airdrop_claims: Map<(u64, Addr), Uint128>,
+    pub listings: Map<StakedNftId, Listing>,
+    pub users_loyalty: Map<Addr, Loyalty>,
+    pub users_loyalty_promotions: Map<(Addr, u64), Vec<LoyaltyPromotion>>,
+    pub reward_tokens: Map<Addr, Empty>,
 }
 
 #[cfg(not(feature = "library"))]
@@ -70,7 +125,28 @@ impl NftVaultContract {
             users_staked_nfts: IndexedMap::new("n", indexes),
             users_collection_staked_amounts: Map::new("U"),
             total_staked_amount: SnapshotItem::new("t", "t_p", "t_l", Strategy::EveryBlock),
+            users_staked_amounts: SnapshotMap::new("u", "u_p", "u_l", Strategy::EveryBlock),
             claims: Claims::new("A"),
+            escrowed_nfts: Map::new("E"),
+            receipt_collection: Item::new("P"),
+            derivative_token: Item::new("D"),
+            derivative_balances: Map::new("B"),
+            derivative_supply: Item::new("S"),
+            derivative_debts: Map::new("Q"),
+            ownership: Ownership::new("O"),
+            roles: Roles::new("L"),
+            status: Item::new("X"),
+            users_pending_nfts: Map::new("W"),
+            users_reward_weights: Map::new("G"),
+            total_reward_weight: Item::new("T"),
+            collected_fees: Map::new("F"),
+            users_boosts: Map::new("K"),
+            airdrops: Map::new("Y"),
+            airdrop_claims: Map::new("Z"),
+            listings: Map::new("M"),
+            users_loyalty: Map::new("H"),
+            users_loyalty_promotions: Map::new("J"),
+            reward_tokens: Map::new("V"),
         }
     }
 
@@ -79,6 +155,7 @@ impl NftVaultContract {
         &self,
         ctx: InstantiateCtx,
         config: Config<String>,
+        owner: Option<String>,
     ) -> Result<Response, ContractError> {
         nonpayable(&ctx.info)?;
 
@@ -87,15 +164,31 @@ impl NftVaultContract {
         let config = config.str_to_addr(ctx.deps.api)?;
         self.config.save(ctx.deps.storage, &config)?;
 
+        let owner = address_or(&ctx.info.sender, maybe_addr(ctx.deps.api, owner)?.as_ref());
+        let ownership = self.ownership.initialize(ctx.deps.storage, &owner)?;
+
         self.reward_accounts.save(ctx.deps.storage, &vec![])?;
 
+        self.validate_fee(ctx.deps.as_ref(), config.stake_fee.as_ref())?;
+        self.validate_fee(ctx.deps.as_ref(), config.unstake_fee.as_ref())?;
+
+        self.status.save(ctx.deps.storage, &Status::default())?;
+
+        self.total_reward_weight
+            .save(ctx.deps.storage, &Uint128::zero())?;
+
         self.total_staked_amount
             .save(ctx.deps.storage, &Uint128::zero(), ctx.env.block.height)?;
 
-        let response = Response::new().add_event(ConfigEvent {
-            ty: "set-config",
-            config: &config,
-        });
+        let response = Response::new()
+            .add_event(ConfigEvent {
+                ty: "set-config",
+                config: &config,
+            })
+            .add_event(OwnershipEvent {
+                ty: "set-ownership",
+                ownership: &ownership,
+            });
 
         Ok(response)
     }
@@ -106,10 +199,20 @@ impl NftVaultContract {
         ctx: ExecCtx,
         rewards_code_id: Option<u64>,
         unstaking_duration_sec: Option<u64>,
+        receipt_code_id: Option<u64>,
+        derivative_code_id: Option<u64>,
+        warmup_duration_sec: Option<u64>,
+        loyalty_tiers: Option<Vec<LoyaltyTier>>,
+        collection_unstaking_durations: Option<Vec<CollectionUnstakingDuration<String>>>,
     ) -> Result<Response, ContractError> {
         nonpayable(&ctx.info)?;
 
-        only_contract_admin(&ctx.deps.querier, &ctx.info, &ctx.env)?;
+        self.roles.assert_owner_or_role(
+            ctx.deps.storage,
+            &self.ownership,
+            &ctx.info.sender,
+            Role::ConfigManager,
+        )?;
 
         let mut config = self.config.load(ctx.deps.storage)?;
 
@@ -121,6 +224,30 @@ impl NftVaultContract {
             config.unstaking_duration_sec = unstaking_duration_sec;
         }
 
+        if let Some(receipt_code_id) = receipt_code_id {
+            config.receipt_code_id = Some(receipt_code_id);
+        }
+
+        if let Some(derivative_code_id) = derivative_code_id {
+            config.derivative_code_id = Some(derivative_code_id);
+        }
+
+        if let Some(warmup_duration_sec) = warmup_duration_sec {
+            config.warmup_duration_sec = warmup_duration_sec;
+        }
+
+        if let Some(loyalty_tiers) = loyalty_tiers {
+            config.loyalty_tiers = validate_loyalty_tiers(loyalty_tiers)?;
+        }
+
+        if let Some(collection_unstaking_durations) = collection_unstaking_durations {
+            config.collection_unstaking_durations = validate_collection_unstaking_durations(
+                ctx.deps.api,
+                &config.collections,
+                collection_unstaking_durations,
+            )?;
+        }
+
         self.config.save(ctx.deps.storage, &config)?;
 
         let response = Response::new().add_event(ConfigEvent {
@@ -132,86 +259,243 @@ impl NftVaultContract {
     }
 
     #[sv::msg(exec)]
-    pub fn create_reward_account(
+    pub fn update_fees(
         &self,
         ctx: ExecCtx,
+        stake_fee: Option<Fee<String>>,
+        unstake_fee: Option<Fee<String>>,
+        sale_fee: Option<SaleFee<String>>,
+        remove_fees: Option<Vec<FeeKind>>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.roles.assert_owner_or_role(
+            ctx.deps.storage,
+            &self.ownership,
+            &ctx.info.sender,
+            Role::ConfigManager,
+        )?;
+
+        let remove_fees = remove_fees.unwrap_or_default();
+        ensure!(
+            !(stake_fee.is_some() && remove_fees.contains(&FeeKind::Stake)
+                || unstake_fee.is_some() && remove_fees.contains(&FeeKind::Unstake)
+                || sale_fee.is_some() && remove_fees.contains(&FeeKind::Sale)),
+            CommonError::InvalidInput("fee cannot be set and removed at once".to_string())
+        );
+
+        let mut config = self.config.load(ctx.deps.storage)?;
+
+        if let Some(stake_fee) = stake_fee {
+            config.stake_fee = Some(stake_fee.str_to_addr(ctx.deps.api)?);
+        }
+
+        if let Some(unstake_fee) = unstake_fee {
+            config.unstake_fee = Some(unstake_fee.str_to_addr(ctx.deps.api)?);
+        }
+
+        if let Some(sale_fee) = sale_fee {
+            config.sale_fee = Some(sale_fee.str_to_addr(ctx.deps.api)?);
+        }
+
+        for fee_kind in remove_fees {
+            match fee_kind {
+                FeeKind::Stake => config.stake_fee = None,
+                FeeKind::Unstake => config.unstake_fee = None,
+                FeeKind::Sale => config.sale_fee = None,
+            }
+        }
+
+        self.validate_fee(ctx.deps.as_ref(), config.stake_fee.as_ref())?;
+        self.validate_fee(ctx.deps.as_ref(), config.unstake_fee.as_ref())?;
+
+        self.config.save(ctx.deps.storage, &config)?;
+
+        let response = Response::new().add_event(ConfigEvent {
+            ty: "update-config",
+            config: &config,
+        });
+
+        Ok(response)
+    }
+
+    #[sv::msg(exec)]
+    pub fn create_reward_account(
+        &self,
+        mut ctx: ExecCtx,
         label: String,
         reward_asset: RewardAsset,
-        period_start: Timestamp,
-        duration_sec: u64,
+        schedule: Vec<EmissionSegment>,
+        curve: Option<EmissionCurve>,
+        undistributed_mode: UndistributedMode,
+        grace_period_sec: u64,
+        vesting: Option<Vesting>,
+        accounting: Option<Accounting>,
     ) -> Result<Response, ContractError> {
-        only_contract_admin(&ctx.deps.querier, &ctx.info, &ctx.env)?;
+        self.roles.assert_owner_or_role(
+            ctx.deps.storage,
+            &self.ownership,
+            &ctx.info.sender,
+            Role::RewardManager,
+        )?;
 
-        let fund_amount = match &reward_asset {
+        match &reward_asset {
             RewardAsset::Native(denom) => must_pay(&ctx.info, &denom)?,
-            RewardAsset::Cw20(cw20) => {
-                let balance_response: BalanceResponse = ctx.deps.querier.query_wasm_smart(
-                    cw20.to_string(),
-                    &Cw20QueryMsg::Balance {
-                        address: ctx.env.contract.address.to_string(),
-                    },
-                )?;
-                balance_response.balance
+            RewardAsset::Cw20(_) => {
+                return Err(CommonError::InvalidInput(
+                    "cw20 reward accounts are created by sending the tokens to the vault"
+                        .to_string(),
+                )
+                .into())
             }
         };
 
-        let config = self.config.load(ctx.deps.storage)?;
-        let mut reward_accounts = self.reward_accounts.load(ctx.deps.storage)?;
+        let (reward_contract_addr, instantiate_msg) = self.reward_account_instantiate_msg(
+            ctx.deps.branch(),
+            &ctx.env,
+            label,
+            StakeRewardsInstantiateMsg {
+                stake: ctx.env.contract.address.to_string(),
+                reward_asset,
+                schedule,
+                curve,
+                undistributed_mode,
+                funder: Some(ctx.info.sender.to_string()),
+                grace_period_sec,
+                accounting: accounting.unwrap_or(Accounting::Pull),
+                vesting,
+                cw20_amount: None,
+            },
+            ctx.info.funds,
+        )?;
 
-        let salt = generate_salt(vec![
-            ctx.env.contract.address.to_string().as_bytes(),
-            (reward_accounts.len() as u64).to_be_bytes().as_ref(),
-        ]);
+        Ok(Response::new()
+            .add_event(RewardAccountEvent {
+                ty: "create-reward-account",
+                address: &reward_contract_addr.to_string(),
+            })
+            .add_message(instantiate_msg))
+    }
 
-        let reward_contract_addr = generate_instantiate_2_addr(
-            &ctx.deps.as_ref(),
-            &ctx.env.contract.address,
-            config.rewards_code_id,
-            &salt,
-        )?;
+    /// Cw20 receive hook, creates or funds a reward account paying out the sent token with
+    /// exactly the sent amount
+    #[sv::msg(exec)]
+    pub fn receive(
+        &self,
+        mut ctx: ExecCtx,
+        sender: String,
+        amount: Uint128,
+        msg: Binary,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
 
-        reward_accounts.push(reward_contract_addr.clone());
-        self.reward_accounts
-            .save(ctx.deps.storage, &reward_accounts)?;
+        let sender = ctx.deps.api.addr_validate(&sender)?;
+        let reward_asset = RewardAsset::Cw20(ctx.info.sender.clone());
 
-        let instantiate_msg = WasmMsg::Instantiate2 {
-            admin: Some(ctx.env.contract.address.to_string()),
-            code_id: config.rewards_code_id,
-            label: label.to_string(),
-            msg: to_json_binary(&StakeRewardsInstantiateMsg {
-                stake: ctx.env.contract.address.to_string(),
-                reward_asset: reward_asset.clone(),
-                period_start,
-                duration_sec,
-            })?,
-            funds: ctx.info.funds,
-            salt,
-        };
+        match from_json(&msg)? {
+            ReceiveMsg::CreateRewardAccount {
+                label,
+                schedule,
+                curve,
+                undistributed_mode,
+                grace_period_sec,
+                vesting,
+                accounting,
+            } => {
+                ensure!(
+                    self.reward_tokens
+                        .has(ctx.deps.storage, ctx.info.sender.clone()),
+                    CommonError::Unauthorized("sender is not an approved reward token".to_string())
+                );
+                self.roles.assert_owner_or_role(
+                    ctx.deps.storage,
+                    &self.ownership,
+                    &sender,
+                    Role::RewardManager,
+                )?;
 
-        let mut response = Response::new().add_event(RewardAccountEvent {
-            ty: "create-reward-account",
-            address: &reward_contract_addr.to_string(),
-        });
+                let (reward_contract_addr, instantiate_msg) = self.reward_account_instantiate_msg(
+                    ctx.deps.branch(),
+                    &ctx.env,
+                    label,
+                    StakeRewardsInstantiateMsg {
+                        stake: ctx.env.contract.address.to_string(),
+                        reward_asset,
+                        schedule,
+                        curve,
+                        undistributed_mode,
+                        funder: Some(sender.to_string()),
+                        grace_period_sec,
+                        accounting: accounting.unwrap_or(Accounting::Pull),
+                        vesting,
+                        cw20_amount: Some(amount),
+                    },
+                    vec![],
+                )?;
 
-        if let RewardAsset::Cw20(cw20) = &reward_asset {
-            response = response.add_message(WasmMsg::Execute {
-                contract_addr: cw20.to_string(),
-                msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
-                    recipient: reward_contract_addr.to_string(),
-                    amount: fund_amount,
-                })?,
-                funds: vec![],
-            });
-        }
+                // The tokens must be in place before the reward account checks its funding
+                Ok(Response::new()
+                    .add_event(RewardAccountEvent {
+                        ty: "create-reward-account",
+                        address: reward_contract_addr.as_str(),
+                    })
+                    .add_message(WasmMsg::Execute {
+                        contract_addr: ctx.info.sender.to_string(),
+                        msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                            recipient: reward_contract_addr.to_string(),
+                            amount,
+                        })?,
+                        funds: vec![],
+                    })
+                    .add_message(instantiate_msg))
+            }
+            ReceiveMsg::FundRewardAccount {
+                reward_account,
+                extra_duration_sec,
+            } => {
+                let reward_account = ctx.deps.api.addr_validate(&reward_account)?;
+                ensure!(
+                    self.reward_accounts
+                        .load(ctx.deps.storage)?
+                        .contains(&reward_account),
+                    ContractError::RewardAccountNotFound
+                );
 
-        response = response.add_message(instantiate_msg);
+                let distributions: Vec<Distribution> = ctx
+                    .deps
+                    .querier
+                    .query_wasm_smart(&reward_account, &StakeRewardsQueryMsg::Distributions {})?;
+                ensure!(
+                    distributions
+                        .iter()
+                        .any(|distribution| distribution.reward_asset == reward_asset),
+                    CommonError::Unauthorized(
+                        "sender is not the reward token of the reward account".to_string()
+                    )
+                );
 
-        Ok(response)
+                Ok(Response::new()
+                    .add_event(RewardAccountEvent {
+                        ty: "fund-reward-account",
+                        address: reward_account.as_str(),
+                    })
+                    .add_message(WasmMsg::Execute {
+                        contract_addr: ctx.info.sender.to_string(),
+                        msg: to_json_binary(&Cw20ExecuteMsg::Send {
+                            contract: reward_account.to_string(),
+                            amount,
+                            msg: to_json_binary(&StakeRewardsReceiveMsg::Fund {
+                                extra_duration_sec,
+                            })?,
+                        })?,
+                        funds: vec![],
+                    }))
+            }
+        }
     }
 
     #[sv::msg(exec)]
     pub fn stake(&self, ctx: ExecCtx, nfts: Vec<Nft<String>>) -> Result<Response, ContractError> {
-        nonpayable(&ctx.info)?;
+        self.assert_not_paused(ctx.deps.storage, false)?;
 
         ensure!(
             !nfts.is_empty(),
@@ -226,10 +510,27 @@ impl NftVaultContract {
 
         let config = self.config.load(ctx.deps.storage)?;
         let reward_accounts = self.reward_accounts.load(ctx.deps.storage)?;
+        let receipt_collection = self.receipt_collection.may_load(ctx.deps.storage)?;
 
-        let mut response = Response::new();
+        let mut response = self.collect_fee(
+            ctx.deps.storage,
+            &ctx.info,
+            config.stake_fee.as_ref(),
+            "stake",
+            nfts.len(),
+        )?;
 
-        let mut collection_deltas: HashMap<Addr, i64> = HashMap::new();
+        // NFTs that completed the warm-up period are activated along with the stake
+        let StakerNfts {
+            mut collection_deltas,
+            nfts: activated_nfts,
+        } = self.activate_nfts(ctx.deps.storage, &ctx.env, &config, &sender)?;
+        if !activated_nfts.is_empty() {
+            response = response.add_event(ActivateNftsEvent {
+                staker: &sender,
+                nfts: &activated_nfts,
+            });
+        }
 
         let internal_nfts = nfts
             .into_iter()
@@ -242,9 +543,21 @@ impl NftVaultContract {
                 CommonError::InvalidInput("collection not allowed".to_string())
             );
 
-            // Update collection count
-            let count = collection_deltas.entry(nft.collection.clone()).or_insert(0);
-            *count = count.checked_add(1).unwrap();
+            // Update collection count, NFTs only count once warmed up
+            if config.warmup_duration_sec == 0 {
+                let count = collection_deltas.entry(nft.collection.clone()).or_insert(0);
+                *count = count.checked_add(1).unwrap();
+                self.add_active_nft(
+                    ctx.deps.storage,
+                    &ctx.env,
+                    &config,
+                    &sender,
+                    &nft,
+                    ctx.env.block.time,
+                )?;
+            } else {
+                self.add_pending_nft(ctx.deps.storage, &sender, ctx.env.block.time, &nft)?;
+            }
 
             // Check owner and transfer NFT to contract
             only_owner(&ctx.deps.querier, &sender, &nft.collection, &nft.token_id)?;
@@ -254,6 +567,15 @@ impl NftVaultContract {
                 &ctx.env.contract.address,
             ));
 
+            // Mint a receipt representing the staked position
+            if let Some(receipt_collection) = &receipt_collection {
+                response = response.add_submessage(mint_receipt_message(
+                    receipt_collection,
+                    &nft,
+                    &sender,
+                )?);
+            }
+
             // Save staked NFT
             self.users_staked_nfts.save(
                 ctx.deps.storage,
@@ -261,14 +583,14 @@ impl NftVaultContract {
                 &StakedNft {
                     staker: sender.clone(),
                     nft: nft,
+                    has_receipt: receipt_collection.is_some(),
+                    staked_at: ctx.env.block.time,
+                    user: None,
                 },
             )?;
         }
 
-        let UpdateStakeResult {
-            user_staked_amount,
-            total_staked_amount,
-        } = self.update_stake_amounts(
+        let update_stake_result = self.update_stake_amounts(
             ctx.deps.storage,
             &ctx.env,
             config,
@@ -277,11 +599,13 @@ impl NftVaultContract {
         )?;
 
         // Setup the stake change messages with the previous staked amount and total staked amount
-        let stake_change_msgs = setup_stake_change_messages(
+        let stake_change_msgs = self.stake_change_messages(
+            ctx.deps.storage,
+            &ctx.env,
             &reward_accounts,
             &sender,
-            user_staked_amount,
-            total_staked_amount,
+            &update_stake_result,
+            true,
         )?;
 
         response = response.add_submessages(stake_change_msgs);
@@ -291,7 +615,7 @@ impl NftVaultContract {
 
     #[sv::msg(exec)]
     pub fn unstake(&self, ctx: ExecCtx, nfts: Vec<Nft<String>>) -> Result<Response, ContractError> {
-        nonpayable(&ctx.info)?;
+        self.assert_not_paused(ctx.deps.storage, true)?;
 
         ensure!(
             !nfts.is_empty(),
@@ -302,428 +626,5679 @@ impl NftVaultContract {
             CommonError::InvalidInput("too many nfts to unstake".to_string())
         );
 
-        let sender = ctx.info.sender.clone();
-
-        let config = self.config.load(ctx.deps.storage)?;
-        let reward_accounts = self.reward_accounts.load(ctx.deps.storage)?;
-
-        let mut collection_deltas: HashMap<Addr, i64> = HashMap::new();
-
         let internal_nfts = nfts
             .into_iter()
             .map(|nft| nft.str_to_addr(ctx.deps.api))
             .collect::<Result<Vec<Nft<cosmwasm_std::Addr>>, ContractError>>()?;
 
-        for nft in &internal_nfts {
-            // Update collection count
-            let count = collection_deltas.entry(nft.collection.clone()).or_insert(0);
-            *count = count.checked_sub(1).unwrap();
-
-            let staked_nft = self.users_staked_nfts.may_load(
-                ctx.deps.storage,
-                (nft.collection.clone(), nft.token_id.clone()),
-            )?;
-            ensure!(
-                staked_nft.is_some(),
-                CommonError::InvalidInput("nft not staked".to_string())
-            );
-            ensure!(
-                staked_nft.unwrap().staker == sender,
-                CommonError::Unauthorized("nft not staked by sender".to_string())
-            );
+        // Exits in emergency mode are free
+        let config = self.config.load(ctx.deps.storage)?;
+        let unstake_fee = match self.status.load(ctx.deps.storage)?.emergency_mode {
+            true => None,
+            false => config.unstake_fee.as_ref(),
+        };
 
-            // Remove staked NFT
-            self.users_staked_nfts.remove(
-                ctx.deps.storage,
-                (nft.collection.clone(), nft.token_id.clone()),
-            )?;
-        }
+        let fee_response = self.collect_fee(
+            ctx.deps.storage,
+            &ctx.info,
+            unstake_fee,
+            "unstake",
+            internal_nfts.len(),
+        )?;
 
-        // Create a claim for the unstaked nfts
-        self.claims.create_claim(
+        let response = self.unstake_nfts(
             ctx.deps.storage,
-            &sender,
+            &ctx.env,
+            &ctx.info.sender,
             internal_nfts,
-            Expiration::AtTime(
-                ctx.env
-                    .block
-                    .time
-                    .plus_seconds(config.unstaking_duration_sec),
-            ),
+            true,
         )?;
 
-        let UpdateStakeResult {
-            user_staked_amount,
-            total_staked_amount,
-        } = self.update_stake_amounts(
+        Ok(fee_response
+            .add_submessages(response.messages)
+            .add_events(response.events))
+    }
+
+    #[sv::msg(exec)]
+    pub fn activate(
+        &self,
+        ctx: ExecCtx,
+        staker: Option<String>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.assert_not_paused(ctx.deps.storage, false)?;
+
+        let staker = address_or(&ctx.info.sender, maybe_addr(ctx.deps.api, staker)?.as_ref());
+
+        let config = self.config.load(ctx.deps.storage)?;
+        let reward_accounts = self.reward_accounts.load(ctx.deps.storage)?;
+
+        let StakerNfts {
+            collection_deltas,
+            nfts: activated_nfts,
+        } = self.activate_nfts(ctx.deps.storage, &ctx.env, &config, &staker)?;
+        ensure!(
+            !activated_nfts.is_empty(),
+            CommonError::InvalidInput("no nfts to activate".to_string())
+        );
+
+        let update_stake_result = self.update_stake_amounts(
             ctx.deps.storage,
             &ctx.env,
             config,
-            &sender,
+            &staker,
             collection_deltas,
         )?;
 
-        // Setup the stake change messages with the previous staked amount and total staked amount
-        let stake_change_msgs = setup_stake_change_messages(
+        let stake_change_msgs = self.stake_change_messages(
+            ctx.deps.storage,
+            &ctx.env,
             &reward_accounts,
-            &sender,
-            user_staked_amount,
-            total_staked_amount,
+            &staker,
+            &update_stake_result,
+            true,
         )?;
 
-        let response = Response::new().add_submessages(stake_change_msgs);
+        let response = Response::new()
+            .add_event(ActivateNftsEvent {
+                staker: &staker,
+                nfts: &activated_nfts,
+            })
+            .add_submessages(stake_change_msgs);
 
         Ok(response)
     }
 
     #[sv::msg(exec)]
-    pub fn claim(
-        &self,
-        ctx: ExecCtx,
-        recipient: Option<String>,
-    ) -> Result<Response, ContractError> {
+    pub fn poke(&self, ctx: ExecCtx, staker: Option<String>) -> Result<Response, ContractError> {
         nonpayable(&ctx.info)?;
+        self.assert_not_paused(ctx.deps.storage, false)?;
 
-        let sender = ctx.info.sender.clone();
-        let recipient = address_or(&sender, maybe_addr(ctx.deps.api, recipient)?.as_ref());
-
-        let claimable_nfts =
-            self.claims
-                .claim_tokens(ctx.deps.storage, &sender, &ctx.env.block, None)?;
         ensure!(
-            !claimable_nfts.is_empty(),
-            ContractError::ClaimableNftsNotFound
+            !self.derivative_token.exists(ctx.deps.storage),
+            CommonError::InvalidInput("rewards follow the derivative token".to_string())
         );
 
-        let mut response = Response::new();
+        let staker = address_or(&ctx.info.sender, maybe_addr(ctx.deps.api, staker)?.as_ref());
+        let reward_accounts = self.reward_accounts.load(ctx.deps.storage)?;
 
-        for nft in &claimable_nfts {
-            response =
-                response.add_submessage(transfer_nft(&nft.collection, &nft.token_id, &recipient));
-        }
+        let UpdateRewardWeightResult {
+            user_reward_weight,
+            total_reward_weight,
+            next_user_reward_weight,
+        } = self.update_reward_weight(ctx.deps.storage, &ctx.env, &staker)?;
+        ensure!(
+            user_reward_weight != next_user_reward_weight,
+            CommonError::InvalidInput("reward weight is up to date".to_string())
+        );
 
-        response = response.add_event(Event::new("claim-unstaked".to_string()).add_attributes(
-            vec![
-                    attr("sender", sender.to_string()),
-                    attr(
-                        "nfts",
-                        claimable_nfts
-                            .iter()
-                            .map(|nft| nft.to_string())
-                            .collect::<Vec<String>>()
-                            .join(","),
-                    ),
-                ],
-        ));
+        let response = Response::new()
+            .add_event(RewardWeightEvent {
+                staker: &staker,
+                reward_weight: next_user_reward_weight,
+                total_reward_weight: self.total_reward_weight.load(ctx.deps.storage)?,
+            })
+            .add_submessages(setup_stake_change_messages(
+                &reward_accounts,
+                &staker,
+                user_reward_weight,
+                total_reward_weight,
+            )?);
 
         Ok(response)
     }
 
     #[sv::msg(exec)]
-    pub fn claim_rewards(
+    pub fn update_boosts(
         &self,
         ctx: ExecCtx,
-        recipient: Option<String>,
+        boost_collections: Vec<BoostCollection<String>>,
+        max_boosts: u32,
     ) -> Result<Response, ContractError> {
         nonpayable(&ctx.info)?;
+        self.roles.assert_owner_or_role(
+            ctx.deps.storage,
+            &self.ownership,
+            &ctx.info.sender,
+            Role::ConfigManager,
+        )?;
 
-        let sender = ctx.info.sender.clone();
-        let recipient = address_or(&sender, maybe_addr(ctx.deps.api, recipient)?.as_ref());
+        let mut config = self.config.load(ctx.deps.storage)?;
 
-        let config = self.config.load(ctx.deps.storage)?;
-        let reward_accounts = self.reward_accounts.load(ctx.deps.storage)?;
+        config.boost_collections =
+            validate_boost_collections(ctx.deps.api, &config.collections, boost_collections)?;
+        config.max_boosts = max_boosts;
 
-        let empty_collection_deltas: HashMap<Addr, i64> = HashMap::new();
-        let UpdateStakeResult {
-            user_staked_amount,
-            total_staked_amount,
-        } = self.update_stake_amounts(
-            ctx.deps.storage,
-            &ctx.env,
-            config,
-            &sender,
-            empty_collection_deltas,
-        )?;
+        self.config.save(ctx.deps.storage, &config)?;
 
-        let claim_json = to_json_binary(&PassageRewardsExecuteMsg::ClaimRewards {
-            recipient: recipient.to_string(),
-            staked_amount: user_staked_amount,
-            total_staked: total_staked_amount,
-        })?;
+        let response = Response::new().add_event(ConfigEvent {
+            ty: "update-config",
+            config: &config,
+        });
 
-        let claim_msgs = reward_accounts
-            .iter()
-            .map(|addr| {
-                SubMsg::new(WasmMsg::Execute {
-                    contract_addr: addr.to_string(),
-                    msg: claim_json.clone(),
-                    funds: vec![],
-                })
+        Ok(response)
+    }
+
+    #[sv::msg(exec)]
+    pub fn lock_boost(&self, ctx: ExecCtx, nft: Nft<String>) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.assert_not_paused(ctx.deps.storage, false)?;
+
+        ensure!(
+            !self.derivative_token.exists(ctx.deps.storage),
+            CommonError::InvalidInput("rewards follow the derivative token".to_string())
+        );
+
+        let sender = ctx.info.sender.clone();
+        let config = self.config.load(ctx.deps.storage)?;
+        let nft = nft.str_to_addr(ctx.deps.api)?;
+
+        ensure!(
+            config
+                .boost_collections
+                .iter()
+                .any(|bc| bc.collection == nft.collection),
+            CommonError::InvalidInput("collection does not boost".to_string())
+        );
+
+        let mut boosts = self
+            .users_boosts
+            .may_load(ctx.deps.storage, sender.clone())?
+            .unwrap_or_default();
+        ensure!(
+            boosts.len() < config.max_boosts as usize,
+            CommonError::InvalidInput("no boost slot left".to_string())
+        );
+
+        // Check owner and transfer NFT to contract
+        only_owner(&ctx.deps.querier, &sender, &nft.collection, &nft.token_id)?;
+
+        boosts.push(nft.clone());
+        self.users_boosts
+            .save(ctx.deps.storage, sender.clone(), &boosts)?;
+
+        let response = self
+            .sync_reward_weight(ctx.deps.storage, &ctx.env, &sender)?
+            .add_event(BoostEvent {
+                ty: "lock-boost",
+                staker: &sender,
+                nft: &nft,
             })
-            .collect::<Vec<SubMsg>>();
+            .add_submessage(transfer_nft(
+                &nft.collection,
+                &nft.token_id,
+                &ctx.env.contract.address,
+            ));
 
-        let response = Response::new()
-            .add_event(
-                Event::new("claim-rewards".to_string())
-                    .add_attributes(vec![attr("sender", sender.to_string())]),
-            )
-            .add_submessages(claim_msgs);
+        Ok(response)
+    }
+
+    #[sv::msg(exec)]
+    pub fn unlock_boost(&self, ctx: ExecCtx, nft: Nft<String>) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.assert_not_paused(ctx.deps.storage, true)?;
+
+        ensure!(
+            !self.derivative_token.exists(ctx.deps.storage),
+            CommonError::InvalidInput("rewards follow the derivative token".to_string())
+        );
+
+        let sender = ctx.info.sender.clone();
+        let nft = nft.str_to_addr(ctx.deps.api)?;
+
+        let mut boosts = self
+            .users_boosts
+            .may_load(ctx.deps.storage, sender.clone())?
+            .unwrap_or_default();
+        let index = boosts
+            .iter()
+            .position(|boost| boost == &nft)
+            .ok_or_else(|| CommonError::InvalidInput("nft not locked by sender".to_string()))?;
+        boosts.remove(index);
+
+        if boosts.is_empty() {
+            self.users_boosts.remove(ctx.deps.storage, sender.clone());
+        } else {
+            self.users_boosts
+                .save(ctx.deps.storage, sender.clone(), &boosts)?;
+        }
+
+        // Boosts are returned right away, without an unstaking period
+        let mut response = Response::new();
+        if !self.status.load(ctx.deps.storage)?.emergency_mode {
+            response = self.sync_reward_weight(ctx.deps.storage, &ctx.env, &sender)?;
+        }
+
+        response = response
+            .add_event(BoostEvent {
+                ty: "unlock-boost",
+                staker: &sender,
+                nft: &nft,
+            })
+            .add_submessage(transfer_nft(&nft.collection, &nft.token_id, &sender));
 
         Ok(response)
     }
 
-    #[sv::msg(query)]
-    pub fn config(&self, ctx: QueryCtx) -> StdResult<Config<Addr>> {
-        self.config.load(ctx.deps.storage)
+    /// Lends the usage rights of a staked NFT until they expire, the staker keeps earning
+    /// rewards. Passing no user revokes them.
+    #[sv::msg(exec)]
+    pub fn set_nft_user(
+        &self,
+        ctx: ExecCtx,
+        nft: Nft<String>,
+        user: Option<NftUser<String>>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.assert_not_paused(ctx.deps.storage, false)?;
+
+        let nft = nft.str_to_addr(ctx.deps.api)?;
+        let staked_nft_id = (nft.collection.clone(), nft.token_id.clone());
+        let mut staked_nft = self
+            .users_staked_nfts
+            .may_load(ctx.deps.storage, staked_nft_id.clone())?
+            .ok_or_else(|| CommonError::InvalidInput("nft not staked".to_string()))?;
+        ensure!(
+            staked_nft.staker == ctx.info.sender,
+            CommonError::Unauthorized("sender is not the staker".to_string())
+        );
+
+        staked_nft.user = match user {
+            Some(NftUser { user, expires }) => {
+                ensure!(
+                    expires > ctx.env.block.time,
+                    CommonError::InvalidInput("expires is in the past".to_string())
+                );
+                Some(NftUser {
+                    user: ctx.deps.api.addr_validate(&user)?,
+                    expires,
+                })
+            }
+            None => None,
+        };
+        self.users_staked_nfts
+            .save(ctx.deps.storage, staked_nft_id, &staked_nft)?;
+
+        let response = Response::new().add_event(NftUserEvent {
+            staker: &ctx.info.sender,
+            nft: &nft,
+            user: staked_nft.user.as_ref(),
+        });
+
+        Ok(response)
     }
 
-    #[sv::msg(query)]
-    pub fn reward_accounts(&self, ctx: QueryCtx) -> StdResult<Vec<Addr>> {
-        self.reward_accounts.load(ctx.deps.storage)
+    /// Offers a staked NFT for sale until it expires, never by default, replacing any previous ask
+    #[sv::msg(exec)]
+    pub fn list_nft(
+        &self,
+        ctx: ExecCtx,
+        nft: Nft<String>,
+        price: Coin,
+        expires: Option<Expiration>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.assert_not_paused(ctx.deps.storage, false)?;
+
+        ensure!(
+            !price.amount.is_zero(),
+            CommonError::InvalidInput("price must be greater than zero".to_string())
+        );
+        let expires = expires.unwrap_or_default();
+        ensure!(
+            !expires.is_expired(&ctx.env.block),
+            CommonError::InvalidInput("expires is in the past".to_string())
+        );
+
+        let nft = nft.str_to_addr(ctx.deps.api)?;
+        let staked_nft_id = (nft.collection.clone(), nft.token_id.clone());
+        let staked_nft = self
+            .users_staked_nfts
+            .may_load(ctx.deps.storage, staked_nft_id.clone())?
+            .ok_or_else(|| CommonError::InvalidInput("nft not staked".to_string()))?;
+        ensure!(
+            staked_nft.staker == ctx.info.sender,
+            CommonError::Unauthorized("sender is not the staker".to_string())
+        );
+
+        let listing = Listing {
+            seller: ctx.info.sender.clone(),
+            nft,
+            price,
+            expires,
+        };
+        self.listings
+            .save(ctx.deps.storage, staked_nft_id, &listing)?;
+
+        let response = Response::new().add_event(ListingEvent {
+            ty: "list-nft",
+            listing: &listing,
+        });
+
+        Ok(response)
     }
 
-    #[sv::msg(query)]
-    pub fn users_staked_nfts(
+    #[sv::msg(exec)]
+    pub fn delist_nft(&self, ctx: ExecCtx, nft: Nft<String>) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let nft = nft.str_to_addr(ctx.deps.api)?;
+        let staked_nft_id = (nft.collection.clone(), nft.token_id.clone());
+        let listing = self
+            .listings
+            .may_load(ctx.deps.storage, staked_nft_id.clone())?
+            .ok_or_else(|| CommonError::InvalidInput("nft not listed".to_string()))?;
+        ensure!(
+            listing.seller == ctx.info.sender,
+            CommonError::Unauthorized("sender is not the seller".to_string())
+        );
+
+        self.listings.remove(ctx.deps.storage, staked_nft_id);
+
+        let response = Response::new().add_event(ListingEvent {
+            ty: "delist-nft",
+            listing: &listing,
+        });
+
+        Ok(response)
+    }
+
+    /// Buys a listed NFT at its ask, along with the unstake fee. The seller's rewards are settled
+    /// as on unstake, the NFT is claimable by the buyer after the unstaking period and the
+    /// proceeds, minus the sale fee, go to the seller.
+    #[sv::msg(exec)]
+    pub fn buy_nft(&self, ctx: ExecCtx, nft: Nft<String>) -> Result<Response, ContractError> {
+        self.assert_not_paused(ctx.deps.storage, false)?;
+
+        let nft = nft.str_to_addr(ctx.deps.api)?;
+        let staked_nft_id = (nft.collection.clone(), nft.token_id.clone());
+        let listing = self
+            .listings
+            .may_load(ctx.deps.storage, staked_nft_id.clone())?
+            .ok_or_else(|| CommonError::InvalidInput("nft not listed".to_string()))?;
+        ensure!(
+            !listing.expires.is_expired(&ctx.env.block),
+            CommonError::InvalidInput("listing expired".to_string())
+        );
+        ensure!(
+            listing.seller != ctx.info.sender,
+            CommonError::InvalidInput("cannot buy own listing".to_string())
+        );
+
+        let config = self.config.load(ctx.deps.storage)?;
+        let reward_accounts = self.reward_accounts.load(ctx.deps.storage)?;
+
+        // A native unstake fee is paid along with the price
+        let mut required_funds = vec![listing.price.clone()];
+        if let Some(Fee {
+            asset: FeeAsset::Native(denom),
+            amount,
+            ..
+        }) = &config.unstake_fee
+        {
+            match required_funds.iter_mut().find(|fund| &fund.denom == denom) {
+                Some(fund) => fund.amount = fund.amount.checked_add(*amount)?,
+                None => required_funds.push(coin(amount.u128(), denom)),
+            }
+        }
+        ensure!(
+            ctx.info.funds.len() == required_funds.len()
+                && required_funds
+                    .iter()
+                    .all(|fund| ctx.info.funds.contains(fund)),
+            CommonError::InvalidInput(format!(
+                "{} required",
+                required_funds
+                    .iter()
+                    .map(|fund| fund.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ))
+        );
+
+        let staked_nft = self
+            .users_staked_nfts
+            .load(ctx.deps.storage, staked_nft_id.clone())?;
+        ensure!(
+            staked_nft.staker == listing.seller,
+            CommonError::InternalError("seller is not the staker".to_string())
+        );
+
+        // The sale stands in for an unstake, so the buyer pays the unstake fee
+        let mut response = match &config.unstake_fee {
+            Some(fee) => self.record_fee(ctx.deps.storage, &ctx.info.sender, fee, "buy", 1)?,
+            None => Response::new(),
+        };
+
+        // Pending NFTs were never counted
+        let mut collection_deltas = HashMap::new();
+        if !self.remove_pending_nft(ctx.deps.storage, &staked_nft)? {
+            self.remove_active_nft(
+                ctx.deps.storage,
+                &ctx.env,
+                &config,
+                &listing.seller,
+                &nft,
+                staked_nft.staked_at,
+            )?;
+            collection_deltas.insert(nft.collection.clone(), -1i64);
+        }
+
+        self.users_staked_nfts
+            .remove(ctx.deps.storage, staked_nft_id.clone())?;
+        self.listings.remove(ctx.deps.storage, staked_nft_id);
+
+        // The NFT leaves the vault after the unstaking period, as it would have for the seller
+        self.claims.create_claim(
+            ctx.deps.storage,
+            &ctx.info.sender,
+            vec![nft.clone()],
+            Expiration::AtTime(
+                ctx.env
+                    .block
+                    .time
+                    .plus_seconds(config.collection_unstaking_duration_sec(&nft.collection)),
+            ),
+        )?;
+
+        if let (true, Some(receipt_collection)) = (
+            staked_nft.has_receipt,
+            self.receipt_collection.may_load(ctx.deps.storage)?,
+        ) {
+            response = response.add_submessage(burn_receipt_message(&receipt_collection, &nft)?);
+        }
+
+        let update_stake_result = self.update_stake_amounts(
+            ctx.deps.storage,
+            &ctx.env,
+            config.clone(),
+            &listing.seller,
+            collection_deltas,
+        )?;
+        response = response.add_submessages(self.stake_change_messages(
+            ctx.deps.storage,
+            &ctx.env,
+            &reward_accounts,
+            &listing.seller,
+            &update_stake_result,
+            true,
+        )?);
+
+        let fee_amount = config.sale_fee.as_ref().map_or(Uint128::zero(), |fee| {
+            listing
+                .price
+                .amount
+                .multiply_ratio(fee.fee_bps, BASIS_POINTS)
+        });
+        let proceeds = listing.price.amount.checked_sub(fee_amount)?;
+
+        if let (false, Some(fee)) = (fee_amount.is_zero(), &config.sale_fee) {
+            response = response.add_message(BankMsg::Send {
+                to_address: fee.recipient.to_string(),
+                amount: coins(fee_amount.u128(), &listing.price.denom),
+            });
+        }
+        if !proceeds.is_zero() {
+            response = response.add_message(BankMsg::Send {
+                to_address: listing.seller.to_string(),
+                amount: coins(proceeds.u128(), &listing.price.denom),
+            });
+        }
+
+        response = response.add_event(SaleEvent {
+            listing: &listing,
+            buyer: &ctx.info.sender,
+            fee_amount,
+        });
+
+        Ok(response)
+    }
+
+    /// Shares a lump sum between the current stakers, cw20 airdrops are pulled from the
+    /// sender's allowance
+    #[sv::msg(exec)]
+    pub fn airdrop(
         &self,
-        ctx: QueryCtx,
-        staker: String,
-        query_options: QueryOptions<(String, String)>,
-    ) -> StdResult<Vec<StakedNft>> {
-        let staker = ctx.deps.api.addr_validate(&staker)?;
+        ctx: ExecCtx,
+        asset: FeeAsset<String>,
+        amount: Uint128,
+        expiration: Expiration,
+    ) -> Result<Response, ContractError> {
+        self.assert_not_paused(ctx.deps.storage, false)?;
 
-        let QueryOptionsInternal {
-            limit,
-            order,
-            min,
-            max,
-        } = query_options.unpack(
-            &|offset| {
-                (
-                    staker.clone(),
-                    (
-                        ctx.deps.api.addr_validate(&offset.0).unwrap(),
-                        offset.1.to_string(),
-                    ),
-                )
-            },
-            None,
-            None,
+        ensure!(
+            !amount.is_zero(),
+            CommonError::InvalidInput("airdrop amount must be greater than zero".to_string())
+        );
+        ensure!(
+            !expiration.is_expired(&ctx.env.block),
+            CommonError::InvalidInput("expiration is in the past".to_string())
+        );
+
+        let asset = match asset {
+            FeeAsset::Native(denom) => {
+                ensure!(
+                    must_pay(&ctx.info, &denom)? == amount,
+                    CommonError::InvalidInput(format!("{}{} required", amount, denom))
+                );
+                FeeAsset::Native(denom)
+            }
+            FeeAsset::Cw20(cw20) => {
+                nonpayable(&ctx.info)?;
+                FeeAsset::Cw20(ctx.deps.api.addr_validate(&cw20)?)
+            }
+        };
+
+        // Shares are computed from the staking powers at the start of the block, so stakes
+        // within the same block cannot dilute them
+        let height = ctx.env.block.height;
+        let total_staked_amount = self
+            .total_staked_amount
+            .may_load_at_height(ctx.deps.storage, height)?
+            .unwrap_or_default();
+        ensure!(
+            !total_staked_amount.is_zero(),
+            CommonError::InvalidInput("nothing staked".to_string())
+        );
+
+        let id = self
+            .airdrops
+            .keys(ctx.deps.storage, None, None, Order::Descending)
+            .next()
+            .transpose()?
+            .map_or(1, |id| id + 1);
+
+        let airdrop = Airdrop {
+            id,
+            funder: ctx.info.sender.clone(),
+            asset,
+            amount,
+            height,
+            total_staked_amount,
+            claimed_amount: Uint128::zero(),
+            expiration,
+            clawed_back: false,
+        };
+        self.airdrops.save(ctx.deps.storage, id, &airdrop)?;
+
+        let mut response = Response::new().add_event(AirdropEvent {
+            ty: "create-airdrop",
+            airdrop: &airdrop,
+            account: &ctx.info.sender,
+            amount,
+        });
+
+        if let FeeAsset::Cw20(cw20) = &airdrop.asset {
+            response = response.add_message(WasmMsg::Execute {
+                contract_addr: cw20.to_string(),
+                msg: to_json_binary(&Cw20ExecuteMsg::TransferFrom {
+                    owner: ctx.info.sender.to_string(),
+                    recipient: ctx.env.contract.address.to_string(),
+                    amount,
+                })?,
+                funds: vec![],
+            });
+        }
+
+        Ok(response)
+    }
+
+    #[sv::msg(exec)]
+    pub fn claim_airdrop(
+        &self,
+        ctx: ExecCtx,
+        airdrop_id: u64,
+        recipient: Option<String>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.assert_not_paused(ctx.deps.storage, true)?;
+
+        let sender = ctx.info.sender.clone();
+        let recipient = address_or(&sender, maybe_addr(ctx.deps.api, recipient)?.as_ref());
+
+        let mut airdrop = self.airdrops.load(ctx.deps.storage, airdrop_id)?;
+        ensure!(
+            !airdrop.expiration.is_expired(&ctx.env.block),
+            ContractError::AirdropExpired
+        );
+        ensure!(
+            !self
+                .airdrop_claims
+                .has(ctx.deps.storage, (airdrop_id, sender.clone())),
+            ContractError::AirdropAlreadyClaimed
+        );
+
+        let amount = self.airdrop_share_of(ctx.deps.storage, &airdrop, &sender)?;
+        ensure!(
+            !amount.is_zero(),
+            CommonError::InvalidInput("no airdrop share".to_string())
+        );
+
+        self.airdrop_claims
+            .save(ctx.deps.storage, (airdrop_id, sender.clone()), &amount)?;
+        airdrop.claimed_amount = airdrop.claimed_amount.checked_add(amount)?;
+        self.airdrops.save(ctx.deps.storage, airdrop_id, &airdrop)?;
+
+        let response = Response::new()
+            .add_event(AirdropEvent {
+                ty: "claim-airdrop",
+                airdrop: &airdrop,
+                account: &sender,
+                amount,
+            })
+            .add_submessage(send_asset_message(&airdrop.asset, &recipient, amount)?);
+
+        Ok(response)
+    }
+
+    /// Returns the unclaimed part of an expired airdrop, including rounding leftovers
+    #[sv::msg(exec)]
+    pub fn clawback_airdrop(
+        &self,
+        ctx: ExecCtx,
+        airdrop_id: u64,
+        recipient: Option<String>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let mut airdrop = self.airdrops.load(ctx.deps.storage, airdrop_id)?;
+        ensure!(
+            airdrop.funder == ctx.info.sender,
+            CommonError::Unauthorized("only the funder can claw back".to_string())
+        );
+        ensure!(
+            airdrop.expiration.is_expired(&ctx.env.block),
+            CommonError::InvalidInput("airdrop has not expired".to_string())
         );
+        ensure!(
+            !airdrop.clawed_back,
+            CommonError::InvalidInput("airdrop already clawed back".to_string())
+        );
+
+        let recipient = address_or(
+            &ctx.info.sender,
+            maybe_addr(ctx.deps.api, recipient)?.as_ref(),
+        );
+
+        let amount = airdrop.amount.checked_sub(airdrop.claimed_amount)?;
+        airdrop.clawed_back = true;
+        self.airdrops.save(ctx.deps.storage, airdrop_id, &airdrop)?;
+
+        let mut response = Response::new().add_event(AirdropEvent {
+            ty: "clawback-airdrop",
+            airdrop: &airdrop,
+            account: &recipient,
+            amount,
+        });
+
+        if !amount.is_zero() {
+            response =
+                response.add_submessage(send_asset_message(&airdrop.asset, &recipient, amount)?);
+        }
+
+        Ok(response)
+    }
+
+    #[sv::msg(exec)]
+    pub fn claim(
+        &self,
+        ctx: ExecCtx,
+        recipient: Option<String>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.assert_not_paused(ctx.deps.storage, true)?;
+
+        let sender = ctx.info.sender.clone();
+        let recipient = address_or(&sender, maybe_addr(ctx.deps.api, recipient)?.as_ref());
+
+        let claimable_nfts =
+            self.claims
+                .claim_tokens(ctx.deps.storage, &sender, &ctx.env.block, None)?;
+        ensure!(
+            !claimable_nfts.is_empty(),
+            ContractError::ClaimableNftsNotFound
+        );
+
+        let mut response = Response::new();
+
+        for nft in &claimable_nfts {
+            response =
+                response.add_submessage(transfer_nft(&nft.collection, &nft.token_id, &recipient));
+        }
+
+        response = response.add_event(Event::new("claim-unstaked".to_string()).add_attributes(
+            vec![
+                    attr("sender", sender.to_string()),
+                    attr(
+                        "nfts",
+                        claimable_nfts
+                            .iter()
+                            .map(|nft| nft.to_string())
+                            .collect::<Vec<String>>()
+                            .join(","),
+                    ),
+                ],
+        ));
+
+        Ok(response)
+    }
+
+    #[sv::msg(exec)]
+    pub fn claim_rewards(
+        &self,
+        ctx: ExecCtx,
+        recipient: Option<String>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.assert_not_paused(ctx.deps.storage, false)?;
+
+        let sender = ctx.info.sender.clone();
+        let recipient = address_or(&sender, maybe_addr(ctx.deps.api, recipient)?.as_ref());
+
+        let config = self.config.load(ctx.deps.storage)?;
+        let reward_accounts = self.reward_accounts.load(ctx.deps.storage)?;
+
+        let mut response = Response::new();
+
+        // Settle NFTs that completed the warm-up period before claiming
+        let StakerNfts {
+            collection_deltas,
+            nfts: activated_nfts,
+        } = self.activate_nfts(ctx.deps.storage, &ctx.env, &config, &sender)?;
+
+        let update_stake_result = self.update_stake_amounts(
+            ctx.deps.storage,
+            &ctx.env,
+            config,
+            &sender,
+            collection_deltas,
+        )?;
+
+        if !activated_nfts.is_empty() {
+            response = response.add_event(ActivateNftsEvent {
+                staker: &sender,
+                nfts: &activated_nfts,
+            });
+        }
+
+        // Rewards follow the derivative token holders when it exists
+        let (user_staked_amount, total_staked_amount) =
+            if self.derivative_token.exists(ctx.deps.storage) {
+                if !activated_nfts.is_empty() {
+                    response = response.add_submessages(self.stake_change_messages(
+                        ctx.deps.storage,
+                        &ctx.env,
+                        &reward_accounts,
+                        &sender,
+                        &update_stake_result,
+                        true,
+                    )?);
+                }
+
+                (
+                    self.derivative_balances
+                        .may_load(ctx.deps.storage, sender.clone())?
+                        .unwrap_or_default(),
+                    self.derivative_supply.load(ctx.deps.storage)?,
+                )
+            } else {
+                // Settle any change in reward weight before claiming
+                let update_reward_weight_result =
+                    self.update_reward_weight(ctx.deps.storage, &ctx.env, &sender)?;
+
+                if update_reward_weight_result.user_reward_weight
+                    != update_reward_weight_result.next_user_reward_weight
+                {
+                    response = response.add_submessages(setup_stake_change_messages(
+                        &reward_accounts,
+                        &sender,
+                        update_reward_weight_result.user_reward_weight,
+                        update_reward_weight_result.total_reward_weight,
+                    )?);
+                }
+
+                (
+                    update_reward_weight_result.next_user_reward_weight,
+                    self.total_reward_weight.load(ctx.deps.storage)?,
+                )
+            };
+
+        let claim_json = to_json_binary(&PassageRewardsExecuteMsg::ClaimRewards {
+            recipient: recipient.to_string(),
+            staked_amount: Some(user_staked_amount),
+            total_staked: Some(total_staked_amount),
+        })?;
+
+        let claim_msgs = reward_accounts
+            .iter()
+            .map(|addr| {
+                SubMsg::new(WasmMsg::Execute {
+                    contract_addr: addr.to_string(),
+                    msg: claim_json.clone(),
+                    funds: vec![],
+                })
+            })
+            .collect::<Vec<SubMsg>>();
+
+        response = response
+            .add_event(
+                Event::new("claim-rewards".to_string())
+                    .add_attributes(vec![attr("sender", sender.to_string())]),
+            )
+            .add_submessages(claim_msgs);
+
+        Ok(response)
+    }
+
+    #[sv::msg(exec)]
+    pub fn force_unstake(
+        &self,
+        ctx: ExecCtx,
+        nfts: Vec<Nft<String>>,
+        recipient: Option<String>,
+        reason: String,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        self.roles.assert_owner_or_role(
+            ctx.deps.storage,
+            &self.ownership,
+            &ctx.info.sender,
+            Role::Moderator,
+        )?;
+
+        ensure!(
+            !nfts.is_empty(),
+            CommonError::InvalidInput("no nfts to unstake".to_string())
+        );
+        ensure!(
+            nfts.len() <= MAX_NFTS,
+            CommonError::InvalidInput("too many nfts to unstake".to_string())
+        );
+        ensure!(
+            !reason.trim().is_empty(),
+            CommonError::InvalidInput("reason must be provided".to_string())
+        );
+
+        let recipient = maybe_addr(ctx.deps.api, recipient)?;
+
+        let config = self.config.load(ctx.deps.storage)?;
+        let reward_accounts = self.reward_accounts.load(ctx.deps.storage)?;
+        let receipt_collection = self.receipt_collection.may_load(ctx.deps.storage)?;
+
+        let internal_nfts = nfts
+            .into_iter()
+            .map(|nft| nft.str_to_addr(ctx.deps.api))
+            .collect::<Result<Vec<Nft<Addr>>, ContractError>>()?;
+
+        let mut response = Response::new();
+
+        // Group the removed NFTs by staker, so that each staker is settled once
+        let mut stakers: BTreeMap<Addr, StakerNfts> = BTreeMap::new();
+
+        for nft in internal_nfts {
+            let staked_nft_id = (nft.collection.clone(), nft.token_id.clone());
+
+            let staked_nft = self
+                .users_staked_nfts
+                .may_load(ctx.deps.storage, staked_nft_id.clone())?
+                .ok_or_else(|| CommonError::InvalidInput("nft not staked".to_string()))?;
+
+            let is_pending = self.remove_pending_nft(ctx.deps.storage, &staked_nft)?;
+            if !is_pending {
+                self.remove_active_nft(
+                    ctx.deps.storage,
+                    &ctx.env,
+                    &config,
+                    &staked_nft.staker,
+                    &nft,
+                    staked_nft.staked_at,
+                )?;
+            }
+
+            // Remove staked NFT
+            self.users_staked_nfts
+                .remove(ctx.deps.storage, staked_nft_id.clone())?;
+            self.listings
+                .remove(ctx.deps.storage, staked_nft_id.clone());
+
+            if let (true, Some(receipt_collection)) = (staked_nft.has_receipt, &receipt_collection)
+            {
+                response = response.add_submessage(burn_receipt_message(receipt_collection, &nft)?);
+            }
+
+            // Hold the NFT in escrow when no recipient is given
+            if recipient.is_none() {
+                self.escrowed_nfts.save(
+                    ctx.deps.storage,
+                    staked_nft_id,
+                    &EscrowedNft {
+                        staker: staked_nft.staker.clone(),
+                        nft: nft.clone(),
+                        reason: reason.clone(),
+                    },
+                )?;
+            }
+
+            let staker_nfts = stakers.entry(staked_nft.staker).or_default();
+
+            // Update collection count, pending NFTs were never counted
+            if !is_pending {
+                let count = staker_nfts
+                    .collection_deltas
+                    .entry(nft.collection.clone())
+                    .or_insert(0);
+                *count = count.checked_sub(1).unwrap();
+            }
+
+            staker_nfts.nfts.push(nft);
+        }
+
+        for (
+            staker,
+            StakerNfts {
+                collection_deltas,
+                nfts: staker_nfts,
+            },
+        ) in stakers
+        {
+            let update_stake_result = self.update_stake_amounts(
+                ctx.deps.storage,
+                &ctx.env,
+                config.clone(),
+                &staker,
+                collection_deltas,
+            )?;
+
+            // Settle the staker's rewards with the amounts prior to the removal, redeeming
+            // whatever derivative tokens the staker still holds
+            let stake_change_msgs = self.stake_change_messages(
+                ctx.deps.storage,
+                &ctx.env,
+                &reward_accounts,
+                &staker,
+                &update_stake_result,
+                false,
+            )?;
+
+            if let Some(recipient) = &recipient {
+                for nft in &staker_nfts {
+                    response = response.add_submessage(transfer_nft(
+                        &nft.collection,
+                        &nft.token_id,
+                        recipient,
+                    ));
+                }
+            }
+
+            response = response
+                .add_event(ForceUnstakeEvent {
+                    staker: &staker,
+                    nfts: &staker_nfts,
+                    recipient: recipient.as_ref(),
+                    reason: &reason,
+                })
+                .add_submessages(stake_change_msgs);
+        }
+
+        Ok(response)
+    }
+
+    #[sv::msg(exec)]
+    pub fn release_escrowed_nfts(
+        &self,
+        ctx: ExecCtx,
+        nfts: Vec<Nft<String>>,
+        recipient: String,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        self.roles.assert_owner_or_role(
+            ctx.deps.storage,
+            &self.ownership,
+            &ctx.info.sender,
+            Role::Moderator,
+        )?;
+
+        ensure!(
+            !nfts.is_empty(),
+            CommonError::InvalidInput("no nfts to release".to_string())
+        );
+        ensure!(
+            nfts.len() <= MAX_NFTS,
+            CommonError::InvalidInput("too many nfts to release".to_string())
+        );
+
+        let recipient = ctx.deps.api.addr_validate(&recipient)?;
+
+        let internal_nfts = nfts
+            .into_iter()
+            .map(|nft| nft.str_to_addr(ctx.deps.api))
+            .collect::<Result<Vec<Nft<Addr>>, ContractError>>()?;
+
+        let mut response = Response::new();
+
+        for nft in &internal_nfts {
+            let escrowed_nft_id = (nft.collection.clone(), nft.token_id.clone());
+            ensure!(
+                self.escrowed_nfts
+                    .has(ctx.deps.storage, escrowed_nft_id.clone()),
+                CommonError::InvalidInput("nft not in escrow".to_string())
+            );
+            self.escrowed_nfts.remove(ctx.deps.storage, escrowed_nft_id);
+
+            response =
+                response.add_submessage(transfer_nft(&nft.collection, &nft.token_id, &recipient));
+        }
+
+        response = response.add_event(ReleaseEscrowEvent {
+            nfts: &internal_nfts,
+            recipient: &recipient,
+        });
+
+        Ok(response)
+    }
+
+    #[sv::msg(exec)]
+    pub fn create_receipt_collection(
+        &self,
+        ctx: ExecCtx,
+        label: String,
+        name: String,
+        symbol: String,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        self.roles.assert_owner_or_role(
+            ctx.deps.storage,
+            &self.ownership,
+            &ctx.info.sender,
+            Role::ConfigManager,
+        )?;
+
+        ensure!(
+            !self.receipt_collection.exists(ctx.deps.storage),
+            CommonError::InvalidInput("receipt collection already exists".to_string())
+        );
+        ensure!(
+            !self.derivative_token.exists(ctx.deps.storage),
+            CommonError::InvalidInput("derivative token already exists".to_string())
+        );
+
+        let config = self.config.load(ctx.deps.storage)?;
+        let receipt_code_id = config.receipt_code_id.ok_or_else(|| {
+            CommonError::InvalidInput("receipt code id not configured".to_string())
+        })?;
+
+        let salt = generate_salt(vec![
+            ctx.env.contract.address.to_string().as_bytes(),
+            "receipt".as_bytes(),
+        ]);
+
+        let receipt_collection_addr = generate_instantiate_2_addr(
+            &ctx.deps.as_ref(),
+            &ctx.env.contract.address,
+            receipt_code_id,
+            &salt,
+        )?;
+
+        self.receipt_collection
+            .save(ctx.deps.storage, &receipt_collection_addr)?;
+
+        let instantiate_msg = WasmMsg::Instantiate2 {
+            admin: Some(ctx.env.contract.address.to_string()),
+            code_id: receipt_code_id,
+            label,
+            msg: to_json_binary(&StakeReceiptInstantiateMsg { name, symbol })?,
+            funds: vec![],
+            salt,
+        };
+
+        let response = Response::new()
+            .add_event(ReceiptCollectionEvent {
+                ty: "create-receipt-collection",
+                address: receipt_collection_addr.as_str(),
+            })
+            .add_message(instantiate_msg);
+
+        Ok(response)
+    }
+
+    #[sv::msg(exec)]
+    pub fn receipt_transfer(
+        &self,
+        ctx: ExecCtx,
+        nft: Nft<String>,
+        previous_owner: String,
+        recipient: String,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.assert_not_paused(ctx.deps.storage, false)?;
+
+        self.only_receipt_collection(ctx.deps.storage, &ctx.info.sender)?;
+
+        let nft = nft.str_to_addr(ctx.deps.api)?;
+        let previous_owner = ctx.deps.api.addr_validate(&previous_owner)?;
+        let recipient = ctx.deps.api.addr_validate(&recipient)?;
+
+        let staked_nft_id = (nft.collection.clone(), nft.token_id.clone());
+        let mut staked_nft = self
+            .users_staked_nfts
+            .may_load(ctx.deps.storage, staked_nft_id.clone())?
+            .ok_or_else(|| CommonError::InvalidInput("nft not staked".to_string()))?;
+        ensure!(
+            staked_nft.staker == previous_owner,
+            CommonError::InternalError("receipt owner is not the staker".to_string())
+        );
+
+        let config = self.config.load(ctx.deps.storage)?;
+        let reward_accounts = self.reward_accounts.load(ctx.deps.storage)?;
+
+        // The receipt holder owns the staked position, including any warm-up progress and
+        // loyalty streak
+        let is_pending = self.remove_pending_nft(ctx.deps.storage, &staked_nft)?;
+        if is_pending {
+            self.add_pending_nft(
+                ctx.deps.storage,
+                &recipient,
+                staked_nft.staked_at,
+                &staked_nft.nft,
+            )?;
+        } else {
+            self.remove_active_nft(
+                ctx.deps.storage,
+                &ctx.env,
+                &config,
+                &previous_owner,
+                &nft,
+                staked_nft.staked_at,
+            )?;
+            self.add_active_nft(
+                ctx.deps.storage,
+                &ctx.env,
+                &config,
+                &recipient,
+                &nft,
+                staked_nft.staked_at,
+            )?;
+        }
+
+        staked_nft.staker = recipient.clone();
+        staked_nft.user = None;
+        self.users_staked_nfts
+            .save(ctx.deps.storage, staked_nft_id.clone(), &staked_nft)?;
+        self.listings.remove(ctx.deps.storage, staked_nft_id);
+
+        let mut response = Response::new().add_event(
+            Event::new("transfer-staked-nft".to_string()).add_attributes(vec![
+                attr("nft", nft.to_string()),
+                attr("previous_owner", previous_owner.to_string()),
+                attr("recipient", recipient.to_string()),
+            ]),
+        );
+
+        if is_pending {
+            return Ok(response);
+        }
+
+        for (staker, delta) in [(&previous_owner, -1i64), (&recipient, 1i64)] {
+            let collection_deltas = HashMap::from([(nft.collection.clone(), delta)]);
+
+            let update_stake_result = self.update_stake_amounts(
+                ctx.deps.storage,
+                &ctx.env,
+                config.clone(),
+                staker,
+                collection_deltas,
+            )?;
+
+            response = response.add_submessages(self.stake_change_messages(
+                ctx.deps.storage,
+                &ctx.env,
+                &reward_accounts,
+                staker,
+                &update_stake_result,
+                true,
+            )?);
+        }
+
+        Ok(response)
+    }
+
+    #[sv::msg(exec)]
+    pub fn receipt_burn(
+        &self,
+        ctx: ExecCtx,
+        nft: Nft<String>,
+        owner: String,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.assert_not_paused(ctx.deps.storage, true)?;
+
+        self.only_receipt_collection(ctx.deps.storage, &ctx.info.sender)?;
+
+        // Burning a receipt cannot pay the unstake fee, holders unstake through the vault instead
+        let config = self.config.load(ctx.deps.storage)?;
+        ensure!(
+            config.unstake_fee.is_none() || self.status.load(ctx.deps.storage)?.emergency_mode,
+            CommonError::InvalidInput(
+                "unstake fee required, unstake through the vault".to_string()
+            )
+        );
+
+        let nft = nft.str_to_addr(ctx.deps.api)?;
+        let owner = ctx.deps.api.addr_validate(&owner)?;
+
+        // The receipt is already burned, so only the staked NFT is unstaked
+        self.unstake_nfts(ctx.deps.storage, &ctx.env, &owner, vec![nft], false)
+    }
+
+    #[sv::msg(exec)]
+    pub fn create_derivative_token(
+        &self,
+        ctx: ExecCtx,
+        label: String,
+        name: String,
+        symbol: String,
+        decimals: u8,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        self.roles.assert_owner_or_role(
+            ctx.deps.storage,
+            &self.ownership,
+            &ctx.info.sender,
+            Role::ConfigManager,
+        )?;
+
+        ensure!(
+            !self.derivative_token.exists(ctx.deps.storage),
+            CommonError::InvalidInput("derivative token already exists".to_string())
+        );
+        ensure!(
+            !self.receipt_collection.exists(ctx.deps.storage),
+            CommonError::InvalidInput("receipt collection already exists".to_string())
+        );
+        // Existing stakers would hold staking power without any derivative tokens
+        ensure!(
+            self.total_staked_amount.load(ctx.deps.storage)?.is_zero(),
+            CommonError::InvalidInput("derivative token requires an empty vault".to_string())
+        );
+        // Locked boosts could not be unlocked anymore
+        ensure!(
+            self.users_boosts.is_empty(ctx.deps.storage),
+            CommonError::InvalidInput("derivative token requires no locked boosts".to_string())
+        );
+
+        let config = self.config.load(ctx.deps.storage)?;
+        let derivative_code_id = config.derivative_code_id.ok_or_else(|| {
+            CommonError::InvalidInput("derivative code id not configured".to_string())
+        })?;
+
+        let salt = generate_salt(vec![
+            ctx.env.contract.address.to_string().as_bytes(),
+            "derivative".as_bytes(),
+        ]);
+
+        let derivative_token_addr = generate_instantiate_2_addr(
+            &ctx.deps.as_ref(),
+            &ctx.env.contract.address,
+            derivative_code_id,
+            &salt,
+        )?;
+
+        self.derivative_token
+            .save(ctx.deps.storage, &derivative_token_addr)?;
+        self.derivative_supply
+            .save(ctx.deps.storage, &Uint128::zero())?;
+
+        let instantiate_msg = WasmMsg::Instantiate2 {
+            admin: Some(ctx.env.contract.address.to_string()),
+            code_id: derivative_code_id,
+            label,
+            msg: to_json_binary(&StakeDerivativeInstantiateMsg {
+                name,
+                symbol,
+                decimals,
+            })?,
+            funds: vec![],
+            salt,
+        };
+
+        let response = Response::new()
+            .add_event(DerivativeTokenEvent {
+                ty: "create-derivative-token",
+                address: derivative_token_addr.as_str(),
+            })
+            .add_message(instantiate_msg);
+
+        Ok(response)
+    }
+
+    #[sv::msg(exec)]
+    pub fn derivative_transfer(
+        &self,
+        ctx: ExecCtx,
+        sender: String,
+        recipient: String,
+        amount: Uint128,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.assert_not_paused(ctx.deps.storage, false)?;
+
+        let derivative_token = self.derivative_token.may_load(ctx.deps.storage)?;
+        ensure!(
+            derivative_token.as_ref() == Some(&ctx.info.sender),
+            CommonError::Unauthorized("sender is not the derivative token".to_string())
+        );
+
+        let sender = ctx.deps.api.addr_validate(&sender)?;
+        let recipient = ctx.deps.api.addr_validate(&recipient)?;
+
+        let reward_accounts = self.reward_accounts.load(ctx.deps.storage)?;
+        let total_supply = self.derivative_supply.load(ctx.deps.storage)?;
+
+        let mut response = Response::new().add_event(DerivativeTransferEvent {
+            sender: &sender,
+            recipient: &recipient,
+            amount,
+        });
+
+        if sender == recipient {
+            return Ok(response);
+        }
+
+        // Settle the rewards of both holders with their balances prior to the transfer
+        for (holder, is_sender) in [(&sender, true), (&recipient, false)] {
+            let balance = self
+                .derivative_balances
+                .may_load(ctx.deps.storage, holder.clone())?
+                .unwrap_or_default();
+
+            let next_balance = if is_sender {
+                balance.checked_sub(amount)?
+            } else {
+                balance.checked_add(amount)?
+            };
+            self.derivative_balances
+                .save(ctx.deps.storage, holder.clone(), &next_balance)?;
+
+            response = response.add_submessages(setup_stake_change_messages(
+                &reward_accounts,
+                holder,
+                balance,
+                total_supply,
+            )?);
+        }
+
+        Ok(response)
+    }
+
+    #[sv::msg(exec)]
+    pub fn transfer_ownership(
+        &self,
+        ctx: ExecCtx,
+        new_owner: String,
+        expiry: Option<Expiration>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let new_owner = ctx.deps.api.addr_validate(&new_owner)?;
+        let ownership = self.ownership.transfer_ownership(
+            ctx.deps.storage,
+            &ctx.env.block,
+            &ctx.info.sender,
+            new_owner,
+            expiry,
+        )?;
+
+        Ok(Response::new().add_event(OwnershipEvent {
+            ty: "transfer-ownership",
+            ownership: &ownership,
+        }))
+    }
+
+    #[sv::msg(exec)]
+    pub fn accept_ownership(&self, ctx: ExecCtx) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let ownership =
+            self.ownership
+                .accept_ownership(ctx.deps.storage, &ctx.env.block, &ctx.info.sender)?;
+
+        Ok(Response::new().add_event(OwnershipEvent {
+            ty: "accept-ownership",
+            ownership: &ownership,
+        }))
+    }
+
+    #[sv::msg(exec)]
+    pub fn renounce_ownership(&self, ctx: ExecCtx) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let ownership = self
+            .ownership
+            .renounce_ownership(ctx.deps.storage, &ctx.info.sender)?;
+
+        Ok(Response::new().add_event(OwnershipEvent {
+            ty: "renounce-ownership",
+            ownership: &ownership,
+        }))
+    }
+
+    #[sv::msg(exec)]
+    pub fn grant_role(
+        &self,
+        ctx: ExecCtx,
+        address: String,
+        role: Role,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.ownership
+            .assert_owner(ctx.deps.storage, &ctx.info.sender)?;
+
+        let address = ctx.deps.api.addr_validate(&address)?;
+        self.roles.grant(ctx.deps.storage, &address, role)?;
+
+        Ok(Response::new().add_event(RoleEvent {
+            ty: "grant-role",
+            address: &address,
+            role,
+        }))
+    }
+
+    #[sv::msg(exec)]
+    pub fn revoke_role(
+        &self,
+        ctx: ExecCtx,
+        address: String,
+        role: Role,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.ownership
+            .assert_owner(ctx.deps.storage, &ctx.info.sender)?;
+
+        let address = ctx.deps.api.addr_validate(&address)?;
+        self.roles.revoke(ctx.deps.storage, &address, role)?;
+
+        Ok(Response::new().add_event(RoleEvent {
+            ty: "revoke-role",
+            address: &address,
+            role,
+        }))
+    }
+
+    /// Approves a cw20 token, trusted to report who sent it, to create reward accounts
+    #[sv::msg(exec)]
+    pub fn add_reward_token(&self, ctx: ExecCtx, token: String) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.ownership
+            .assert_owner(ctx.deps.storage, &ctx.info.sender)?;
+
+        let token = ctx.deps.api.addr_validate(&token)?;
+        ensure!(
+            !self.reward_tokens.has(ctx.deps.storage, token.clone()),
+            CommonError::InvalidInput(format!("{} is already a reward token", token))
+        );
+        self.reward_tokens
+            .save(ctx.deps.storage, token.clone(), &Empty {})?;
+
+        Ok(Response::new().add_event(RewardTokenEvent {
+            ty: "add-reward-token",
+            token: &token,
+        }))
+    }
+
+    #[sv::msg(exec)]
+    pub fn remove_reward_token(
+        &self,
+        ctx: ExecCtx,
+        token: String,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.ownership
+            .assert_owner(ctx.deps.storage, &ctx.info.sender)?;
+
+        let token = ctx.deps.api.addr_validate(&token)?;
+        ensure!(
+            self.reward_tokens.has(ctx.deps.storage, token.clone()),
+            CommonError::InvalidInput(format!("{} is not a reward token", token))
+        );
+        self.reward_tokens.remove(ctx.deps.storage, token.clone());
+
+        Ok(Response::new().add_event(RewardTokenEvent {
+            ty: "remove-reward-token",
+            token: &token,
+        }))
+    }
+
+    #[sv::msg(exec)]
+    pub fn pause(&self, ctx: ExecCtx) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.roles.assert_owner_or_role(
+            ctx.deps.storage,
+            &self.ownership,
+            &ctx.info.sender,
+            Role::Pauser,
+        )?;
+
+        self.update_status(ctx.deps.storage, "pause", |status| status.paused = true)
+    }
+
+    #[sv::msg(exec)]
+    pub fn unpause(&self, ctx: ExecCtx) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.roles.assert_owner_or_role(
+            ctx.deps.storage,
+            &self.ownership,
+            &ctx.info.sender,
+            Role::Pauser,
+        )?;
+
+        self.update_status(ctx.deps.storage, "unpause", |status| status.paused = false)
+    }
+
+    #[sv::msg(sudo)]
+    pub fn set_paused(&self, ctx: SudoCtx, paused: bool) -> Result<Response, ContractError> {
+        self.update_status(ctx.deps.storage, "sudo-set-paused", |status| {
+            status.paused = paused
+        })
+    }
+
+    #[sv::msg(sudo)]
+    pub fn set_emergency_mode(
+        &self,
+        ctx: SudoCtx,
+        emergency_mode: bool,
+    ) -> Result<Response, ContractError> {
+        self.update_status(ctx.deps.storage, "sudo-set-emergency-mode", |status| {
+            status.emergency_mode = emergency_mode
+        })
+    }
+
+    #[sv::msg(sudo)]
+    pub fn set_owner(
+        &self,
+        ctx: SudoCtx,
+        owner: Option<String>,
+    ) -> Result<Response, ContractError> {
+        let owner = maybe_addr(ctx.deps.api, owner)?;
+        let ownership = self.ownership.set_owner(ctx.deps.storage, owner)?;
+
+        Ok(Response::new().add_event(OwnershipEvent {
+            ty: "sudo-set-owner",
+            ownership: &ownership,
+        }))
+    }
+
+    #[sv::msg(reply)]
+    pub fn reply(&self, _ctx: ReplyCtx, reply: Reply) -> Result<Response, ContractError> {
+        match (reply.id, reply.result) {
+            (EMERGENCY_STAKE_CHANGE_REPLY_ID, SubMsgResult::Err(error)) => Ok(Response::new()
+                .add_event(
+                    Event::new("reward-account-error".to_string())
+                        .add_attributes(vec![attr("error", error)]),
+                )),
+            (id, _) => Err(CommonError::InternalError(format!("unknown reply id {}", id)).into()),
+        }
+    }
+
+    #[sv::msg(query)]
+    pub fn config(&self, ctx: QueryCtx) -> StdResult<ConfigResponse> {
+        let config = self.config.load(ctx.deps.storage)?;
+
+        let unstaking_durations = config
+            .collections
+            .iter()
+            .map(|collection| CollectionUnstakingDuration {
+                collection: collection.clone(),
+                unstaking_duration_sec: config.collection_unstaking_duration_sec(collection),
+            })
+            .collect();
+
+        Ok(ConfigResponse {
+            config,
+            unstaking_durations,
+        })
+    }
+
+    #[sv::msg(query)]
+    pub fn ownership(&self, ctx: QueryCtx) -> StdResult<OwnershipInfo> {
+        self.ownership.load(ctx.deps.storage)
+    }
+
+    #[sv::msg(query)]
+    pub fn roles(
+        &self,
+        ctx: QueryCtx,
+        query_options: QueryOptions<String>,
+    ) -> StdResult<Vec<(Addr, Vec<Role>)>> {
+        let QueryOptionsInternal {
+            limit,
+            order,
+            min,
+            max,
+        } = query_options.unpack(
+            &|offset| ctx.deps.api.addr_validate(offset).unwrap(),
+            None,
+            None,
+        );
+
+        self.roles.range(ctx.deps.storage, min, max, order, limit)
+    }
+
+    #[sv::msg(query)]
+    pub fn reward_tokens(
+        &self,
+        ctx: QueryCtx,
+        query_options: QueryOptions<String>,
+    ) -> StdResult<Vec<Addr>> {
+        let QueryOptionsInternal {
+            limit,
+            order,
+            min,
+            max,
+        } = query_options.unpack(
+            &|offset| ctx.deps.api.addr_validate(offset).unwrap(),
+            None,
+            None,
+        );
+
+        self.reward_tokens
+            .keys(ctx.deps.storage, min, max, order)
+            .take(limit)
+            .collect()
+    }
+
+    #[sv::msg(query)]
+    pub fn status(&self, ctx: QueryCtx) -> StdResult<Status> {
+        self.status.load(ctx.deps.storage)
+    }
+
+    #[sv::msg(query)]
+    pub fn reward_accounts(&self, ctx: QueryCtx) -> StdResult<Vec<Addr>> {
+        self.reward_accounts.load(ctx.deps.storage)
+    }
+
+    #[sv::msg(query)]
+    pub fn receipt_collection(&self, ctx: QueryCtx) -> StdResult<Option<Addr>> {
+        self.receipt_collection.may_load(ctx.deps.storage)
+    }
+
+    #[sv::msg(query)]
+    pub fn derivative_token(&self, ctx: QueryCtx) -> StdResult<Option<Addr>> {
+        self.derivative_token.may_load(ctx.deps.storage)
+    }
+
+    /// Derivative tokens a staker failed to return when force unstaked, netted on the next mint
+    #[sv::msg(query)]
+    pub fn derivative_debt(&self, ctx: QueryCtx, staker: String) -> StdResult<Uint128> {
+        let staker = ctx.deps.api.addr_validate(&staker)?;
+
+        Ok(self
+            .derivative_debts
+            .may_load(ctx.deps.storage, staker)?
+            .unwrap_or_default())
+    }
+
+    #[sv::msg(query)]
+    pub fn users_staked_nfts(
+        &self,
+        ctx: QueryCtx,
+        staker: String,
+        query_options: QueryOptions<(String, String)>,
+    ) -> StdResult<Vec<StakedNft>> {
+        let staker = ctx.deps.api.addr_validate(&staker)?;
+
+        let QueryOptionsInternal {
+            limit,
+            order,
+            min,
+            max,
+        } = query_options.unpack(
+            &|offset| {
+                (
+                    staker.clone(),
+                    (
+                        ctx.deps.api.addr_validate(&offset.0).unwrap(),
+                        offset.1.to_string(),
+                    ),
+                )
+            },
+            None,
+            None,
+        );
+
+        let results = self
+            .users_staked_nfts
+            .idx
+            .staker_collection
+            .sub_prefix(staker.clone())
+            .range(ctx.deps.storage, min, max, order)
+            .take(limit)
+            .map(|res| res.map(|(_, pq)| pq))
+            .collect::<StdResult<Vec<StakedNft>>>()?;
+
+        Ok(results)
+    }
+
+    #[sv::msg(query)]
+    pub fn users_collection_staked_amounts(
+        &self,
+        ctx: QueryCtx,
+        staker: String,
+        query_options: QueryOptions<String>,
+    ) -> StdResult<Vec<(Addr, u64)>> {
+        let staker = ctx.deps.api.addr_validate(&staker)?;
+
+        let QueryOptionsInternal {
+            limit,
+            order,
+            min,
+            max,
+        } = query_options.unpack(
+            &|offset| ctx.deps.api.addr_validate(&offset).unwrap(),
+            None,
+            None,
+        );
+
+        let results = self
+            .users_collection_staked_amounts
+            .prefix(staker.clone())
+            .range(ctx.deps.storage, min, max, order)
+            .take(limit)
+            .map(|res| res.map(|(collection, amount)| (collection, amount)))
+            .collect::<StdResult<Vec<_>>>()?;
+
+        Ok(results)
+    }
+
+    #[sv::msg(query)]
+    pub fn staking_power(&self, ctx: QueryCtx, staker: String) -> StdResult<StakingPower> {
+        let staker = ctx.deps.api.addr_validate(&staker)?;
+        let config = self.config.load(ctx.deps.storage)?;
+
+        let mut pending_amounts: HashMap<Addr, u64> = HashMap::new();
+        let mut next_activation = None;
+
+        for res in self.users_pending_nfts.prefix(staker.clone()).range(
+            ctx.deps.storage,
+            None,
+            None,
+            Order::Ascending,
+        ) {
+            let (staked_at, nfts) = res?;
+
+            if next_activation.is_none() {
+                next_activation = Some(Timestamp::from_seconds(
+                    staked_at + config.warmup_duration_sec,
+                ));
+            }
+
+            for nft in nfts {
+                *pending_amounts.entry(nft.collection).or_default() += 1;
+            }
+        }
+
+        let mut active = u64::MAX;
+        let mut active_and_pending = u64::MAX;
+
+        for collection in &config.collections {
+            let amount = self
+                .users_collection_staked_amounts
+                .may_load(ctx.deps.storage, (staker.clone(), collection.clone()))?
+                .unwrap_or_default();
+            let pending_amount = pending_amounts.get(collection).copied().unwrap_or_default();
+
+            active = min(active, amount);
+            active_and_pending = min(active_and_pending, amount + pending_amount);
+        }
+
+        if config.collections.is_empty() {
+            active = 0;
+            active_and_pending = 0;
+        }
+
+        Ok(StakingPower {
+            active: Uint128::from(active),
+            pending: Uint128::from(active_and_pending - active),
+            next_activation,
+        })
+    }
+
+    #[sv::msg(query)]
+    pub fn reward_weight(&self, ctx: QueryCtx, staker: String) -> StdResult<RewardWeight> {
+        let staker = ctx.deps.api.addr_validate(&staker)?;
+
+        Ok(RewardWeight {
+            reward_weight: self
+                .users_reward_weights
+                .may_load(ctx.deps.storage, staker.clone())?
+                .unwrap_or_default(),
+            latest_reward_weight: self.reward_weight_of(ctx.deps.storage, &ctx.env, &staker)?,
+            total_reward_weight: self.total_reward_weight.load(ctx.deps.storage)?,
+        })
+    }
+
+    /// Queried by reward accounts funded from outside the vault
+    #[sv::msg(query)]
+    pub fn rewards_total_staked(&self, ctx: QueryCtx) -> StdResult<Uint128> {
+        self.rewards_total_staked_of(ctx.deps.storage)
+    }
+
+    /// Queried by reward accounts in pull accounting when stakers claim directly
+    #[sv::msg(query)]
+    pub fn rewards_staked_amount(&self, ctx: QueryCtx, address: String) -> StdResult<Uint128> {
+        let staker = ctx.deps.api.addr_validate(&address)?;
+
+        self.rewards_staked_amount_of(ctx.deps.storage, staker)
+    }
+
+    #[sv::msg(query)]
+    pub fn boosts(&self, ctx: QueryCtx, staker: String) -> StdResult<Vec<Nft<Addr>>> {
+        let staker = ctx.deps.api.addr_validate(&staker)?;
+
+        Ok(self
+            .users_boosts
+            .may_load(ctx.deps.storage, staker)?
+            .unwrap_or_default())
+    }
+
+    /// Resolves the current user of a staked NFT, expired usage rights resolve to none
+    #[sv::msg(query)]
+    pub fn nft_user(&self, ctx: QueryCtx, nft: Nft<String>) -> StdResult<Option<Addr>> {
+        let collection = ctx.deps.api.addr_validate(&nft.collection)?;
+
+        let user = self
+            .users_staked_nfts
+            .may_load(ctx.deps.storage, (collection, nft.token_id))?
+            .and_then(|staked_nft| staked_nft.user)
+            .filter(|user| user.expires > ctx.env.block.time)
+            .map(|user| user.user);
+
+        Ok(user)
+    }
+
+    #[sv::msg(query)]
+    pub fn collected_fees(&self, ctx: QueryCtx) -> StdResult<Vec<CollectedFee>> {
+        self.collected_fees
+            .range(ctx.deps.storage, None, None, Order::Ascending)
+            .map(|res| res.map(|(_, collected_fee)| collected_fee))
+            .collect()
+    }
+
+    #[sv::msg(query)]
+    pub fn listings(
+        &self,
+        ctx: QueryCtx,
+        query_options: QueryOptions<(String, String)>,
+    ) -> StdResult<Vec<Listing>> {
+        let QueryOptionsInternal {
+            limit,
+            order,
+            min,
+            max,
+        } = query_options.unpack(
+            &|offset| {
+                (
+                    ctx.deps.api.addr_validate(&offset.0).unwrap(),
+                    offset.1.to_string(),
+                )
+            },
+            None,
+            None,
+        );
+
+        self.listings
+            .range(ctx.deps.storage, min, max, order)
+            .take(limit)
+            .map(|res| res.map(|(_, listing)| listing))
+            .collect()
+    }
+
+    #[sv::msg(query)]
+    pub fn airdrops(
+        &self,
+        ctx: QueryCtx,
+        query_options: QueryOptions<u64>,
+    ) -> StdResult<Vec<Airdrop>> {
+        let QueryOptionsInternal {
+            limit,
+            order,
+            min,
+            max,
+        } = query_options.unpack(&|offset| *offset, None, None);
+
+        self.airdrops
+            .range(ctx.deps.storage, min, max, order)
+            .take(limit)
+            .map(|res| res.map(|(_, airdrop)| airdrop))
+            .collect()
+    }
+
+    #[sv::msg(query)]
+    pub fn airdrop_share(
+        &self,
+        ctx: QueryCtx,
+        airdrop_id: u64,
+        staker: String,
+    ) -> StdResult<AirdropShare> {
+        let staker = ctx.deps.api.addr_validate(&staker)?;
+        let airdrop = self.airdrops.load(ctx.deps.storage, airdrop_id)?;
+
+        match self
+            .airdrop_claims
+            .may_load(ctx.deps.storage, (airdrop_id, staker.clone()))?
+        {
+            Some(amount) => Ok(AirdropShare {
+                amount,
+                claimed: true,
+            }),
+            None => Ok(AirdropShare {
+                amount: self.airdrop_share_of(ctx.deps.storage, &airdrop, &staker)?,
+                claimed: false,
+            }),
+        }
+    }
+
+    #[sv::msg(query)]
+    pub fn escrowed_nfts(
+        &self,
+        ctx: QueryCtx,
+        query_options: QueryOptions<(String, String)>,
+    ) -> StdResult<Vec<EscrowedNft>> {
+        let QueryOptionsInternal {
+            limit,
+            order,
+            min,
+            max,
+        } = query_options.unpack(
+            &|offset| {
+                (
+                    ctx.deps.api.addr_validate(&offset.0).unwrap(),
+                    offset.1.to_string(),
+                )
+            },
+            None,
+            None,
+        );
+
+        let results = self
+            .escrowed_nfts
+            .range(ctx.deps.storage, min, max, order)
+            .take(limit)
+            .map(|res| res.map(|(_, escrowed_nft)| escrowed_nft))
+            .collect::<StdResult<Vec<EscrowedNft>>>()?;
+
+        Ok(results)
+    }
+
+    /// Lists every staker's staking power at the start of the given height
+    #[sv::msg(query)]
+    pub fn staking_powers_at_height(
+        &self,
+        ctx: QueryCtx,
+        height: u64,
+        query_options: QueryOptions<String>,
+    ) -> StdResult<Vec<(Addr, Uint128)>> {
+        let QueryOptionsInternal {
+            limit,
+            order,
+            min,
+            max,
+        } = query_options.unpack(
+            &|offset| ctx.deps.api.addr_validate(offset).unwrap(),
+            None,
+            None,
+        );
+
+        self.users_staked_amounts
+            .keys(ctx.deps.storage, min, max, order)
+            .take(limit)
+            .map(|res| {
+                let staker = res?;
+                let staked_amount = self
+                    .users_staked_amounts
+                    .may_load_at_height(ctx.deps.storage, staker.clone(), height)?
+                    .unwrap_or_default();
+                Ok((staker, staked_amount))
+            })
+            .collect()
+    }
+
+    #[sv::msg(query)]
+    pub fn total_staked_amount_at_height(
+        &self,
+        ctx: QueryCtx,
+        height: Option<u64>,
+    ) -> StdResult<Option<Uint128>> {
+        let height = height.unwrap_or(ctx.env.block.height);
+
+        let results = self
+            .total_staked_amount
+            .may_load_at_height(ctx.deps.storage, height)?;
+
+        Ok(results)
+    }
+
+    #[sv::msg(query)]
+    pub fn claims(&self, ctx: QueryCtx, staker: String) -> StdResult<Vec<Claim>> {
+        let staker = ctx.deps.api.addr_validate(&staker)?;
+
+        let results = self.claims.query_claims(ctx.deps, &staker)?;
+
+        Ok(results.claims)
+    }
+
+    /// Removes the staker's NFTs from the vault and creates a claim for them
+    pub fn unstake_nfts(
+        &self,
+        storage: &mut dyn Storage,
+        env: &Env,
+        sender: &Addr,
+        nfts: Vec<Nft<Addr>>,
+        burn_receipts: bool,
+    ) -> Result<Response, ContractError> {
+        let config = self.config.load(storage)?;
+        let reward_accounts = self.reward_accounts.load(storage)?;
+        let receipt_collection = self.receipt_collection.may_load(storage)?;
+
+        let mut response = Response::new();
+
+        // NFTs that completed the warm-up period are activated before unstaking
+        let StakerNfts {
+            mut collection_deltas,
+            nfts: activated_nfts,
+        } = self.activate_nfts(storage, env, &config, sender)?;
+        if !activated_nfts.is_empty() {
+            response = response.add_event(ActivateNftsEvent {
+                staker: sender,
+                nfts: &activated_nfts,
+            });
+        }
+
+        for nft in &nfts {
+            let staked_nft = self
+                .users_staked_nfts
+                .may_load(storage, (nft.collection.clone(), nft.token_id.clone()))?;
+            ensure!(
+                staked_nft.is_some(),
+                CommonError::InvalidInput("nft not staked".to_string())
+            );
+            let staked_nft = staked_nft.unwrap();
+            ensure!(
+                staked_nft.staker == sender,
+                CommonError::Unauthorized("nft not staked by sender".to_string())
+            );
+
+            // Update collection count, pending NFTs were never counted
+            if !self.remove_pending_nft(storage, &staked_nft)? {
+                let count = collection_deltas.entry(nft.collection.clone()).or_insert(0);
+                *count = count.checked_sub(1).unwrap();
+                self.remove_active_nft(storage, env, &config, sender, nft, staked_nft.staked_at)?;
+            }
+
+            // Burn the receipt representing the staked position
+            if let (true, true, Some(receipt_collection)) =
+                (burn_receipts, staked_nft.has_receipt, &receipt_collection)
+            {
+                response = response.add_submessage(burn_receipt_message(receipt_collection, nft)?);
+            }
+
+            // Remove staked NFT
+            self.users_staked_nfts
+                .remove(storage, (nft.collection.clone(), nft.token_id.clone()))?;
+            self.listings
+                .remove(storage, (nft.collection.clone(), nft.token_id.clone()));
+        }
+
+        let update_stake_result =
+            self.update_stake_amounts(storage, env, config.clone(), sender, collection_deltas)?;
+
+        // In emergency mode NFTs are returned right away, and the reward accounts are settled
+        // without letting a failing reward account block the exit
+        if self.status.load(storage)?.emergency_mode {
+            let stake_change_msgs = self.stake_change_messages(
+                storage,
+                env,
+                &reward_accounts,
+                sender,
+                &update_stake_result,
+                false,
+            )?;
+            let stake_change_msgs = reply_on_reward_account_error(
+                stake_change_msgs,
+                &reward_accounts,
+                EMERGENCY_STAKE_CHANGE_REPLY_ID,
+            );
+
+            for nft in &nfts {
+                response =
+                    response.add_submessage(transfer_nft(&nft.collection, &nft.token_id, sender));
+            }
+
+            return Ok(response.add_submessages(stake_change_msgs));
+        }
+
+        // Create a claim for the unstaked nfts per release time
+        let mut nfts_by_duration: BTreeMap<u64, Vec<Nft<Addr>>> = BTreeMap::new();
+        for nft in nfts {
+            nfts_by_duration
+                .entry(config.collection_unstaking_duration_sec(&nft.collection))
+                .or_default()
+                .push(nft);
+        }
+
+        for (unstaking_duration_sec, nfts) in nfts_by_duration {
+            self.claims.create_claim(
+                storage,
+                sender,
+                nfts,
+                Expiration::AtTime(env.block.time.plus_seconds(unstaking_duration_sec)),
+            )?;
+        }
+
+        // Setup the stake change messages with the previous staked amount and total staked amount
+        let stake_change_msgs = self.stake_change_messages(
+            storage,
+            env,
+            &reward_accounts,
+            sender,
+            &update_stake_result,
+            true,
+        )?;
+
+        response = response.add_submessages(stake_change_msgs);
+
+        Ok(response)
+    }
+
+    /// Builds the messages settling the staker's rewards after their staking power changed.
+    /// Without the derivative token, rewards are settled against the staker's reward weight.
+    /// When the derivative token exists, the power difference is minted to or redeemed from
+    /// the staker, and rewards are settled against the derivative balances instead. Unless the
+    /// redeem is strict, what the staker no longer holds is recorded as their derivative debt.
+    pub fn stake_change_messages(
+        &self,
+        storage: &mut dyn Storage,
+        env: &Env,
+        reward_accounts: &[Addr],
+        staker: &Addr,
+        update_stake_result: &UpdateStakeResult,
+        strict_redeem: bool,
+    ) -> Result<Vec<SubMsg>, ContractError> {
+        let UpdateStakeResult {
+            user_staked_amount,
+            next_user_staked_amount,
+            ..
+        } = *update_stake_result;
+
+        // Without a derivative token rewards follow the stakers' reward weights
+        let derivative_token = match self.derivative_token.may_load(storage)? {
+            Some(derivative_token) => derivative_token,
+            None => {
+                let UpdateRewardWeightResult {
+                    user_reward_weight,
+                    total_reward_weight,
+                    ..
+                } = self.update_reward_weight(storage, env, staker)?;
+
+                return setup_stake_change_messages(
+                    reward_accounts,
+                    staker,
+                    user_reward_weight,
+                    total_reward_weight,
+                );
+            }
+        };
+
+        let balance = self
+            .derivative_balances
+            .may_load(storage, staker.clone())?
+            .unwrap_or_default();
+        let total_supply = self.derivative_supply.load(storage)?;
+
+        let mut sub_msgs =
+            setup_stake_change_messages(reward_accounts, staker, balance, total_supply)?;
+
+        let debt = self
+            .derivative_debts
+            .may_load(storage, staker.clone())?
+            .unwrap_or_default();
+
+        let (next_balance, next_total_supply, next_debt) =
+            if next_user_staked_amount >= user_staked_amount {
+                // Staking power added first backs the tokens left unredeemed by a forced unstake
+                let netted_amount = min(debt, next_user_staked_amount - user_staked_amount);
+                let mint_amount = next_user_staked_amount - user_staked_amount - netted_amount;
+                if !mint_amount.is_zero() {
+                    sub_msgs.push(mint_derivative_message(
+                        &derivative_token,
+                        staker,
+                        mint_amount,
+                    )?);
+                }
+                (
+                    balance.checked_add(mint_amount)?,
+                    total_supply.checked_add(mint_amount)?,
+                    debt - netted_amount,
+                )
+            } else {
+                let mut redeem_amount = user_staked_amount - next_user_staked_amount;
+                ensure!(
+                    !strict_redeem || balance >= redeem_amount,
+                    CommonError::InsufficientFunds(
+                        "insufficient derivative balance to unstake".to_string()
+                    )
+                );
+
+                // Tokens the staker moved away stay in circulation, unbacked until netted
+                let shortfall = redeem_amount - min(balance, redeem_amount);
+                redeem_amount -= shortfall;
+                if !redeem_amount.is_zero() {
+                    sub_msgs.push(redeem_derivative_message(
+                        &derivative_token,
+                        staker,
+                        redeem_amount,
+                    )?);
+                }
+                (
+                    balance.checked_sub(redeem_amount)?,
+                    total_supply.checked_sub(redeem_amount)?,
+                    debt.checked_add(shortfall)?,
+                )
+            };
+
+        self.derivative_balances
+            .save(storage, staker.clone(), &next_balance)?;
+        self.derivative_supply.save(storage, &next_total_supply)?;
+        match next_debt.is_zero() {
+            true => self.derivative_debts.remove(storage, staker.clone()),
+            false => self
+                .derivative_debts
+                .save(storage, staker.clone(), &next_debt)?,
+        }
+
+        Ok(sub_msgs)
+    }
+
+    /// Registers a new reward account at its predicted address and builds its instantiation
+    pub fn reward_account_instantiate_msg(
+        &self,
+        deps: DepsMut,
+        env: &Env,
+        label: String,
+        msg: StakeRewardsInstantiateMsg,
+        funds: Vec<Coin>,
+    ) -> Result<(Addr, WasmMsg), ContractError> {
+        let config = self.config.load(deps.storage)?;
+        let mut reward_accounts = self.reward_accounts.load(deps.storage)?;
+
+        let salt = generate_salt(vec![
+            env.contract.address.to_string().as_bytes(),
+            (reward_accounts.len() as u64).to_be_bytes().as_ref(),
+        ]);
+
+        let reward_contract_addr = generate_instantiate_2_addr(
+            &deps.as_ref(),
+            &env.contract.address,
+            config.rewards_code_id,
+            &salt,
+        )?;
+
+        reward_accounts.push(reward_contract_addr.clone());
+        self.reward_accounts.save(deps.storage, &reward_accounts)?;
+
+        let instantiate_msg = WasmMsg::Instantiate2 {
+            admin: Some(env.contract.address.to_string()),
+            code_id: config.rewards_code_id,
+            label,
+            msg: to_json_binary(&msg)?,
+            funds,
+            salt,
+        };
+
+        Ok((reward_contract_addr, instantiate_msg))
+    }
+
+    /// Errors unless a reward account fee recipient belongs to the vault and pays out the fee asset
+    pub fn validate_fee(&self, deps: Deps, fee: Option<&Fee<Addr>>) -> Result<(), ContractError> {
+        let (fee_asset, reward_account) = match fee {
+            Some(Fee {
+                asset,
+                recipient: FeeRecipient::RewardAccount(reward_account),
+                ..
+            }) => (asset, reward_account),
+            _ => return Ok(()),
+        };
+
+        ensure!(
+            self.reward_accounts
+                .load(deps.storage)?
+                .contains(reward_account),
+            ContractError::RewardAccountNotFound
+        );
+
+        let distributions: Vec<Distribution> = deps
+            .querier
+            .query_wasm_smart(reward_account, &StakeRewardsQueryMsg::Distributions {})?;
+        let fee_reward_asset = fee_asset.reward_asset();
+        ensure!(
+            distributions
+                .iter()
+                .any(|distribution| distribution.reward_asset == fee_reward_asset),
+            CommonError::InvalidInput("fee asset is not a reward asset".to_string())
+        );
+
+        Ok(())
+    }
+
+    /// Checks the fee paid for the operation before recording it
+    pub fn collect_fee(
+        &self,
+        storage: &mut dyn Storage,
+        info: &MessageInfo,
+        fee: Option<&Fee<Addr>>,
+        operation: &str,
+        nft_count: usize,
+    ) -> Result<Response, ContractError> {
+        let fee = match fee {
+            Some(fee) => fee,
+            None => {
+                nonpayable(info)?;
+                return Ok(Response::new());
+            }
+        };
+
+        let amount = fee.amount.checked_mul(Uint128::from(nft_count as u64))?;
+
+        match &fee.asset {
+            FeeAsset::Native(denom) => ensure!(
+                must_pay(info, denom)? == amount,
+                CommonError::InvalidInput(format!("fee of {}{} required", amount, denom))
+            ),
+            FeeAsset::Cw20(_) => nonpayable(info)?,
+        }
+
+        self.record_fee(storage, &info.sender, fee, operation, nft_count)
+    }
+
+    /// Records a fee paid for the operation and builds the response forwarding it
+    pub fn record_fee(
+        &self,
+        storage: &mut dyn Storage,
+        payer: &Addr,
+        fee: &Fee<Addr>,
+        operation: &str,
+        nft_count: usize,
+    ) -> Result<Response, ContractError> {
+        let amount = fee.amount.checked_mul(Uint128::from(nft_count as u64))?;
+
+        self.collected_fees
+            .update(storage, fee.asset.to_string(), |collected_fee| {
+                let mut collected_fee = collected_fee.unwrap_or(CollectedFee {
+                    asset: fee.asset.clone(),
+                    amount: Uint128::zero(),
+                });
+                collected_fee.amount = collected_fee.amount.checked_add(amount)?;
+                Ok::<_, ContractError>(collected_fee)
+            })?;
+
+        // Reward accounts are topped up against the rewards total before the operation
+        let total_staked = self.rewards_total_staked_of(storage)?;
+
+        let response = Response::new()
+            .add_event(FeeEvent {
+                operation,
+                payer,
+                fee,
+                amount,
+            })
+            .add_submessages(fee_messages(fee, payer, amount, total_staked)?);
+
+        Ok(response)
+    }
+
+    /// The total the reward accounts distribute against, the derivative supply once the
+    /// derivative token exists
+    pub fn rewards_total_staked_of(&self, storage: &dyn Storage) -> StdResult<Uint128> {
+        match self.derivative_token.exists(storage) {
+            true => self.derivative_supply.load(storage),
+            false => self.total_reward_weight.load(storage),
+        }
+    }
+
+    /// The staker's amount the reward accounts distribute against, as last settled by the vault
+    pub fn rewards_staked_amount_of(
+        &self,
+        storage: &dyn Storage,
+        staker: Addr,
+    ) -> StdResult<Uint128> {
+        let staked_amount = match self.derivative_token.exists(storage) {
+            true => self.derivative_balances.may_load(storage, staker)?,
+            false => self.users_reward_weights.may_load(storage, staker)?,
+        };
+
+        Ok(staked_amount.unwrap_or_default())
+    }
+
+    /// The staker's reward weight, the weighted sets of active NFTs including loyalty bonuses
+    pub fn reward_weight_of(
+        &self,
+        storage: &dyn Storage,
+        env: &Env,
+        staker: &Addr,
+    ) -> StdResult<Uint128> {
+        let config = self.config.load(storage)?;
+
+        let (loyalty, _) = self.loyalty_of(storage, env, &config, staker)?;
+
+        let reward_weight = config
+            .collections
+            .iter()
+            .map(|collection| loyalty.collection_weight(&config, collection))
+            .min()
+            .unwrap_or_default();
+
+        // Boosts multiply the weight of the staked sets
+        let boost_bps = self
+            .users_boosts
+            .may_load(storage, staker.clone())?
+            .unwrap_or_default()
+            .iter()
+            .map(|boost| config.boost_bonus_bps(&boost.collection))
+            .sum::<u64>();
+
+        Ok(Uint128::from(reward_weight).multiply_ratio(BASIS_POINTS + boost_bps, BASIS_POINTS))
+    }
+
+    /// Settles a change in the staker's reward weight with the reward accounts
+    pub fn sync_reward_weight(
+        &self,
+        storage: &mut dyn Storage,
+        env: &Env,
+        staker: &Addr,
+    ) -> Result<Response, ContractError> {
+        let UpdateRewardWeightResult {
+            user_reward_weight,
+            total_reward_weight,
+            next_user_reward_weight,
+        } = self.update_reward_weight(storage, env, staker)?;
+
+        if user_reward_weight == next_user_reward_weight {
+            return Ok(Response::new());
+        }
+
+        let reward_accounts = self.reward_accounts.load(storage)?;
+
+        let response = Response::new()
+            .add_event(RewardWeightEvent {
+                staker,
+                reward_weight: next_user_reward_weight,
+                total_reward_weight: self.total_reward_weight.load(storage)?,
+            })
+            .add_submessages(setup_stake_change_messages(
+                &reward_accounts,
+                staker,
+                user_reward_weight,
+                total_reward_weight,
+            )?);
+
+        Ok(response)
+    }
+
+    /// Recomputes the staker's reward weight, returning it along with the previous weight and total
+    pub fn update_reward_weight(
+        &self,
+        storage: &mut dyn Storage,
+        env: &Env,
+        staker: &Addr,
+    ) -> StdResult<UpdateRewardWeightResult> {
+        let user_reward_weight = self
+            .users_reward_weights
+            .may_load(storage, staker.clone())?
+            .unwrap_or_default();
+        let total_reward_weight = self.total_reward_weight.load(storage)?;
+
+        let config = self.config.load(storage)?;
+        self.promote_loyalty(storage, env, &config, staker)?;
+
+        let next_user_reward_weight = self.reward_weight_of(storage, env, staker)?;
+
+        if next_user_reward_weight != user_reward_weight {
+            self.users_reward_weights
+                .save(storage, staker.clone(), &next_user_reward_weight)?;
+            self.total_reward_weight.save(
+                storage,
+                &total_reward_weight
+                    .checked_sub(user_reward_weight)?
+                    .checked_add(next_user_reward_weight)?,
+            )?;
+        }
+
+        Ok(UpdateRewardWeightResult {
+            user_reward_weight,
+            total_reward_weight,
+            next_user_reward_weight,
+        })
+    }
+
+    /// The staker's loyalty as of the block time, with the due promotions applied, along with
+    /// the changes to their promotion queue. Loyalty counted against other tier durations is
+    /// rebuilt from the staker's active NFTs.
+    pub fn loyalty_of(
+        &self,
+        storage: &dyn Storage,
+        env: &Env,
+        config: &Config<Addr>,
+        staker: &Addr,
+    ) -> StdResult<(Loyalty, LoyaltyPromotions)> {
+        let now = env.block.time.seconds();
+        let tier_durations = config.loyalty_tier_durations();
+
+        let mut loyalty = match self.users_loyalty.may_load(storage, staker.clone())? {
+            Some(loyalty) if loyalty.tier_durations == tier_durations => loyalty,
+            _ => return self.rebuild_loyalty(storage, env, config, staker),
+        };
+        let mut promotions = LoyaltyPromotions::default();
+
+        let mut due_promotions = vec![];
+        for res in self.users_loyalty_promotions.prefix(staker.clone()).range(
+            storage,
+            None,
+            Some(Bound::inclusive(now)),
+            Order::Ascending,
+        ) {
+            let (promote_at, queued) = res?;
+            promotions.removed.push(promote_at);
+            due_promotions.extend(queued);
+        }
+
+        // An NFT may have reached several tiers since the staker's last interaction
+        while let Some(promotion) = due_promotions.pop() {
+            let tier = promotion.tier as usize;
+            loyalty.remove(&promotion.collection, tier - 1, promotion.count)?;
+            loyalty.add(&promotion.collection, tier, promotion.count)?;
+
+            if let Some(promote_at) = config.next_loyalty_promotion(promotion.staked_at, tier) {
+                let next_promotion = LoyaltyPromotion {
+                    tier: promotion.tier + 1,
+                    ..promotion
+                };
+                match promote_at <= now {
+                    true => due_promotions.push(next_promotion),
+                    false => promotions.added.push((promote_at, next_promotion)),
+                }
+            }
+        }
+
+        Ok((loyalty, promotions))
+    }
+
+    /// Counts the staker's active NFTs from scratch, replacing their promotion queue
+    pub fn rebuild_loyalty(
+        &self,
+        storage: &dyn Storage,
+        env: &Env,
+        config: &Config<Addr>,
+        staker: &Addr,
+    ) -> StdResult<(Loyalty, LoyaltyPromotions)> {
+        let now = env.block.time.seconds();
+
+        let pending_nfts = self
+            .users_pending_nfts
+            .prefix(staker.clone())
+            .range(storage, None, None, Order::Ascending)
+            .map(|res| res.map(|(_, nfts)| nfts))
+            .collect::<StdResult<Vec<_>>>()?
+            .into_iter()
+            .flatten()
+            .map(|nft| (nft.collection, nft.token_id))
+            .collect::<HashSet<_>>();
+
+        let mut loyalty = Loyalty::new(config.loyalty_tier_durations());
+        let mut promotions = LoyaltyPromotions {
+            reset: true,
+            ..Default::default()
+        };
+
+        for res in self
+            .users_staked_nfts
+            .idx
+            .staker_collection
+            .sub_prefix(staker.clone())
+            .range(storage, None, None, Order::Ascending)
+        {
+            let (_, staked_nft) = res?;
+            if pending_nfts.contains(&(
+                staked_nft.nft.collection.clone(),
+                staked_nft.nft.token_id.clone(),
+            )) {
+                continue;
+            }
+
+            let staked_at = staked_nft.staked_at.seconds();
+            let tier = config.loyalty_tier(now.saturating_sub(staked_at));
+            loyalty.add(&staked_nft.nft.collection, tier, 1)?;
+
+            if let Some(promote_at) = config.next_loyalty_promotion(staked_at, tier) {
+                promotions.added.push((
+                    promote_at,
+                    LoyaltyPromotion {
+                        collection: staked_nft.nft.collection,
+                        staked_at,
+                        tier: tier as u32 + 1,
+                        count: 1,
+                    },
+                ));
+            }
+        }
+
+        Ok((loyalty, promotions))
+    }
+
+    pub fn save_loyalty(
+        &self,
+        storage: &mut dyn Storage,
+        staker: &Addr,
+        loyalty: &Loyalty,
+        promotions: LoyaltyPromotions,
+    ) -> StdResult<()> {
+        let LoyaltyPromotions {
+            reset,
+            mut removed,
+            added,
+            cancelled,
+        } = promotions;
+
+        if reset {
+            removed = self
+                .users_loyalty_promotions
+                .prefix(staker.clone())
+                .keys(storage, None, None, Order::Ascending)
+                .collect::<StdResult<Vec<_>>>()?;
+        }
+        for promote_at in removed {
+            self.users_loyalty_promotions
+                .remove(storage, (staker.clone(), promote_at));
+        }
+
+        for (promote_at, promotion) in added {
+            self.users_loyalty_promotions.update(
+                storage,
+                (staker.clone(), promote_at),
+                |queued| -> StdResult<_> {
+                    let mut queued = queued.unwrap_or_default();
+                    match queued.iter_mut().find(|q| q.same_cohort(&promotion)) {
+                        Some(cohort) => cohort.count += promotion.count,
+                        None => queued.push(promotion),
+                    }
+                    Ok(queued)
+                },
+            )?;
+        }
+
+        for (promote_at, promotion) in cancelled {
+            let key = (staker.clone(), promote_at);
+            let mut queued = self.users_loyalty_promotions.load(storage, key.clone())?;
+            let cohort = queued
+                .iter_mut()
+                .find(|q| q.same_cohort(&promotion))
+                .ok_or_else(|| StdError::generic_err("loyalty promotion not found"))?;
+            cohort.count -= promotion.count;
+            queued.retain(|q| q.count > 0);
+
+            match queued.is_empty() {
+                true => self.users_loyalty_promotions.remove(storage, key),
+                false => self.users_loyalty_promotions.save(storage, key, &queued)?,
+            }
+        }
+
+        self.users_loyalty.save(storage, staker.clone(), loyalty)
+    }
+
+    /// Settles the staker's loyalty promotions due by the block time
+    pub fn promote_loyalty(
+        &self,
+        storage: &mut dyn Storage,
+        env: &Env,
+        config: &Config<Addr>,
+        staker: &Addr,
+    ) -> StdResult<()> {
+        let (loyalty, promotions) = self.loyalty_of(storage, env, config, staker)?;
+        self.save_loyalty(storage, staker, &loyalty, promotions)
+    }
+
+    /// Counts an NFT becoming active for the staker, before it is saved as such
+    pub fn add_active_nft(
+        &self,
+        storage: &mut dyn Storage,
+        env: &Env,
+        config: &Config<Addr>,
+        staker: &Addr,
+        nft: &Nft<Addr>,
+        staked_at: Timestamp,
+    ) -> StdResult<()> {
+        let (mut loyalty, mut promotions) = self.loyalty_of(storage, env, config, staker)?;
+
+        let staked_at = staked_at.seconds();
+        let tier = config.loyalty_tier(env.block.time.seconds().saturating_sub(staked_at));
+        loyalty.add(&nft.collection, tier, 1)?;
+
+        if let Some(promote_at) = config.next_loyalty_promotion(staked_at, tier) {
+            promotions.added.push((
+                promote_at,
+                LoyaltyPromotion {
+                    collection: nft.collection.clone(),
+                    staked_at,
+                    tier: tier as u32 + 1,
+                    count: 1,
+                },
+            ));
+        }
+
+        self.save_loyalty(storage, staker, &loyalty, promotions)
+    }
+
+    /// Stops counting an active NFT of the staker, before it is removed or moved
+    pub fn remove_active_nft(
+        &self,
+        storage: &mut dyn Storage,
+        env: &Env,
+        config: &Config<Addr>,
+        staker: &Addr,
+        nft: &Nft<Addr>,
+        staked_at: Timestamp,
+    ) -> StdResult<()> {
+        let (mut loyalty, mut promotions) = self.loyalty_of(storage, env, config, staker)?;
+
+        let staked_at = staked_at.seconds();
+        let tier = config.loyalty_tier(env.block.time.seconds().saturating_sub(staked_at));
+        loyalty.remove(&nft.collection, tier, 1)?;
+
+        if let Some(promote_at) = config.next_loyalty_promotion(staked_at, tier) {
+            promotions.cancelled.push((
+                promote_at,
+                LoyaltyPromotion {
+                    collection: nft.collection.clone(),
+                    staked_at,
+                    tier: tier as u32 + 1,
+                    count: 1,
+                },
+            ));
+        }
+
+        self.save_loyalty(storage, staker, &loyalty, promotions)
+    }
+
+    /// Activates the staker's NFTs that completed the warm-up period, returning the collection deltas
+    pub fn activate_nfts(
+        &self,
+        storage: &mut dyn Storage,
+        env: &Env,
+        config: &Config<Addr>,
+        staker: &Addr,
+    ) -> StdResult<StakerNfts> {
+        let matured_at = env
+            .block
+            .time
+            .seconds()
+            .saturating_sub(config.warmup_duration_sec);
+
+        let matured = self
+            .users_pending_nfts
+            .prefix(staker.clone())
+            .range(
+                storage,
+                None,
+                Some(Bound::inclusive(matured_at)),
+                Order::Ascending,
+            )
+            .collect::<StdResult<Vec<_>>>()?;
+
+        let mut collection_deltas: HashMap<Addr, i64> = HashMap::new();
+        let mut activated_nfts = vec![];
+
+        for (staked_at, nfts) in matured {
+            for nft in nfts {
+                let count = collection_deltas.entry(nft.collection.clone()).or_insert(0);
+                *count = count.checked_add(1).unwrap();
+                self.add_active_nft(
+                    storage,
+                    env,
+                    config,
+                    staker,
+                    &nft,
+                    Timestamp::from_seconds(staked_at),
+                )?;
+                activated_nfts.push(nft);
+            }
+
+            self.users_pending_nfts
+                .remove(storage, (staker.clone(), staked_at));
+        }
+
+        Ok(StakerNfts {
+            collection_deltas,
+            nfts: activated_nfts,
+        })
+    }
+
+    pub fn add_pending_nft(
+        &self,
+        storage: &mut dyn Storage,
+        staker: &Addr,
+        staked_at: Timestamp,
+        nft: &Nft<Addr>,
+    ) -> StdResult<()> {
+        self.users_pending_nfts.update(
+            storage,
+            (staker.clone(), staked_at.seconds()),
+            |pending_nfts| -> StdResult<_> {
+                let mut pending_nfts = pending_nfts.unwrap_or_default();
+                pending_nfts.push(nft.clone());
+                Ok(pending_nfts)
+            },
+        )?;
+
+        Ok(())
+    }
+
+    /// Removes the NFT from the staker's pending NFTs, returning whether it was still warming up
+    pub fn remove_pending_nft(
+        &self,
+        storage: &mut dyn Storage,
+        staked_nft: &StakedNft,
+    ) -> StdResult<bool> {
+        let key = (staked_nft.staker.clone(), staked_nft.staked_at.seconds());
+
+        let mut pending_nfts = match self.users_pending_nfts.may_load(storage, key.clone())? {
+            Some(pending_nfts) => pending_nfts,
+            None => return Ok(false),
+        };
+
+        let pending_len = pending_nfts.len();
+        pending_nfts.retain(|nft| nft != &staked_nft.nft);
+        if pending_nfts.len() == pending_len {
+            return Ok(false);
+        }
+
+        if pending_nfts.is_empty() {
+            self.users_pending_nfts.remove(storage, key);
+        } else {
+            self.users_pending_nfts.save(storage, key, &pending_nfts)?;
+        }
+
+        Ok(true)
+    }
+
+    /// The staker's pro rata share of the airdrop, rounded down
+    pub fn airdrop_share_of(
+        &self,
+        storage: &dyn Storage,
+        airdrop: &Airdrop,
+        staker: &Addr,
+    ) -> StdResult<Uint128> {
+        let staked_amount = self
+            .users_staked_amounts
+            .may_load_at_height(storage, staker.clone(), airdrop.height)?
+            .unwrap_or_default();
+
+        Ok(airdrop
+            .amount
+            .multiply_ratio(staked_amount, airdrop.total_staked_amount))
+    }
+
+    /// Errors when paused or in emergency mode, emergency exits are allowed in emergency mode.
+    pub fn assert_not_paused(
+        &self,
+        storage: &dyn Storage,
+        emergency_exit: bool,
+    ) -> Result<(), ContractError> {
+        let status = self.status.load(storage)?;
+
+        if status.emergency_mode {
+            ensure!(emergency_exit, ContractError::EmergencyMode);
+            return Ok(());
+        }
+
+        ensure!(!status.paused, ContractError::Paused);
+
+        Ok(())
+    }
+
+    pub fn update_status(
+        &self,
+        storage: &mut dyn Storage,
+        ty: &str,
+        action: impl FnOnce(&mut Status),
+    ) -> Result<Response, ContractError> {
+        let mut status = self.status.load(storage)?;
+        action(&mut status);
+        self.status.save(storage, &status)?;
+
+        Ok(Response::new().add_event(StatusEvent {
+            ty,
+            status: &status,
+        }))
+    }
+
+    pub fn only_receipt_collection(
+        &self,
+        storage: &dyn Storage,
+        sender: &Addr,
+    ) -> Result<(), ContractError> {
+        let receipt_collection = self.receipt_collection.may_load(storage)?;
+        ensure!(
+            receipt_collection.as_ref() == Some(sender),
+            CommonError::Unauthorized("sender is not the receipt collection".to_string())
+        );
+        Ok(())
+    }
+
+    pub fn update_stake_amounts(
+        &self,
+        storage: &mut dyn Storage,
+        env: &Env,
+        config: Config<Addr>,
+        sender: &Addr,
+        collection_deltas: HashMap<Addr, i64>,
+    ) -> Result<UpdateStakeResult, ContractError> {
+        let mut user_staked_amount_before = u64::MAX;
+        let mut user_staked_amount_after = u64::MAX;
+
+        for collection in &config.collections {
+            let user_collection_staked_amount_before = self
+                .users_collection_staked_amounts
+                .may_load(storage, (sender.clone(), collection.clone()))?
+                .unwrap_or_default();
+
+            let user_collection_staked_amount_after = user_collection_staked_amount_before
+                .checked_add_signed(*collection_deltas.get(collection).unwrap_or(&0i64))
+                .unwrap();
+
+            if user_collection_staked_amount_before != user_collection_staked_amount_after {
+                self.users_collection_staked_amounts.save(
+                    storage,
+                    (sender.clone(), collection.clone()),
+                    &user_collection_staked_amount_after,
+                )?;
+            }
+
+            user_staked_amount_before = min(
+                user_staked_amount_before,
+                user_collection_staked_amount_before,
+            );
+
+            user_staked_amount_after = min(
+                user_staked_amount_after,
+                user_collection_staked_amount_after,
+            );
+        }
+        ensure!(
+            user_staked_amount_before != u64::MAX,
+            CommonError::InternalError("user staked amount before is u64::MAX".to_string())
+        );
+        ensure!(
+            user_staked_amount_after != u64::MAX,
+            CommonError::InternalError("user staked amount after is u64::MAX".to_string())
+        );
+
+        let total_staked_amount_before = self.total_staked_amount.load(storage)?;
+        let total_staked_amount_after = total_staked_amount_before
+            .checked_sub(Uint128::from(user_staked_amount_before))?
+            .checked_add(Uint128::from(user_staked_amount_after))?;
+
+        if total_staked_amount_before != total_staked_amount_after {
+            self.total_staked_amount
+                .save(storage, &total_staked_amount_after, env.block.height)?;
+        }
+
+        // Stakers are kept with zero power so their history stays listed
+        if user_staked_amount_before != user_staked_amount_after {
+            self.users_staked_amounts.save(
+                storage,
+                sender.clone(),
+                &Uint128::from(user_staked_amount_after),
+                env.block.height,
+            )?;
+        }
+
+        Ok(UpdateStakeResult {
+            user_staked_amount: Uint128::from(user_staked_amount_before),
+            total_staked_amount: total_staked_amount_before,
+            next_user_staked_amount: Uint128::from(user_staked_amount_after),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        contract::{
+            self,
+            sv::mt::{CodeId, NftVaultContractProxy},
+        },
+        error::ContractError,
+        helpers::UpdateStakeResult,
+        msg::ReceiveMsg,
+        roles::Role,
+        state::{
+            AirdropShare, BoostCollection, CollectedFee, CollectionUnstakingDuration, Config, Fee,
+            FeeAsset, FeeKind, FeeRecipient, LoyaltyTier, Nft, NftUser, SaleFee, StakedNft,
+        },
+    };
+
+    use cosmwasm_std::{
+        coin, coins, testing::mock_env, to_json_binary, Addr, Binary, Empty, Response, StdError,
+        StdResult, Timestamp, Uint128,
+    };
+    use cw_utils::Expiration;
+    use stake_derivative::contract::sv::mt::{
+        CodeId as StakeDerivativeCodeId, StakeDerivativeContractProxy,
+    };
+    use stake_receipt::contract::sv::mt::{
+        CodeId as StakeReceiptCodeId, StakeReceiptContractProxy,
+    };
+    use stake_rewards::{
+        contract::sv::mt::{CodeId as StakeRewardsCodeId, StakeExternalRewardsContractProxy},
+        state::{Accounting, Emission, EmissionSegment, RewardAsset, UndistributedMode},
+    };
+    use std::collections::HashMap;
+    use sylvia::{
+        cw_multi_test::{App as CwApp, ContractWrapper, Executor, IntoAddr},
+        multitest::{App, Proxy},
+    };
+    use uju_cw2_common::error::CommonError;
+
+    /// Accepts any message, standing in for an NFT collection
+    fn mock_collection(app: &App<CwApp>, owner: &Addr) -> Addr {
+        let collection_code_id = app.app_mut().store_code(Box::new(ContractWrapper::new(
+            |_, _, _, _: Empty| -> StdResult<Response> { Ok(Response::new()) },
+            |_, _, _, _: Empty| -> StdResult<Response> { Ok(Response::new()) },
+            |_, _, _: Empty| -> StdResult<Binary> { Ok(Default::default()) },
+        )));
+
+        app.app_mut()
+            .instantiate_contract(
+                collection_code_id,
+                owner.clone(),
+                &Empty {},
+                &[],
+                "collection",
+                None,
+            )
+            .unwrap()
+    }
+
+    fn instantiate_vault<'app>(
+        app: &'app App<CwApp>,
+        owner: &Addr,
+        collection: &Addr,
+        warmup_duration_sec: u64,
+    ) -> Proxy<'app, CwApp, contract::NftVaultContract> {
+        CodeId::store_code(app)
+            .instantiate(
+                Config {
+                    rewards_code_id: StakeRewardsCodeId::store_code(app).code_id(),
+                    collections: vec![collection.to_string()],
+                    unstaking_duration_sec: 60,
+                    receipt_code_id: None,
+                    derivative_code_id: None,
+                    warmup_duration_sec,
+                    loyalty_tiers: vec![],
+                    collection_unstaking_durations: vec![],
+                    stake_fee: None,
+                    unstake_fee: None,
+                    sale_fee: None,
+                    boost_collections: vec![],
+                    max_boosts: 0,
+                },
+                None,
+            )
+            .call(owner)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_update_stake_amounts() {
+        let app: App<CwApp> = App::default();
+        let mut app_mut = app.app_mut();
+
+        let user1 = "user1".into_addr();
+
+        let collection1 = "collection1".into_addr();
+        let collection2 = "collection2".into_addr();
+
+        let nft_vault = contract::NftVaultContract::new();
+
+        nft_vault
+            .users_collection_staked_amounts
+            .save(
+                app_mut.storage_mut(),
+                (user1.clone(), collection1.clone()),
+                &4,
+            )
+            .unwrap();
+        nft_vault
+            .users_collection_staked_amounts
+            .save(
+                app_mut.storage_mut(),
+                (user1.clone(), collection2.clone()),
+                &7,
+            )
+            .unwrap();
+        nft_vault
+            .total_staked_amount
+            .save(app_mut.storage_mut(), &Uint128::new(4), 0)
+            .unwrap();
+
+        let mut env = mock_env();
+        env.block.height = 1;
+
+        let config = Config::<Addr> {
+            rewards_code_id: 0,
+            unstaking_duration_sec: 60,
+            collections: vec![collection1.clone(), collection2.clone()],
+            receipt_code_id: None,
+            derivative_code_id: None,
+            warmup_duration_sec: 0,
+            loyalty_tiers: vec![],
+            collection_unstaking_durations: vec![],
+            stake_fee: None,
+            unstake_fee: None,
+            sale_fee: None,
+            boost_collections: vec![],
+            max_boosts: 0,
+        };
+
+        let mut collection_deltas: HashMap<Addr, i64> = HashMap::new();
+        collection_deltas.insert(collection1.clone(), 6);
+        collection_deltas.insert(collection2.clone(), 8);
+
+        let _result = nft_vault.update_stake_amounts(
+            app_mut.storage_mut(),
+            &env,
+            config,
+            &user1,
+            collection_deltas,
+        );
+
+        let user_collection_staked_amount_1 = nft_vault
+            .users_collection_staked_amounts
+            .load(app_mut.storage_mut(), (user1.clone(), collection1.clone()))
+            .unwrap();
+        assert_eq!(user_collection_staked_amount_1, 10);
+
+        let user_collection_staked_amount_2 = nft_vault
+            .users_collection_staked_amounts
+            .load(app_mut.storage_mut(), (user1.clone(), collection2.clone()))
+            .unwrap();
+        assert_eq!(user_collection_staked_amount_2, 15);
+
+        let total_staked_amount = nft_vault
+            .total_staked_amount
+            .load(app_mut.storage_mut())
+            .unwrap();
+        assert_eq!(total_staked_amount, Uint128::new(10));
+    }
+
+    #[test]
+    fn test_stake_change_messages_with_derivative() {
+        let app: App<CwApp> = App::default();
+        let mut app_mut = app.app_mut();
+
+        let user1 = "user1".into_addr();
+        let reward_account = "reward_account".into_addr();
+        let derivative_token = "derivative_token".into_addr();
+
+        let nft_vault = contract::NftVaultContract::new();
+
+        nft_vault
+            .derivative_token
+            .save(app_mut.storage_mut(), &derivative_token)
+            .unwrap();
+        nft_vault
+            .derivative_supply
+            .save(app_mut.storage_mut(), &Uint128::new(5))
+            .unwrap();
+        nft_vault
+            .derivative_balances
+            .save(app_mut.storage_mut(), user1.clone(), &Uint128::new(2))
+            .unwrap();
+
+        // Staking power added is minted to the staker
+        let sub_msgs = nft_vault
+            .stake_change_messages(
+                app_mut.storage_mut(),
+                &mock_env(),
+                std::slice::from_ref(&reward_account),
+                &user1,
+                &UpdateStakeResult {
+                    user_staked_amount: Uint128::new(2),
+                    total_staked_amount: Uint128::new(5),
+                    next_user_staked_amount: Uint128::new(4),
+                },
+                true,
+            )
+            .unwrap();
+        assert_eq!(sub_msgs.len(), 2);
+        assert_eq!(
+            nft_vault
+                .derivative_balances
+                .load(app_mut.storage_mut(), user1.clone())
+                .unwrap(),
+            Uint128::new(4)
+        );
+        assert_eq!(
+            nft_vault
+                .derivative_supply
+                .load(app_mut.storage_mut())
+                .unwrap(),
+            Uint128::new(7)
+        );
+
+        // Unstaking more than the derivative balance fails
+        nft_vault
+            .derivative_balances
+            .save(app_mut.storage_mut(), user1.clone(), &Uint128::new(1))
+            .unwrap();
+        let update_stake_result = UpdateStakeResult {
+            user_staked_amount: Uint128::new(4),
+            total_staked_amount: Uint128::new(7),
+            next_user_staked_amount: Uint128::new(1),
+        };
+        let err = nft_vault
+            .stake_change_messages(
+                app_mut.storage_mut(),
+                &mock_env(),
+                std::slice::from_ref(&reward_account),
+                &user1,
+                &update_stake_result,
+                true,
+            )
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::CommonError(CommonError::InsufficientFunds(_))
+        ));
+
+        // Forced removals only redeem the remaining balance
+        let sub_msgs = nft_vault
+            .stake_change_messages(
+                app_mut.storage_mut(),
+                &mock_env(),
+                &[reward_account],
+                &user1,
+                &update_stake_result,
+                false,
+            )
+            .unwrap();
+        assert_eq!(sub_msgs.len(), 2);
+        assert_eq!(
+            nft_vault
+                .derivative_balances
+                .load(app_mut.storage_mut(), user1)
+                .unwrap(),
+            Uint128::zero()
+        );
+        assert_eq!(
+            nft_vault
+                .derivative_supply
+                .load(app_mut.storage_mut())
+                .unwrap(),
+            Uint128::new(6)
+        );
+    }
+
+    #[test]
+    fn test_sudo() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+        let new_owner = "new_owner".into_addr();
+        let user1 = "user1".into_addr();
+
+        let collection1 = mock_collection(&app, &owner);
+        let nft_vault = instantiate_vault(&app, &owner, &collection1, 0);
+
+        let nft = Nft {
+            collection: collection1.to_string(),
+            token_id: "1".to_string(),
+        };
+
+        // Paused by governance
+        nft_vault.set_paused(true).unwrap();
+        let err = nft_vault.stake(vec![nft.clone()]).call(&user1).unwrap_err();
+        assert_eq!(err, ContractError::Paused);
+
+        nft_vault.set_paused(false).unwrap();
+        assert!(!nft_vault.status().unwrap().paused);
+
+        // Owner replaced by governance
+        nft_vault.set_owner(Some(new_owner.to_string())).unwrap();
+        assert_eq!(
+            nft_vault.ownership().unwrap().owner,
+            Some(new_owner.clone())
+        );
+        assert!(nft_vault.pause().call(&owner).is_err());
+        nft_vault.pause().call(&new_owner).unwrap();
+        nft_vault.unpause().call(&new_owner).unwrap();
+
+        // Stake directly in storage, as there is no cw721 to transfer from
+        {
+            let contract = contract::NftVaultContract::new();
+            let staked_at = app.block_info().time;
+            let mut app_mut = app.app_mut();
+            let mut storage = app_mut.contract_storage_mut(&nft_vault.contract_addr);
+
+            contract
+                .users_staked_nfts
+                .save(
+                    storage.as_mut(),
+                    (collection1.clone(), "1".to_string()),
+                    &StakedNft {
+                        staker: user1.clone(),
+                        nft: Nft {
+                            collection: collection1.clone(),
+                            token_id: "1".to_string(),
+                        },
+                        has_receipt: false,
+                        staked_at,
+                        user: None,
+                    },
+                )
+                .unwrap();
+            contract
+                .users_collection_staked_amounts
+                .save(storage.as_mut(), (user1.clone(), collection1.clone()), &1)
+                .unwrap();
+            contract
+                .total_staked_amount
+                .save(storage.as_mut(), &Uint128::one(), 1)
+                .unwrap();
+        }
+
+        // Emergency mode blocks staking, but lets NFTs out without an unstaking period
+        nft_vault.set_emergency_mode(true).unwrap();
+        let err = nft_vault.stake(vec![nft.clone()]).call(&user1).unwrap_err();
+        assert_eq!(err, ContractError::EmergencyMode);
+
+        nft_vault.unstake(vec![nft]).call(&user1).unwrap();
+        assert!(nft_vault.claims(user1.to_string()).unwrap().is_empty());
+        assert_eq!(
+            nft_vault
+                .users_collection_staked_amounts(user1.to_string(), Default::default())
+                .unwrap(),
+            vec![(collection1, 0)]
+        );
+    }
+
+    #[test]
+    fn test_warmup() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+        let user1 = "user1".into_addr();
+
+        let collection1 = mock_collection(&app, &owner);
+        let nft_vault = instantiate_vault(&app, &owner, &collection1, 100);
+
+        let nft = |token_id: &str| Nft {
+            collection: collection1.to_string(),
+            token_id: token_id.to_string(),
+        };
+
+        nft_vault.stake(vec![nft("1")]).call(&user1).unwrap();
+
+        // Newly staked NFTs do not contribute staking power until warmed up
+        let staking_power = nft_vault.staking_power(user1.to_string()).unwrap();
+        assert_eq!(staking_power.active, Uint128::zero());
+        assert_eq!(staking_power.pending, Uint128::one());
+        assert_eq!(
+            staking_power.next_activation,
+            Some(Timestamp::from_seconds(
+                app.block_info().time.seconds() + 100
+            ))
+        );
+
+        app.update_block(|block| block.time = block.time.plus_seconds(50));
+        assert!(nft_vault
+            .activate(Some(user1.to_string()))
+            .call(&owner)
+            .is_err());
+
+        // Lazily activated on the next interaction
+        nft_vault.stake(vec![nft("2")]).call(&user1).unwrap();
+        app.update_block(|block| block.time = block.time.plus_seconds(50));
+        nft_vault.stake(vec![nft("3")]).call(&user1).unwrap();
+
+        let staking_power = nft_vault.staking_power(user1.to_string()).unwrap();
+        assert_eq!(staking_power.active, Uint128::one());
+        assert_eq!(staking_power.pending, Uint128::new(2));
+
+        // Unstaking a pending NFT does not touch active staking power
+        nft_vault.unstake(vec![nft("3")]).call(&user1).unwrap();
+
+        // Activated by anyone once warmed up
+        app.update_block(|block| block.time = block.time.plus_seconds(50));
+        nft_vault
+            .activate(Some(user1.to_string()))
+            .call(&owner)
+            .unwrap();
+
+        let staking_power = nft_vault.staking_power(user1.to_string()).unwrap();
+        assert_eq!(staking_power.active, Uint128::new(2));
+        assert_eq!(staking_power.pending, Uint128::zero());
+        assert_eq!(staking_power.next_activation, None);
+    }
+
+    #[test]
+    fn test_loyalty_tiers() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+        let user1 = "user1".into_addr();
+
+        let collection1 = mock_collection(&app, &owner);
+        let nft_vault = instantiate_vault(&app, &owner, &collection1, 0);
+
+        nft_vault
+            .stake(vec![Nft {
+                collection: collection1.to_string(),
+                token_id: "1".to_string(),
+            }])
+            .call(&user1)
+            .unwrap();
+
+        let reward_weight = nft_vault.reward_weight(user1.to_string()).unwrap();
+        assert_eq!(reward_weight.reward_weight, Uint128::new(10_000));
+        assert_eq!(reward_weight.total_reward_weight, Uint128::new(10_000));
+
+        // Tiers must have distinct durations
+        assert!(nft_vault
+            .update_config(
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some(vec![
+                    LoyaltyTier {
+                        min_duration_sec: 100,
+                        bonus_bps: 1_000,
+                    },
+                    LoyaltyTier {
+                        min_duration_sec: 100,
+                        bonus_bps: 2_000,
+                    },
+                ]),
+                None,
+            )
+            .call(&owner)
+            .is_err());
+
+        nft_vault
+            .update_config(
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some(vec![
+                    LoyaltyTier {
+                        min_duration_sec: 200,
+                        bonus_bps: 5_000,
+                    },
+                    LoyaltyTier {
+                        min_duration_sec: 100,
+                        bonus_bps: 2_000,
+                    },
+                ]),
+                None,
+            )
+            .call(&owner)
+            .unwrap();
+
+        // Nothing to settle before the first tier is reached
+        assert!(nft_vault
+            .poke(Some(user1.to_string()))
+            .call(&owner)
+            .is_err());
+
+        app.update_block(|block| block.time = block.time.plus_seconds(100));
+
+        let reward_weight = nft_vault.reward_weight(user1.to_string()).unwrap();
+        assert_eq!(reward_weight.reward_weight, Uint128::new(10_000));
+        assert_eq!(reward_weight.latest_reward_weight, Uint128::new(12_000));
+
+        // Reward accounts only see the settled weight
+        assert_eq!(
+            nft_vault.rewards_staked_amount(user1.to_string()).unwrap(),
+            Uint128::new(10_000)
+        );
+
+        nft_vault
+            .poke(Some(user1.to_string()))
+            .call(&owner)
+            .unwrap();
+
+        app.update_block(|block| block.time = block.time.plus_seconds(100));
+        nft_vault.poke(None).call(&user1).unwrap();
+
+        let reward_weight = nft_vault.reward_weight(user1.to_string()).unwrap();
+        assert_eq!(reward_weight.reward_weight, Uint128::new(15_000));
+        assert_eq!(reward_weight.total_reward_weight, Uint128::new(15_000));
+        assert_eq!(
+            nft_vault.rewards_staked_amount(user1.to_string()).unwrap(),
+            Uint128::new(15_000)
+        );
+
+        // Unstaking resets the streak
+        nft_vault
+            .unstake(vec![Nft {
+                collection: collection1.to_string(),
+                token_id: "1".to_string(),
+            }])
+            .call(&user1)
+            .unwrap();
+
+        let reward_weight = nft_vault.reward_weight(user1.to_string()).unwrap();
+        assert_eq!(reward_weight.reward_weight, Uint128::zero());
+        assert_eq!(reward_weight.total_reward_weight, Uint128::zero());
+    }
+
+    #[test]
+    fn test_collection_unstaking_durations() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+        let user1 = "user1".into_addr();
+
+        let collection1 = mock_collection(&app, &owner);
+        let collection2 = mock_collection(&app, &owner);
+
+        // Overrides must target collections of the vault
+        assert!(CodeId::store_code(&app)
+            .instantiate(
+                Config {
+                    rewards_code_id: 0,
+                    collections: vec![collection1.to_string()],
+                    unstaking_duration_sec: 60,
+                    receipt_code_id: None,
+                    derivative_code_id: None,
+                    warmup_duration_sec: 0,
+                    loyalty_tiers: vec![],
+                    collection_unstaking_durations: vec![CollectionUnstakingDuration {
+                        collection: collection2.to_string(),
+                        unstaking_duration_sec: 120,
+                    }],
+                    stake_fee: None,
+                    unstake_fee: None,
+                    sale_fee: None,
+                    boost_collections: vec![],
+                    max_boosts: 0,
+                },
+                None,
+            )
+            .call(&owner)
+            .is_err());
+
+        let nft_vault = CodeId::store_code(&app)
+            .instantiate(
+                Config {
+                    rewards_code_id: 0,
+                    collections: vec![collection1.to_string(), collection2.to_string()],
+                    unstaking_duration_sec: 60,
+                    receipt_code_id: None,
+                    derivative_code_id: None,
+                    warmup_duration_sec: 0,
+                    loyalty_tiers: vec![],
+                    collection_unstaking_durations: vec![CollectionUnstakingDuration {
+                        collection: collection2.to_string(),
+                        unstaking_duration_sec: 120,
+                    }],
+                    stake_fee: None,
+                    unstake_fee: None,
+                    sale_fee: None,
+                    boost_collections: vec![],
+                    max_boosts: 0,
+                },
+                None,
+            )
+            .call(&owner)
+            .unwrap();
+
+        let config = nft_vault.config().unwrap();
+        assert_eq!(
+            config
+                .unstaking_durations
+                .iter()
+                .map(|cud| (cud.collection.clone(), cud.unstaking_duration_sec))
+                .collect::<Vec<_>>(),
+            {
+                let mut unstaking_durations =
+                    vec![(collection1.clone(), 60), (collection2.clone(), 120)];
+                unstaking_durations.sort();
+                unstaking_durations
+            }
+        );
+
+        let nfts = vec![
+            Nft {
+                collection: collection1.to_string(),
+                token_id: "1".to_string(),
+            },
+            Nft {
+                collection: collection2.to_string(),
+                token_id: "1".to_string(),
+            },
+        ];
+        nft_vault.stake(nfts.clone()).call(&user1).unwrap();
+        nft_vault.unstake(nfts).call(&user1).unwrap();
+
+        // Unstaked NFTs are split into a claim per release time
+        let now = app.block_info().time;
+        let claims = nft_vault.claims(user1.to_string()).unwrap();
+        assert_eq!(claims.len(), 2);
+        assert_eq!(claims[0].nfts[0].collection, collection1);
+        assert_eq!(
+            claims[0].release_at,
+            Expiration::AtTime(now.plus_seconds(60))
+        );
+        assert_eq!(claims[1].nfts[0].collection, collection2);
+        assert_eq!(
+            claims[1].release_at,
+            Expiration::AtTime(now.plus_seconds(120))
+        );
+    }
+
+    #[test]
+    fn test_fees() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+        let user1 = "user1".into_addr();
+        let treasury = "treasury".into_addr();
+
+        app.app_mut().init_modules(|router, _, storage| {
+            router
+                .bank
+                .init_balance(storage, &user1, coins(100, "ujuno"))
+                .unwrap();
+        });
+
+        let collection1 = mock_collection(&app, &owner);
+        let nft_vault = instantiate_vault(&app, &owner, &collection1, 0);
+
+        let fee = |amount: u128| Fee {
+            asset: FeeAsset::Native("ujuno".to_string()),
+            amount: Uint128::new(amount),
+            recipient: FeeRecipient::Treasury(treasury.to_string()),
+        };
+
+        assert!(nft_vault
+            .update_fees(Some(fee(10)), Some(fee(5)), None, None)
+            .call(&user1)
+            .is_err());
+
+        // Reward account recipients must belong to the vault
+        let err = nft_vault
+            .update_fees(
+                Some(Fee {
+                    asset: FeeAsset::Native("ujuno".to_string()),
+                    amount: Uint128::new(10),
+                    recipient: FeeRecipient::RewardAccount(treasury.to_string()),
+                }),
+                None,
+                None,
+                None,
+            )
+            .call(&owner)
+            .unwrap_err();
+        assert_eq!(err, ContractError::RewardAccountNotFound);
+
+        nft_vault
+            .update_fees(Some(fee(10)), Some(fee(5)), None, None)
+            .call(&owner)
+            .unwrap();
+
+        let nfts = vec![
+            Nft {
+                collection: collection1.to_string(),
+                token_id: "1".to_string(),
+            },
+            Nft {
+                collection: collection1.to_string(),
+                token_id: "2".to_string(),
+            },
+        ];
+
+        // The fee is charged per NFT
+        assert!(nft_vault.stake(nfts.clone()).call(&user1).is_err());
+        assert!(nft_vault
+            .stake(nfts.clone())
+            .with_funds(&coins(10, "ujuno"))
+            .call(&user1)
+            .is_err());
+
+        nft_vault
+            .stake(nfts.clone())
+            .with_funds(&coins(20, "ujuno"))
+            .call(&user1)
+            .unwrap();
+
+        nft_vault
+            .unstake(nfts[..1].to_vec())
+            .with_funds(&coins(5, "ujuno"))
+            .call(&user1)
+            .unwrap();
+
+        let balance = app.app().wrap().query_balance(&treasury, "ujuno").unwrap();
+        assert_eq!(balance.amount, Uint128::new(25));
+
+        let collected_fees = nft_vault.collected_fees().unwrap();
+        assert_eq!(
+            collected_fees,
+            vec![CollectedFee {
+                asset: FeeAsset::Native("ujuno".to_string()),
+                amount: Uint128::new(25),
+            }]
+        );
+
+        // Updating one fee leaves the others unchanged
+        let sale_fee = SaleFee {
+            fee_bps: 500,
+            recipient: treasury.to_string(),
+        };
+        nft_vault
+            .update_fees(None, None, Some(sale_fee), None)
+            .call(&owner)
+            .unwrap();
+
+        let config = nft_vault.config().unwrap().config;
+        let internal_fee = |amount: u128| Fee {
+            asset: FeeAsset::Native("ujuno".to_string()),
+            amount: Uint128::new(amount),
+            recipient: FeeRecipient::Treasury(treasury.clone()),
+        };
+        assert_eq!(config.stake_fee, Some(internal_fee(10)));
+        assert_eq!(config.unstake_fee, Some(internal_fee(5)));
+        assert_eq!(
+            config.sale_fee,
+            Some(SaleFee {
+                fee_bps: 500,
+                recipient: treasury.clone(),
+            })
+        );
+
+        // Fees are removed only when listed, and cannot be set at the same time
+        assert!(nft_vault
+            .update_fees(Some(fee(10)), None, None, Some(vec![FeeKind::Stake]))
+            .call(&owner)
+            .is_err());
+
+        nft_vault
+            .update_fees(
+                None,
+                None,
+                None,
+                Some(vec![FeeKind::Stake, FeeKind::Unstake]),
+            )
+            .call(&owner)
+            .unwrap();
+
+        let config = nft_vault.config().unwrap().config;
+        assert_eq!(config.stake_fee, None);
+        assert_eq!(config.unstake_fee, None);
+        assert!(config.sale_fee.is_some());
+
+        nft_vault.unstake(nfts[1..].to_vec()).call(&user1).unwrap();
+    }
+
+    #[test]
+    fn test_reward_account_fee_after_period_finish() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+        let user1 = "user1".into_addr();
+
+        app.app_mut().init_modules(|router, _, storage| {
+            router
+                .bank
+                .init_balance(storage, &owner, coins(1000, "ujuno"))
+                .unwrap();
+            router
+                .bank
+                .init_balance(storage, &user1, coins(100, "ujuno"))
+                .unwrap();
+        });
+
+        let collection1 = mock_collection(&app, &owner);
+        let nft_vault = instantiate_vault(&app, &owner, &collection1, 0);
+
+        let start = app.block_info().time;
+        nft_vault
+            .create_reward_account(
+                "rewards".to_string(),
+                RewardAsset::Native("ujuno".to_string()),
+                vec![EmissionSegment {
+                    start,
+                    end: start.plus_seconds(100),
+                    emission: Emission::Amount(Uint128::new(1000)),
+                }],
+                None,
+                UndistributedMode::Withdraw,
+                0,
+                None,
+                None,
+            )
+            .with_funds(&coins(1000, "ujuno"))
+            .call(&owner)
+            .unwrap();
+        let reward_account = nft_vault.reward_accounts().unwrap()[0].clone();
+
+        nft_vault
+            .update_fees(
+                None,
+                Some(Fee {
+                    asset: FeeAsset::Native("ujuno".to_string()),
+                    amount: Uint128::new(5),
+                    recipient: FeeRecipient::RewardAccount(reward_account.to_string()),
+                }),
+                None,
+                None,
+            )
+            .call(&owner)
+            .unwrap();
+
+        let nft = Nft {
+            collection: collection1.to_string(),
+            token_id: "1".to_string(),
+        };
+        nft_vault.stake(vec![nft.clone()]).call(&user1).unwrap();
+
+        // The fee cannot be emitted once the period finished, but unstaking still works
+        app.update_block(|block| block.time = block.time.plus_seconds(150));
+        nft_vault
+            .unstake(vec![nft])
+            .with_funds(&coins(5, "ujuno"))
+            .call(&user1)
+            .unwrap();
+
+        let reward_account: Proxy<
+            '_,
+            CwApp,
+            stake_rewards::contract::StakeExternalRewardsContract,
+        > = Proxy::new(reward_account, &app);
+        let balance_sheet = reward_account
+            .balance_sheet(RewardAsset::Native("ujuno".to_string()))
+            .unwrap();
+        assert_eq!(balance_sheet.funded, Uint128::new(1005));
+        assert_eq!(balance_sheet.undistributed, Uint128::new(5));
+
+        // The funder withdraws it
+        reward_account
+            .withdraw_undistributed(None)
+            .call(&owner)
+            .unwrap();
+        let balance = app.app().wrap().query_balance(&owner, "ujuno").unwrap();
+        assert_eq!(balance.amount, Uint128::new(5));
+    }
+
+    #[test]
+    fn test_receipt_burn_unstake_fee() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+        let user1 = "user1".into_addr();
+        let treasury = "treasury".into_addr();
+
+        app.app_mut().init_modules(|router, _, storage| {
+            router
+                .bank
+                .init_balance(storage, &user1, coins(100, "ujuno"))
+                .unwrap();
+        });
+
+        let collection1 = mock_collection(&app, &owner);
+        let nft_vault = instantiate_vault(&app, &owner, &collection1, 0);
+
+        nft_vault
+            .update_config(
+                None,
+                None,
+                Some(StakeReceiptCodeId::store_code(&app).code_id()),
+                None,
+                None,
+                None,
+                None,
+            )
+            .call(&owner)
+            .unwrap();
+        nft_vault
+            .create_receipt_collection(
+                "receipts".to_string(),
+                "Receipts".to_string(),
+                "RCPT".to_string(),
+            )
+            .call(&owner)
+            .unwrap();
+        let receipt_collection: Proxy<'_, CwApp, stake_receipt::contract::StakeReceiptContract> =
+            Proxy::new(nft_vault.receipt_collection().unwrap().unwrap(), &app);
+
+        nft_vault
+            .update_fees(
+                None,
+                Some(Fee {
+                    asset: FeeAsset::Native("ujuno".to_string()),
+                    amount: Uint128::new(5),
+                    recipient: FeeRecipient::Treasury(treasury.to_string()),
+                }),
+                None,
+                None,
+            )
+            .call(&owner)
+            .unwrap();
+
+        let nft = |token_id: &str| Nft {
+            collection: collection1.to_string(),
+            token_id: token_id.to_string(),
+        };
+        let receipt_id = |token_id: &str| format!("{}-{}", collection1, token_id);
+        let burn_receipt = |token_id: &str| {
+            app.app_mut().execute_contract(
+                user1.clone(),
+                receipt_collection.contract_addr.clone(),
+                &stake_receipt::contract::sv::ExecMsg::Burn {
+                    token_id: receipt_id(token_id),
+                },
+                &[],
+            )
+        };
+
+        nft_vault
+            .stake(vec![nft("1"), nft("2")])
+            .call(&user1)
+            .unwrap();
+
+        // Burning the receipt cannot avoid the unstake fee
+        assert!(burn_receipt("1").is_err());
+        assert_eq!(receipt_collection.num_tokens().unwrap().count, 2);
+        assert_eq!(
+            nft_vault
+                .users_staked_nfts(user1.to_string(), Default::default())
+                .unwrap()
+                .len(),
+            2
+        );
+
+        nft_vault
+            .unstake(vec![nft("1")])
+            .with_funds(&coins(5, "ujuno"))
+            .call(&user1)
+            .unwrap();
+        assert_eq!(
+            app.app()
+                .wrap()
+                .query_balance(&treasury, "ujuno")
+                .unwrap()
+                .amount,
+            Uint128::new(5)
+        );
+
+        // Exits in emergency mode are free, burning the receipt included
+        nft_vault.set_emergency_mode(true).unwrap();
+        burn_receipt("2").unwrap();
+        assert_eq!(receipt_collection.num_tokens().unwrap().count, 0);
+        assert!(nft_vault
+            .users_staked_nfts(user1.to_string(), Default::default())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_boosts() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+        let user1 = "user1".into_addr();
+
+        let collection1 = mock_collection(&app, &owner);
+        let boost_collection = mock_collection(&app, &owner);
+        let nft_vault = instantiate_vault(&app, &owner, &collection1, 0);
+
+        let boost = |token_id: &str| Nft {
+            collection: boost_collection.to_string(),
+            token_id: token_id.to_string(),
+        };
+
+        // Staked collections cannot boost
+        assert!(nft_vault
+            .update_boosts(
+                vec![BoostCollection {
+                    collection: collection1.to_string(),
+                    bonus_bps: 5_000,
+                }],
+                1,
+            )
+            .call(&owner)
+            .is_err());
+
+        nft_vault
+            .update_boosts(
+                vec![BoostCollection {
+                    collection: boost_collection.to_string(),
+                    bonus_bps: 5_000,
+                }],
+                1,
+            )
+            .call(&owner)
+            .unwrap();
+
+        nft_vault
+            .stake(vec![Nft {
+                collection: collection1.to_string(),
+                token_id: "1".to_string(),
+            }])
+            .call(&user1)
+            .unwrap();
+
+        // Boosters only multiply rewards, staking power is unchanged
+        nft_vault.lock_boost(boost("1")).call(&user1).unwrap();
+        assert_eq!(nft_vault.boosts(user1.to_string()).unwrap().len(), 1);
+
+        let staking_power = nft_vault.staking_power(user1.to_string()).unwrap();
+        assert_eq!(staking_power.active, Uint128::one());
+
+        let reward_weight = nft_vault.reward_weight(user1.to_string()).unwrap();
+        assert_eq!(reward_weight.reward_weight, Uint128::new(15_000));
+        assert_eq!(reward_weight.total_reward_weight, Uint128::new(15_000));
+
+        // Boost slots are limited
+        assert!(nft_vault.lock_boost(boost("2")).call(&user1).is_err());
+
+        assert!(nft_vault.unlock_boost(boost("1")).call(&owner).is_err());
+        nft_vault.unlock_boost(boost("1")).call(&user1).unwrap();
+        assert!(nft_vault.boosts(user1.to_string()).unwrap().is_empty());
+
+        let reward_weight = nft_vault.reward_weight(user1.to_string()).unwrap();
+        assert_eq!(reward_weight.reward_weight, Uint128::new(10_000));
+        assert_eq!(reward_weight.total_reward_weight, Uint128::new(10_000));
+    }
+
+    #[test]
+    fn test_staking_powers_at_height() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+        let user1 = "user1".into_addr();
+        let user2 = "user2".into_addr();
+
+        let collection1 = mock_collection(&app, &owner);
+        let nft_vault = instantiate_vault(&app, &owner, &collection1, 0);
+
+        let nft = |token_id: &str| Nft {
+            collection: collection1.to_string(),
+            token_id: token_id.to_string(),
+        };
+
+        let height = app.block_info().height;
+        nft_vault
+            .stake(vec![nft("1"), nft("2")])
+            .call(&user1)
+            .unwrap();
+        nft_vault.stake(vec![nft("3")]).call(&user2).unwrap();
+
+        app.update_block(|block| block.height += 1);
+        nft_vault.unstake(vec![nft("1")]).call(&user1).unwrap();
+
+        app.update_block(|block| block.height += 1);
+
+        let powers = |height: u64| {
+            let mut powers = nft_vault
+                .staking_powers_at_height(height, Default::default())
+                .unwrap();
+            powers.sort();
+            powers
+        };
+
+        let mut expected = vec![
+            (user1.clone(), Uint128::zero()),
+            (user2.clone(), Uint128::zero()),
+        ];
+        expected.sort();
+        assert_eq!(powers(height), expected);
+
+        let mut expected = vec![
+            (user1.clone(), Uint128::new(2)),
+            (user2.clone(), Uint128::one()),
+        ];
+        expected.sort();
+        assert_eq!(powers(height + 1), expected);
+
+        let mut expected = vec![
+            (user1.clone(), Uint128::one()),
+            (user2.clone(), Uint128::one()),
+        ];
+        expected.sort();
+        assert_eq!(powers(height + 2), expected);
+    }
+
+    #[test]
+    fn test_airdrop() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+        let user1 = "user1".into_addr();
+        let user2 = "user2".into_addr();
+        let user3 = "user3".into_addr();
+
+        app.app_mut().init_modules(|router, _, storage| {
+            router
+                .bank
+                .init_balance(storage, &owner, coins(100, "ujuno"))
+                .unwrap();
+        });
+
+        let collection1 = mock_collection(&app, &owner);
+        let nft_vault = instantiate_vault(&app, &owner, &collection1, 0);
+
+        let nft = |token_id: &str| Nft {
+            collection: collection1.to_string(),
+            token_id: token_id.to_string(),
+        };
+
+        let asset = FeeAsset::Native("ujuno".to_string());
+        let expiration = Expiration::AtHeight(app.block_info().height + 11);
+
+        // Nothing to share yet
+        assert!(nft_vault
+            .airdrop(asset.clone(), Uint128::new(100), expiration)
+            .with_funds(&coins(100, "ujuno"))
+            .call(&owner)
+            .is_err());
+
+        nft_vault
+            .stake(vec![nft("1"), nft("2")])
+            .call(&user1)
+            .unwrap();
+        nft_vault.stake(vec![nft("3")]).call(&user2).unwrap();
+
+        app.update_block(|block| block.height += 1);
+
+        // Stakes within the airdrop's block do not share in it
+        nft_vault.stake(vec![nft("4")]).call(&user3).unwrap();
+        nft_vault
+            .airdrop(asset.clone(), Uint128::new(100), expiration)
+            .with_funds(&coins(100, "ujuno"))
+            .call(&owner)
+            .unwrap();
+
+        let airdrops = nft_vault.airdrops(Default::default()).unwrap();
+        assert_eq!(airdrops.len(), 1);
+        assert_eq!(airdrops[0].id, 1);
+        assert_eq!(airdrops[0].total_staked_amount, Uint128::new(3));
+
+        assert!(nft_vault.claim_airdrop(1, None).call(&user3).is_err());
+
+        nft_vault.claim_airdrop(1, None).call(&user1).unwrap();
+        let err = nft_vault.claim_airdrop(1, None).call(&user1).unwrap_err();
+        assert_eq!(err, ContractError::AirdropAlreadyClaimed);
+
+        let balance = app.app().wrap().query_balance(&user1, "ujuno").unwrap();
+        assert_eq!(balance.amount, Uint128::new(66));
+        assert_eq!(
+            nft_vault.airdrop_share(1, user1.to_string()).unwrap(),
+            AirdropShare {
+                amount: Uint128::new(66),
+                claimed: true,
+            }
+        );
+        assert_eq!(
+            nft_vault.airdrop_share(1, user2.to_string()).unwrap(),
+            AirdropShare {
+                amount: Uint128::new(33),
+                claimed: false,
+            }
+        );
+
+        // Leftovers stay claimable until expiry
+        assert!(nft_vault.clawback_airdrop(1, None).call(&owner).is_err());
+
+        app.update_block(|block| block.height += 10);
+
+        let err = nft_vault.claim_airdrop(1, None).call(&user2).unwrap_err();
+        assert_eq!(err, ContractError::AirdropExpired);
+
+        assert!(nft_vault.clawback_airdrop(1, None).call(&user1).is_err());
+        nft_vault.clawback_airdrop(1, None).call(&owner).unwrap();
+        assert!(nft_vault.clawback_airdrop(1, None).call(&owner).is_err());
+
+        let balance = app.app().wrap().query_balance(&owner, "ujuno").unwrap();
+        assert_eq!(balance.amount, Uint128::new(34));
+    }
+
+    #[test]
+    fn test_nft_user() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+        let user1 = "user1".into_addr();
+        let user2 = "user2".into_addr();
+        let renter = "renter".into_addr();
+
+        let collection1 = mock_collection(&app, &owner);
+        let nft_vault = instantiate_vault(&app, &owner, &collection1, 0);
+
+        let nft = Nft {
+            collection: collection1.to_string(),
+            token_id: "1".to_string(),
+        };
+        let nft_user = |seconds: u64| NftUser {
+            user: renter.to_string(),
+            expires: app.block_info().time.plus_seconds(seconds),
+        };
+
+        assert!(nft_vault
+            .set_nft_user(nft.clone(), Some(nft_user(100)))
+            .call(&user1)
+            .is_err());
+
+        nft_vault.stake(vec![nft.clone()]).call(&user1).unwrap();
+
+        assert!(nft_vault
+            .set_nft_user(nft.clone(), Some(nft_user(100)))
+            .call(&user2)
+            .is_err());
+        assert!(nft_vault
+            .set_nft_user(nft.clone(), Some(nft_user(0)))
+            .call(&user1)
+            .is_err());
+
+        nft_vault
+            .set_nft_user(nft.clone(), Some(nft_user(100)))
+            .call(&user1)
+            .unwrap();
+        assert_eq!(
+            nft_vault.nft_user(nft.clone()).unwrap(),
+            Some(renter.clone())
+        );
+
+        // Lending does not affect the staker's power
+        let staking_power = nft_vault.staking_power(user1.to_string()).unwrap();
+        assert_eq!(staking_power.active, Uint128::one());
+
+        app.update_block(|block| block.time = block.time.plus_seconds(100));
+        assert_eq!(nft_vault.nft_user(nft.clone()).unwrap(), None);
+
+        nft_vault
+            .set_nft_user(nft.clone(), Some(nft_user(100)))
+            .call(&user1)
+            .unwrap();
+        nft_vault
+            .set_nft_user(nft.clone(), None)
+            .call(&user1)
+            .unwrap();
+        assert_eq!(nft_vault.nft_user(nft.clone()).unwrap(), None);
+
+        // Unstaking clears the user
+        nft_vault
+            .set_nft_user(nft.clone(), Some(nft_user(100)))
+            .call(&user1)
+            .unwrap();
+        nft_vault.unstake(vec![nft.clone()]).call(&user1).unwrap();
+        assert_eq!(nft_vault.nft_user(nft.clone()).unwrap(), None);
+    }
+
+    #[test]
+    fn test_listings() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+        let user1 = "user1".into_addr();
+        let buyer = "buyer".into_addr();
+        let treasury = "treasury".into_addr();
+
+        app.app_mut().init_modules(|router, _, storage| {
+            router
+                .bank
+                .init_balance(storage, &buyer, coins(300, "ujuno"))
+                .unwrap();
+        });
+
+        let collection1 = mock_collection(&app, &owner);
+        let nft_vault = instantiate_vault(&app, &owner, &collection1, 0);
+
+        let nft = |token_id: &str| Nft {
+            collection: collection1.to_string(),
+            token_id: token_id.to_string(),
+        };
+
+        nft_vault
+            .update_fees(
+                None,
+                Some(Fee {
+                    asset: FeeAsset::Native("ujuno".to_string()),
+                    amount: Uint128::new(5),
+                    recipient: FeeRecipient::Treasury(treasury.to_string()),
+                }),
+                Some(SaleFee {
+                    fee_bps: 500,
+                    recipient: treasury.to_string(),
+                }),
+                None,
+            )
+            .call(&owner)
+            .unwrap();
+
+        nft_vault
+            .stake(vec![nft("1"), nft("2")])
+            .call(&user1)
+            .unwrap();
+
+        assert!(nft_vault
+            .list_nft(nft("1"), coin(100, "ujuno"), None)
+            .call(&buyer)
+            .is_err());
+        nft_vault
+            .list_nft(nft("1"), coin(100, "ujuno"), None)
+            .call(&user1)
+            .unwrap();
+        assert!(nft_vault.delist_nft(nft("1")).call(&buyer).is_err());
+
+        // Sellers cannot buy their own listing to skip the unstaking period
+        let err = nft_vault.buy_nft(nft("1")).call(&user1).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CommonError(CommonError::InvalidInput(
+                "cannot buy own listing".to_string()
+            ))
+        );
+
+        // The buyer pays the unstake fee along with the price
+        for amount in [50, 100] {
+            let err = nft_vault
+                .buy_nft(nft("1"))
+                .with_funds(&coins(amount, "ujuno"))
+                .call(&buyer)
+                .unwrap_err();
+            assert_eq!(
+                err,
+                ContractError::CommonError(CommonError::InvalidInput(
+                    "105ujuno required".to_string()
+                ))
+            );
+        }
+
+        nft_vault
+            .buy_nft(nft("1"))
+            .with_funds(&coins(105, "ujuno"))
+            .call(&buyer)
+            .unwrap();
+
+        // The seller's position shrinks and the proceeds are split with the treasury
+        let staking_power = nft_vault.staking_power(user1.to_string()).unwrap();
+        assert_eq!(staking_power.active, Uint128::one());
+        assert!(nft_vault.listings(Default::default()).unwrap().is_empty());
+
+        let balance = app.app().wrap().query_balance(&user1, "ujuno").unwrap();
+        assert_eq!(balance.amount, Uint128::new(95));
+        let balance = app.app().wrap().query_balance(&treasury, "ujuno").unwrap();
+        assert_eq!(balance.amount, Uint128::new(10));
+
+        // The NFT is claimable by the buyer once the unstaking period is over
+        let now = app.block_info().time;
+        let claims = nft_vault.claims(buyer.to_string()).unwrap();
+        assert_eq!(claims.len(), 1);
+        assert_eq!(claims[0].nfts[0].token_id, "1");
+        assert_eq!(
+            claims[0].release_at,
+            Expiration::AtTime(now.plus_seconds(60))
+        );
+        assert_eq!(
+            nft_vault.claim(None).call(&buyer).unwrap_err(),
+            ContractError::ClaimableNftsNotFound
+        );
+
+        app.update_block(|block| block.time = block.time.plus_seconds(60));
+        nft_vault.claim(None).call(&buyer).unwrap();
+        assert!(nft_vault.claims(buyer.to_string()).unwrap().is_empty());
+
+        // Expired listings can no longer be bought
+        let now = app.block_info().time;
+        assert!(nft_vault
+            .list_nft(nft("2"), coin(100, "ujuno"), Some(Expiration::AtTime(now)))
+            .call(&user1)
+            .is_err());
+        nft_vault
+            .list_nft(
+                nft("2"),
+                coin(100, "ujuno"),
+                Some(Expiration::AtTime(now.plus_seconds(10))),
+            )
+            .call(&user1)
+            .unwrap();
+
+        app.update_block(|block| block.time = block.time.plus_seconds(10));
+        let err = nft_vault
+            .buy_nft(nft("2"))
+            .with_funds(&coins(105, "ujuno"))
+            .call(&buyer)
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CommonError(CommonError::InvalidInput("listing expired".to_string()))
+        );
+
+        // Unstaking clears the listing
+        nft_vault
+            .list_nft(nft("2"), coin(100, "ujuno"), None)
+            .call(&user1)
+            .unwrap();
+        assert_eq!(nft_vault.listings(Default::default()).unwrap().len(), 1);
+        nft_vault
+            .unstake(vec![nft("2")])
+            .with_funds(&coins(5, "ujuno"))
+            .call(&user1)
+            .unwrap();
+        assert!(nft_vault.listings(Default::default()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_receive_reward_funding() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+        let user1 = "user1".into_addr();
+
+        let collection1 = mock_collection(&app, &owner);
+        let nft_vault = instantiate_vault(&app, &owner, &collection1, 0);
+
+        // Accepts any message, standing in for the cw20 token
+        let cw20 = mock_collection(&app, &owner);
+
+        let start = app.block_info().time;
+        let schedule = vec![EmissionSegment {
+            start,
+            end: start.plus_seconds(100),
+            emission: Emission::Amount(Uint128::new(1000)),
+        }];
+        let create_msg = to_json_binary(&ReceiveMsg::CreateRewardAccount {
+            label: "rewards".to_string(),
+            schedule: schedule.clone(),
+            curve: None,
+            undistributed_mode: UndistributedMode::Withdraw,
+            grace_period_sec: 0,
+            vesting: None,
+            accounting: None,
+        })
+        .unwrap();
+
+        // Cw20 reward accounts are only created through the token
+        assert!(nft_vault
+            .create_reward_account(
+                "rewards".to_string(),
+                RewardAsset::Cw20(cw20.clone()),
+                schedule,
+                None,
+                UndistributedMode::Withdraw,
+                0,
+                None,
+                None,
+            )
+            .call(&owner)
+            .is_err());
+
+        // The token must be approved to vouch for its sender
+        assert!(nft_vault
+            .receive(owner.to_string(), Uint128::new(1000), create_msg.clone())
+            .call(&cw20)
+            .is_err());
+
+        // Only the owner approves reward tokens
+        assert!(nft_vault
+            .add_reward_token(cw20.to_string())
+            .call(&user1)
+            .is_err());
+        nft_vault
+            .add_reward_token(cw20.to_string())
+            .call(&owner)
+            .unwrap();
+        assert!(nft_vault
+            .add_reward_token(cw20.to_string())
+            .call(&owner)
+            .is_err());
+        assert_eq!(
+            nft_vault.reward_tokens(Default::default()).unwrap(),
+            vec![cw20.clone()]
+        );
+
+        // Only reward managers can create reward accounts
+        assert!(nft_vault
+            .receive(user1.to_string(), Uint128::new(1000), create_msg.clone())
+            .call(&cw20)
+            .is_err());
+
+        // Removed tokens can no longer create reward accounts
+        nft_vault
+            .remove_reward_token(cw20.to_string())
+            .call(&owner)
+            .unwrap();
+        assert!(nft_vault
+            .reward_tokens(Default::default())
+            .unwrap()
+            .is_empty());
+        let err = nft_vault
+            .receive(owner.to_string(), Uint128::new(1000), create_msg)
+            .call(&cw20)
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CommonError(CommonError::Unauthorized(
+                "sender is not an approved reward token".to_string()
+            ))
+        );
+
+        let err = nft_vault
+            .receive(
+                owner.to_string(),
+                Uint128::new(1000),
+                to_json_binary(&ReceiveMsg::FundRewardAccount {
+                    reward_account: user1.to_string(),
+                    extra_duration_sec: None,
+                })
+                .unwrap(),
+            )
+            .call(&cw20)
+            .unwrap_err();
+        assert_eq!(err, ContractError::RewardAccountNotFound);
+    }
+
+    #[test]
+    fn test_reward_account_accounting() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+        let user1 = "user1".into_addr();
+
+        app.app_mut().init_modules(|router, _, storage| {
+            router
+                .bank
+                .init_balance(storage, &owner, coins(2000, "ujuno"))
+                .unwrap();
+        });
+
+        let collection1 = mock_collection(&app, &owner);
+        let nft_vault = instantiate_vault(&app, &owner, &collection1, 0);
+
+        // Accepts any message and reports a balance of 1000, standing in for the cw20 token
+        let cw20_code_id = app.app_mut().store_code(Box::new(ContractWrapper::new(
+            |_, _, _, _: Empty| -> StdResult<Response> { Ok(Response::new()) },
+            |_, _, _, _: Empty| -> StdResult<Response> { Ok(Response::new()) },
+            |_, _, _: Empty| -> StdResult<Binary> {
+                to_json_binary(&cw20::BalanceResponse {
+                    balance: Uint128::new(1000),
+                })
+            },
+        )));
+        let cw20 = app
+            .app_mut()
+            .instantiate_contract(cw20_code_id, owner.clone(), &Empty {}, &[], "cw20", None)
+            .unwrap();
+
+        let start = app.block_info().time;
+        let schedule = vec![EmissionSegment {
+            start,
+            end: start.plus_seconds(100),
+            emission: Emission::Amount(Uint128::new(1000)),
+        }];
+
+        // Reward accounts use pull accounting unless push accounting is requested
+        for accounting in [None, Some(Accounting::Push)] {
+            nft_vault
+                .create_reward_account(
+                    "rewards".to_string(),
+                    RewardAsset::Native("ujuno".to_string()),
+                    schedule.clone(),
+                    None,
+                    UndistributedMode::Withdraw,
+                    0,
+                    None,
+                    accounting,
+                )
+                .with_funds(&coins(1000, "ujuno"))
+                .call(&owner)
+                .unwrap();
+        }
+
+        nft_vault
+            .add_reward_token(cw20.to_string())
+            .call(&owner)
+            .unwrap();
+        nft_vault
+            .receive(
+                owner.to_string(),
+                Uint128::new(1000),
+                to_json_binary(&ReceiveMsg::CreateRewardAccount {
+                    label: "rewards".to_string(),
+                    schedule,
+                    curve: None,
+                    undistributed_mode: UndistributedMode::Withdraw,
+                    grace_period_sec: 0,
+                    vesting: None,
+                    accounting: Some(Accounting::Push),
+                })
+                .unwrap(),
+            )
+            .call(&cw20)
+            .unwrap();
+
+        let reward_accounts: Vec<
+            Proxy<'_, CwApp, stake_rewards::contract::StakeExternalRewardsContract>,
+        > = nft_vault
+            .reward_accounts()
+            .unwrap()
+            .into_iter()
+            .map(|reward_account| Proxy::new(reward_account, &app))
+            .collect();
+        assert_eq!(
+            reward_accounts
+                .iter()
+                .map(|reward_account| reward_account.config().unwrap().accounting)
+                .collect::<Vec<_>>(),
+            vec![Accounting::Pull, Accounting::Push, Accounting::Push]
+        );
+
+        nft_vault
+            .stake(vec![Nft {
+                collection: collection1.to_string(),
+                token_id: "1".to_string(),
+            }])
+            .call(&user1)
+            .unwrap();
+        app.update_block(|block| block.time = block.time.plus_seconds(50));
+
+        // Stakers claim directly only from pull accounting reward accounts
+        reward_accounts[0]
+            .claim_rewards(user1.to_string(), None, None)
+            .call(&user1)
+            .unwrap();
+        assert!(reward_accounts[1]
+            .claim_rewards(user1.to_string(), None, None)
+            .call(&user1)
+            .is_err());
+
+        let balance = app.app().wrap().query_balance(&user1, "ujuno").unwrap();
+        assert_eq!(balance.amount, Uint128::new(500));
+
+        // The vault claims from every reward account
+        nft_vault.claim_rewards(None).call(&user1).unwrap();
+        let balance = app.app().wrap().query_balance(&user1, "ujuno").unwrap();
+        assert_eq!(balance.amount, Uint128::new(1000));
+    }
+
+    #[test]
+    fn test_force_unstake() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+        let user1 = "user1".into_addr();
+        let user2 = "user2".into_addr();
+        let treasury = "treasury".into_addr();
+
+        app.app_mut().init_modules(|router, _, storage| {
+            router
+                .bank
+                .init_balance(storage, &owner, coins(1000, "ujuno"))
+                .unwrap();
+        });
+
+        let collection1 = mock_collection(&app, &owner);
+        let nft_vault = instantiate_vault(&app, &owner, &collection1, 0);
+
+        let start = app.block_info().time;
+        nft_vault
+            .create_reward_account(
+                "rewards".to_string(),
+                RewardAsset::Native("ujuno".to_string()),
+                vec![EmissionSegment {
+                    start,
+                    end: start.plus_seconds(100),
+                    emission: Emission::Amount(Uint128::new(1000)),
+                }],
+                None,
+                UndistributedMode::Withdraw,
+                0,
+                None,
+                None,
+            )
+            .with_funds(&coins(1000, "ujuno"))
+            .call(&owner)
+            .unwrap();
+        let reward_account = nft_vault.reward_accounts().unwrap()[0].clone();
+        let reward_account: Proxy<
+            '_,
+            CwApp,
+            stake_rewards::contract::StakeExternalRewardsContract,
+        > = Proxy::new(reward_account, &app);
+
+        let nft = |token_id: &str| Nft {
+            collection: collection1.to_string(),
+            token_id: token_id.to_string(),
+        };
+
+        nft_vault
+            .stake(vec![nft("1"), nft("2")])
+            .call(&user1)
+            .unwrap();
+        nft_vault.stake(vec![nft("3")]).call(&user2).unwrap();
+
+        app.update_block(|block| block.time = block.time.plus_seconds(30));
+
+        // Only moderators can force unstake
+        let err = nft_vault
+            .force_unstake(vec![nft("1")], None, "spam".to_string())
+            .call(&user2)
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::CommonError(CommonError::Unauthorized(_))
+        ));
+
+        // A reason must be given
+        assert!(nft_vault
+            .force_unstake(vec![nft("1")], None, " ".to_string())
+            .call(&owner)
+            .is_err());
+
+        // Without a recipient the NFTs are held in escrow, settling each staker once
+        let res = nft_vault
+            .force_unstake(vec![nft("1"), nft("3")], None, "spam".to_string())
+            .call(&owner)
+            .unwrap();
+
+        for staker in [&user1, &user2] {
+            assert!(res.events.iter().any(|event| {
+                event.ty == "wasm-force-unstake"
+                    && event
+                        .attributes
+                        .iter()
+                        .any(|attr| attr.key == "staker" && attr.value == staker.as_str())
+                    && event
+                        .attributes
+                        .iter()
+                        .any(|attr| attr.key == "recipient" && attr.value == "escrow")
+                    && event
+                        .attributes
+                        .iter()
+                        .any(|attr| attr.key == "reason" && attr.value == "spam")
+            }));
+        }
+
+        let escrowed_nfts = nft_vault.escrowed_nfts(Default::default()).unwrap();
+        assert_eq!(escrowed_nfts.len(), 2);
+        assert!(escrowed_nfts
+            .iter()
+            .all(|escrowed_nft| escrowed_nft.reason == "spam"));
+        assert_eq!(escrowed_nfts[0].staker, user1);
+        assert_eq!(escrowed_nfts[1].staker, user2);
+
+        // Both stakers were settled with the amounts staked before the removal
+        let user_reward = |staker: &Addr| {
+            reward_account
+                .user_reward(staker.to_string(), RewardAsset::Native("ujuno".to_string()))
+                .unwrap()
+                .unwrap()
+        };
+        assert_eq!(user_reward(&user1).pending_rewards, Uint128::new(200));
+        assert_eq!(user_reward(&user2).pending_rewards, Uint128::new(100));
+
+        let reward_weight = nft_vault.reward_weight(user2.to_string()).unwrap();
+        assert_eq!(reward_weight.reward_weight, Uint128::zero());
+        assert_eq!(
+            reward_weight.total_reward_weight,
+            nft_vault
+                .reward_weight(user1.to_string())
+                .unwrap()
+                .reward_weight
+        );
+
+        // With a recipient the NFTs are transferred directly
+        let res = nft_vault
+            .force_unstake(
+                vec![nft("2")],
+                Some(treasury.to_string()),
+                "spam".to_string(),
+            )
+            .call(&owner)
+            .unwrap();
+        assert!(res.events.iter().any(|event| {
+            event.ty == "wasm-force-unstake"
+                && event
+                    .attributes
+                    .iter()
+                    .any(|attr| attr.key == "recipient" && attr.value == treasury.as_str())
+        }));
+        assert_eq!(
+            nft_vault.escrowed_nfts(Default::default()).unwrap().len(),
+            2
+        );
+        assert_eq!(
+            nft_vault
+                .reward_weight(user1.to_string())
+                .unwrap()
+                .total_reward_weight,
+            Uint128::zero()
+        );
+
+        // Only moderators release escrowed NFTs, and only those in escrow
+        assert!(nft_vault
+            .release_escrowed_nfts(vec![nft("1")], user1.to_string())
+            .call(&user1)
+            .is_err());
+        assert!(nft_vault
+            .release_escrowed_nfts(vec![nft("2")], user1.to_string())
+            .call(&owner)
+            .is_err());
+
+        nft_vault
+            .grant_role(user2.to_string(), Role::Moderator)
+            .call(&owner)
+            .unwrap();
+        nft_vault
+            .release_escrowed_nfts(vec![nft("1")], user1.to_string())
+            .call(&user2)
+            .unwrap();
+
+        let escrowed_nfts = nft_vault.escrowed_nfts(Default::default()).unwrap();
+        assert_eq!(escrowed_nfts.len(), 1);
+        assert_eq!(escrowed_nfts[0].staker, user2);
+    }
+
+    #[test]
+    fn test_force_unstake_receipts_and_pending() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+        let user1 = "user1".into_addr();
+
+        let collection1 = mock_collection(&app, &owner);
+        let nft_vault = instantiate_vault(&app, &owner, &collection1, 100);
+
+        nft_vault
+            .update_config(
+                None,
+                None,
+                Some(StakeReceiptCodeId::store_code(&app).code_id()),
+                None,
+                None,
+                None,
+                None,
+            )
+            .call(&owner)
+            .unwrap();
+        nft_vault
+            .create_receipt_collection(
+                "receipts".to_string(),
+                "Receipts".to_string(),
+                "RCPT".to_string(),
+            )
+            .call(&owner)
+            .unwrap();
+        let receipt_collection: Proxy<'_, CwApp, stake_receipt::contract::StakeReceiptContract> =
+            Proxy::new(nft_vault.receipt_collection().unwrap().unwrap(), &app);
+
+        let nft = |token_id: &str| Nft {
+            collection: collection1.to_string(),
+            token_id: token_id.to_string(),
+        };
+
+        nft_vault.stake(vec![nft("1")]).call(&user1).unwrap();
+        app.update_block(|block| block.time = block.time.plus_seconds(100));
+        nft_vault.stake(vec![nft("2")]).call(&user1).unwrap();
+        nft_vault
+            .list_nft(nft("1"), coin(100, "ujuno"), None)
+            .call(&user1)
+            .unwrap();
+        assert_eq!(receipt_collection.num_tokens().unwrap().count, 2);
+
+        // At least one NFT must be given
+        assert!(nft_vault
+            .force_unstake(vec![], None, "spam".to_string())
+            .call(&owner)
+            .is_err());
+
+        // Warmed up and pending NFTs are both removed, burning their receipts and listings
+        nft_vault
+            .force_unstake(vec![nft("1"), nft("2")], None, "spam".to_string())
+            .call(&owner)
+            .unwrap();
+
+        assert_eq!(receipt_collection.num_tokens().unwrap().count, 0);
+        assert!(nft_vault.listings(Default::default()).unwrap().is_empty());
+        assert!(nft_vault
+            .users_staked_nfts(user1.to_string(), Default::default())
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            nft_vault.escrowed_nfts(Default::default()).unwrap().len(),
+            2
+        );
+
+        let reward_weight = nft_vault.reward_weight(user1.to_string()).unwrap();
+        assert_eq!(reward_weight.reward_weight, Uint128::zero());
+        assert_eq!(reward_weight.total_reward_weight, Uint128::zero());
+
+        // The pending NFT does not activate once its warm-up would have completed
+        app.update_block(|block| block.time = block.time.plus_seconds(100));
+        assert!(nft_vault.activate(None).call(&user1).is_err());
+
+        // Escrowed NFTs are no longer staked
+        assert!(nft_vault
+            .force_unstake(vec![nft("1")], None, "spam".to_string())
+            .call(&owner)
+            .is_err());
+        assert!(nft_vault.unstake(vec![nft("2")]).call(&user1).is_err());
+
+        let res = nft_vault
+            .release_escrowed_nfts(vec![nft("1"), nft("2")], user1.to_string())
+            .call(&owner)
+            .unwrap();
+        assert!(res.events.iter().any(|event| {
+            event.ty == "wasm-release-escrow"
+                && event
+                    .attributes
+                    .iter()
+                    .any(|attr| attr.key == "recipient" && attr.value == user1.as_str())
+        }));
+        assert!(nft_vault
+            .escrowed_nfts(Default::default())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_receipts() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+        let user1 = "user1".into_addr();
+        let user2 = "user2".into_addr();
+
+        let collection1 = mock_collection(&app, &owner);
+        let nft_vault = instantiate_vault(&app, &owner, &collection1, 0);
+
+        nft_vault
+            .update_config(
+                None,
+                None,
+                Some(StakeReceiptCodeId::store_code(&app).code_id()),
+                None,
+                None,
+                None,
+                None,
+            )
+            .call(&owner)
+            .unwrap();
+        nft_vault
+            .create_receipt_collection(
+                "receipts".to_string(),
+                "Receipts".to_string(),
+                "RCPT".to_string(),
+            )
+            .call(&owner)
+            .unwrap();
+        let receipt_collection: Proxy<'_, CwApp, stake_receipt::contract::StakeReceiptContract> =
+            Proxy::new(nft_vault.receipt_collection().unwrap().unwrap(), &app);
+
+        let nft = |token_id: &str| Nft {
+            collection: collection1.to_string(),
+            token_id: token_id.to_string(),
+        };
+        let receipt_id = |token_id: &str| format!("{}-{}", collection1, token_id);
+
+        // Staking mints a receipt per NFT to the staker
+        nft_vault
+            .stake(vec![nft("1"), nft("2")])
+            .call(&user1)
+            .unwrap();
+        assert_eq!(
+            receipt_collection
+                .tokens(user1.to_string(), None, None)
+                .unwrap()
+                .tokens,
+            vec![receipt_id("1"), receipt_id("2")]
+        );
+
+        // Only the receipt collection reports receipt transfers
+        assert!(nft_vault
+            .receipt_transfer(nft("1"), user1.to_string(), user2.to_string())
+            .call(&user1)
+            .is_err());
+        assert!(receipt_collection
+            .transfer_nft(user2.to_string(), receipt_id("1"))
+            .call(&user2)
+            .is_err());
+
+        nft_vault
+            .list_nft(nft("1"), coin(100, "ujuno"), None)
+            .call(&user1)
+            .unwrap();
+
+        // Transferring the receipt moves the staked position and drops its listing
+        receipt_collection
+            .transfer_nft(user2.to_string(), receipt_id("1"))
+            .call(&user1)
+            .unwrap();
+
+        let staked_nfts = nft_vault
+            .users_staked_nfts(user2.to_string(), Default::default())
+            .unwrap();
+        assert_eq!(staked_nfts.len(), 1);
+        assert_eq!(staked_nfts[0].staker, user2);
+        assert_eq!(staked_nfts[0].nft.token_id, "1");
+        assert_eq!(
+            nft_vault
+                .users_staked_nfts(user1.to_string(), Default::default())
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(nft_vault.listings(Default::default()).unwrap().is_empty());
+
+        let reward_weight = nft_vault.reward_weight(user2.to_string()).unwrap();
+        assert_eq!(
+            reward_weight.reward_weight,
+            nft_vault
+                .reward_weight(user1.to_string())
+                .unwrap()
+                .reward_weight
+        );
+        assert_eq!(
+            reward_weight.total_reward_weight,
+            reward_weight.reward_weight * Uint128::new(2)
+        );
+
+        // The previous staker can no longer unstake the NFT
+        assert!(nft_vault.unstake(vec![nft("1")]).call(&user1).is_err());
+
+        // Unstaking burns the receipt
+        nft_vault.unstake(vec![nft("1")]).call(&user2).unwrap();
+        assert!(receipt_collection.owner_of(receipt_id("1"), None).is_err());
+        assert_eq!(receipt_collection.num_tokens().unwrap().count, 1);
+        assert_eq!(nft_vault.claims(user2.to_string()).unwrap().len(), 1);
+
+        // Burning the receipt directly unstakes the NFT
+        receipt_collection
+            .burn(receipt_id("2"))
+            .call(&user1)
+            .unwrap();
+        assert!(nft_vault
+            .users_staked_nfts(user1.to_string(), Default::default())
+            .unwrap()
+            .is_empty());
+        assert_eq!(nft_vault.claims(user1.to_string()).unwrap().len(), 1);
+        assert_eq!(receipt_collection.num_tokens().unwrap().count, 0);
+        assert_eq!(
+            nft_vault
+                .reward_weight(user1.to_string())
+                .unwrap()
+                .total_reward_weight,
+            Uint128::zero()
+        );
+    }
+
+    #[test]
+    fn test_receipt_collection_lifecycle() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+        let user1 = "user1".into_addr();
+        let user2 = "user2".into_addr();
+
+        let collection1 = mock_collection(&app, &owner);
+        let nft_vault = instantiate_vault(&app, &owner, &collection1, 100);
+
+        let nft = |token_id: &str| Nft {
+            collection: collection1.to_string(),
+            token_id: token_id.to_string(),
+        };
+        let receipt_id = |token_id: &str| format!("{}-{}", collection1, token_id);
+
+        let create_receipt_collection = |sender: &Addr| {
+            nft_vault
+                .create_receipt_collection(
+                    "receipts".to_string(),
+                    "Receipts".to_string(),
+                    "RCPT".to_string(),
+                )
+                .call(sender)
+        };
+
+        // The receipt code id must be configured first
+        assert!(create_receipt_collection(&owner).is_err());
 
-        let results = self
-            .users_staked_nfts
-            .idx
-            .staker_collection
-            .sub_prefix(staker.clone())
-            .range(ctx.deps.storage, min, max, order)
-            .take(limit)
-            .map(|res| res.map(|(_, pq)| pq))
-            .collect::<StdResult<Vec<StakedNft>>>()?;
+        nft_vault
+            .update_config(
+                None,
+                None,
+                Some(StakeReceiptCodeId::store_code(&app).code_id()),
+                None,
+                None,
+                None,
+                None,
+            )
+            .call(&owner)
+            .unwrap();
 
-        Ok(results)
-    }
+        // NFTs staked before the receipt collection exists have no receipt
+        nft_vault.stake(vec![nft("1")]).call(&user1).unwrap();
 
-    #[sv::msg(query)]
-    pub fn users_collection_staked_amounts(
-        &self,
-        ctx: QueryCtx,
-        staker: String,
-        query_options: QueryOptions<String>,
-    ) -> StdResult<Vec<(Addr, u64)>> {
-        let staker = ctx.deps.api.addr_validate(&staker)?;
+        assert!(matches!(
+            create_receipt_collection(&user1).unwrap_err(),
+            ContractError::CommonError(CommonError::Unauthorized(_))
+        ));
+        create_receipt_collection(&owner).unwrap();
+        assert!(create_receipt_collection(&owner).is_err());
 
-        let QueryOptionsInternal {
-            limit,
-            order,
-            min,
-            max,
-        } = query_options.unpack(
-            &|offset| ctx.deps.api.addr_validate(&offset).unwrap(),
-            None,
-            None,
+        let receipt_collection: Proxy<'_, CwApp, stake_receipt::contract::StakeReceiptContract> =
+            Proxy::new(nft_vault.receipt_collection().unwrap().unwrap(), &app);
+
+        nft_vault.stake(vec![nft("2")]).call(&user1).unwrap();
+        assert_eq!(
+            receipt_collection
+                .tokens(user1.to_string(), None, None)
+                .unwrap()
+                .tokens,
+            vec![receipt_id("2")]
         );
 
-        let results = self
-            .users_collection_staked_amounts
-            .prefix(staker.clone())
-            .range(ctx.deps.storage, min, max, order)
-            .take(limit)
-            .map(|res| res.map(|(collection, amount)| (collection, amount)))
-            .collect::<StdResult<Vec<_>>>()?;
+        // Transferring the receipt of a pending NFT keeps its warm-up progress
+        app.update_block(|block| block.time = block.time.plus_seconds(50));
+        receipt_collection
+            .transfer_nft(user2.to_string(), receipt_id("2"))
+            .call(&user1)
+            .unwrap();
+        assert!(nft_vault.activate(None).call(&user2).is_err());
 
-        Ok(results)
-    }
+        app.update_block(|block| block.time = block.time.plus_seconds(50));
+        nft_vault.activate(None).call(&user2).unwrap();
+        assert_eq!(
+            nft_vault
+                .reward_weight(user2.to_string())
+                .unwrap()
+                .reward_weight,
+            Uint128::new(10_000)
+        );
 
-    #[sv::msg(query)]
-    pub fn total_staked_amount_at_height(
-        &self,
-        ctx: QueryCtx,
-        height: Option<u64>,
-    ) -> StdResult<Option<Uint128>> {
-        let height = height.unwrap_or(ctx.env.block.height);
+        // The NFT without a receipt unstakes without burning one
+        nft_vault.unstake(vec![nft("1")]).call(&user1).unwrap();
+        assert_eq!(receipt_collection.num_tokens().unwrap().count, 1);
+        assert_eq!(nft_vault.claims(user1.to_string()).unwrap().len(), 1);
+    }
 
-        let results = self
-            .total_staked_amount
-            .may_load_at_height(ctx.deps.storage, height)?;
+    #[test]
+    fn test_derivative_token() {
+        let app: App<CwApp> = App::default();
 
-        Ok(results)
-    }
+        let owner = "owner".into_addr();
+        let user1 = "user1".into_addr();
+        let user2 = "user2".into_addr();
 
-    #[sv::msg(query)]
-    pub fn claims(&self, ctx: QueryCtx, staker: String) -> StdResult<Vec<Claim>> {
-        let staker = ctx.deps.api.addr_validate(&staker)?;
+        let collection1 = mock_collection(&app, &owner);
+        let boost_collection = mock_collection(&app, &owner);
+        let nft_vault = instantiate_vault(&app, &owner, &collection1, 0);
 
-        let results = self.claims.query_claims(ctx.deps, &staker)?;
+        nft_vault
+            .update_config(
+                None,
+                None,
+                None,
+                Some(StakeDerivativeCodeId::store_code(&app).code_id()),
+                None,
+                None,
+                None,
+            )
+            .call(&owner)
+            .unwrap();
+        nft_vault
+            .update_boosts(
+                vec![BoostCollection {
+                    collection: boost_collection.to_string(),
+                    bonus_bps: 5_000,
+                }],
+                1,
+            )
+            .call(&owner)
+            .unwrap();
 
-        Ok(results.claims)
-    }
+        let boost = Nft {
+            collection: boost_collection.to_string(),
+            token_id: "1".to_string(),
+        };
+        let create_derivative_token = || {
+            nft_vault
+                .create_derivative_token(
+                    "derivative".to_string(),
+                    "Staked NFTs".to_string(),
+                    "SNFT".to_string(),
+                    6,
+                )
+                .call(&owner)
+        };
 
-    pub fn update_stake_amounts(
-        &self,
-        storage: &mut dyn Storage,
-        env: &Env,
-        config: Config<Addr>,
-        sender: &Addr,
-        collection_deltas: HashMap<Addr, i64>,
-    ) -> Result<UpdateStakeResult, ContractError> {
-        let mut user_staked_amount_before = u64::MAX;
-        let mut user_staked_amount_after = u64::MAX;
+        // Locked boosts must be unlocked first
+        nft_vault.lock_boost(boost.clone()).call(&user1).unwrap();
+        assert!(create_derivative_token().is_err());
+        nft_vault.unlock_boost(boost.clone()).call(&user1).unwrap();
+        create_derivative_token().unwrap();
 
-        for collection in &config.collections {
-            let user_collection_staked_amount_before = self
-                .users_collection_staked_amounts
-                .may_load(storage, (sender.clone(), collection.clone()))?
-                .unwrap_or_default();
+        // Boosts no longer apply once rewards follow the derivative token
+        assert!(nft_vault.lock_boost(boost.clone()).call(&user1).is_err());
+        assert!(nft_vault.unlock_boost(boost).call(&user1).is_err());
 
-            let user_collection_staked_amount_after = user_collection_staked_amount_before
-                .checked_add_signed(*collection_deltas.get(collection).unwrap_or(&0i64))
-                .unwrap();
+        let derivative_token: Proxy<
+            '_,
+            CwApp,
+            stake_derivative::contract::StakeDerivativeContract,
+        > = Proxy::new(nft_vault.derivative_token().unwrap().unwrap(), &app);
 
-            if user_collection_staked_amount_before != user_collection_staked_amount_after {
-                self.users_collection_staked_amounts.save(
-                    storage,
-                    (sender.clone(), collection.clone()),
-                    &user_collection_staked_amount_after,
-                )?;
-            }
+        let nft = |token_id: &str| Nft {
+            collection: collection1.to_string(),
+            token_id: token_id.to_string(),
+        };
+        let balance = |holder: &Addr| {
+            derivative_token
+                .balance(holder.to_string())
+                .unwrap()
+                .balance
+        };
+        let rewards_staked_amount =
+            |holder: &Addr| nft_vault.rewards_staked_amount(holder.to_string()).unwrap();
 
-            user_staked_amount_before = min(
-                user_staked_amount_before,
-                user_collection_staked_amount_before,
-            );
+        // Staking power is minted to the staker
+        nft_vault
+            .stake(vec![nft("1"), nft("2")])
+            .call(&user1)
+            .unwrap();
+        let staked_amount = balance(&user1);
+        assert!(!staked_amount.is_zero());
+        assert_eq!(rewards_staked_amount(&user1), staked_amount);
 
-            user_staked_amount_after = min(
-                user_staked_amount_after,
-                user_collection_staked_amount_after,
-            );
-        }
-        ensure!(
-            user_staked_amount_before != u64::MAX,
-            CommonError::InternalError("user staked amount before is u64::MAX".to_string())
-        );
-        ensure!(
-            user_staked_amount_after != u64::MAX,
-            CommonError::InternalError("user staked amount after is u64::MAX".to_string())
-        );
+        // Transfers move the rewards along with the tokens
+        let half = staked_amount.checked_div(Uint128::new(2)).unwrap();
+        derivative_token
+            .transfer(user2.to_string(), half)
+            .call(&user1)
+            .unwrap();
+        assert_eq!(balance(&user2), half);
+        assert_eq!(rewards_staked_amount(&user1), staked_amount - half);
+        assert_eq!(rewards_staked_amount(&user2), half);
 
-        let total_staked_amount_before = self.total_staked_amount.load(storage)?;
-        let total_staked_amount_after = total_staked_amount_before
-            .checked_sub(Uint128::from(user_staked_amount_before))?
-            .checked_add(Uint128::from(user_staked_amount_after))?;
+        // Unstaking redeems the staking power, which the staker must still hold
+        nft_vault.unstake(vec![nft("1")]).call(&user1).unwrap();
+        assert!(balance(&user1).is_zero());
+        assert!(rewards_staked_amount(&user1).is_zero());
 
-        if total_staked_amount_before != total_staked_amount_after {
-            self.total_staked_amount
-                .save(storage, &total_staked_amount_after, env.block.height)?;
-        }
+        let err = nft_vault.unstake(vec![nft("2")]).call(&user1).unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::CommonError(CommonError::InsufficientFunds(_))
+        ));
 
-        Ok(UpdateStakeResult {
-            user_staked_amount: Uint128::from(user_staked_amount_before),
-            total_staked_amount: total_staked_amount_before,
-        })
-    }
-}
+        // A forced unstake redeems what is left and records the rest as debt
+        nft_vault
+            .force_unstake(
+                vec![nft("2")],
+                Some(user1.to_string()),
+                "stolen".to_string(),
+            )
+            .call(&owner)
+            .unwrap();
+        assert_eq!(nft_vault.derivative_debt(user1.to_string()).unwrap(), half);
+        assert_eq!(
+            derivative_token.token_info().unwrap().total_supply,
+            nft_vault.rewards_total_staked().unwrap()
+        );
+        assert_eq!(nft_vault.rewards_total_staked().unwrap(), half);
 
-#[cfg(test)]
-mod tests {
-    use crate::{contract, state::Config};
+        // The staker's next stake backs the tokens in circulation before minting more
+        nft_vault.stake(vec![nft("3")]).call(&user1).unwrap();
+        assert!(balance(&user1).is_zero());
+        assert!(nft_vault
+            .derivative_debt(user1.to_string())
+            .unwrap()
+            .is_zero());
+        assert_eq!(derivative_token.token_info().unwrap().total_supply, half);
 
-    use cosmwasm_std::{testing::mock_env, Addr, Uint128};
-    use std::collections::HashMap;
-    use sylvia::{
-        cw_multi_test::{App as CwApp, IntoAddr},
-        multitest::App,
-    };
+        nft_vault.stake(vec![nft("4")]).call(&user1).unwrap();
+        assert_eq!(balance(&user1), half);
+        assert_eq!(
+            derivative_token.token_info().unwrap().total_supply,
+            staked_amount
+        );
+        assert_eq!(nft_vault.rewards_total_staked().unwrap(), staked_amount);
+    }
 
     #[test]
-    fn test_update_stake_amounts() {
+    fn test_emergency_unstake() {
         let app: App<CwApp> = App::default();
-        let mut app_mut = app.app_mut();
 
+        let owner = "owner".into_addr();
         let user1 = "user1".into_addr();
+        let user2 = "user2".into_addr();
 
-        let collection1 = "collection1".into_addr();
-        let collection2 = "collection2".into_addr();
+        let collection1 = mock_collection(&app, &owner);
+        let nft = |token_id: &str| Nft {
+            collection: collection1.to_string(),
+            token_id: token_id.to_string(),
+        };
 
-        let nft_vault = contract::NftVaultContract::new();
+        // Reward weights are still settled
+        let nft_vault = instantiate_vault(&app, &owner, &collection1, 0);
+        nft_vault
+            .stake(vec![nft("1"), nft("2")])
+            .call(&user1)
+            .unwrap();
+        nft_vault.stake(vec![nft("3")]).call(&user2).unwrap();
+        let user2_weight = nft_vault
+            .reward_weight(user2.to_string())
+            .unwrap()
+            .reward_weight;
 
+        nft_vault.set_emergency_mode(true).unwrap();
         nft_vault
-            .users_collection_staked_amounts
-            .save(
-                app_mut.storage_mut(),
-                (user1.clone(), collection1.clone()),
-                &4,
+            .unstake(vec![nft("1"), nft("2")])
+            .call(&user1)
+            .unwrap();
+
+        assert!(nft_vault.claims(user1.to_string()).unwrap().is_empty());
+        let reward_weight = nft_vault.reward_weight(user1.to_string()).unwrap();
+        assert!(reward_weight.reward_weight.is_zero());
+        assert_eq!(reward_weight.total_reward_weight, user2_weight);
+        assert_eq!(nft_vault.rewards_total_staked().unwrap(), user2_weight);
+
+        // Derivative tokens are redeemed, whatever the staker no longer holds becomes debt
+        let nft_vault = instantiate_vault(&app, &owner, &collection1, 0);
+        nft_vault
+            .update_config(
+                None,
+                None,
+                None,
+                Some(StakeDerivativeCodeId::store_code(&app).code_id()),
+                None,
+                None,
+                None,
             )
+            .call(&owner)
             .unwrap();
         nft_vault
-            .users_collection_staked_amounts
-            .save(
-                app_mut.storage_mut(),
-                (user1.clone(), collection2.clone()),
-                &7,
+            .create_derivative_token(
+                "derivative".to_string(),
+                "Staked NFTs".to_string(),
+                "SNFT".to_string(),
+                6,
             )
+            .call(&owner)
             .unwrap();
+        let derivative_token: Proxy<
+            '_,
+            CwApp,
+            stake_derivative::contract::StakeDerivativeContract,
+        > = Proxy::new(nft_vault.derivative_token().unwrap().unwrap(), &app);
+
         nft_vault
-            .total_staked_amount
-            .save(app_mut.storage_mut(), &Uint128::new(4), 0)
+            .stake(vec![nft("4"), nft("5")])
+            .call(&user1)
+            .unwrap();
+        let staked_amount = nft_vault.rewards_staked_amount(user1.to_string()).unwrap();
+        let half = staked_amount.checked_div(Uint128::new(2)).unwrap();
+        derivative_token
+            .transfer(user2.to_string(), half)
+            .call(&user1)
             .unwrap();
 
-        let mut env = mock_env();
-        env.block.height = 1;
+        nft_vault.set_emergency_mode(true).unwrap();
+        nft_vault
+            .unstake(vec![nft("4"), nft("5")])
+            .call(&user1)
+            .unwrap();
 
-        let config = Config::<Addr> {
-            rewards_code_id: 0,
-            unstaking_duration_sec: 60,
-            collections: vec![collection1.clone(), collection2.clone()],
+        assert!(derivative_token
+            .balance(user1.to_string())
+            .unwrap()
+            .balance
+            .is_zero());
+        assert_eq!(derivative_token.token_info().unwrap().total_supply, half);
+        assert_eq!(nft_vault.rewards_total_staked().unwrap(), half);
+        assert_eq!(nft_vault.derivative_debt(user1.to_string()).unwrap(), half);
+    }
+
+    #[test]
+    fn test_emergency_unstake_rewards() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+        let user1 = "user1".into_addr();
+        let user2 = "user2".into_addr();
+
+        app.app_mut().init_modules(|router, _, storage| {
+            router
+                .bank
+                .init_balance(storage, &owner, coins(1000, "ujuno"))
+                .unwrap();
+        });
+
+        let collection1 = mock_collection(&app, &owner);
+        let nft_vault = instantiate_vault(&app, &owner, &collection1, 0);
+
+        let start = app.block_info().time;
+        nft_vault
+            .create_reward_account(
+                "rewards".to_string(),
+                RewardAsset::Native("ujuno".to_string()),
+                vec![EmissionSegment {
+                    start,
+                    end: start.plus_seconds(100),
+                    emission: Emission::Amount(Uint128::new(1000)),
+                }],
+                None,
+                UndistributedMode::Withdraw,
+                0,
+                None,
+                None,
+            )
+            .with_funds(&coins(1000, "ujuno"))
+            .call(&owner)
+            .unwrap();
+        let reward_account: Proxy<
+            '_,
+            CwApp,
+            stake_rewards::contract::StakeExternalRewardsContract,
+        > = Proxy::new(nft_vault.reward_accounts().unwrap()[0].clone(), &app);
+
+        let nft = |token_id: &str| Nft {
+            collection: collection1.to_string(),
+            token_id: token_id.to_string(),
         };
 
-        let mut collection_deltas: HashMap<Addr, i64> = HashMap::new();
-        collection_deltas.insert(collection1.clone(), 6);
-        collection_deltas.insert(collection2.clone(), 8);
+        nft_vault.stake(vec![nft("1")]).call(&user1).unwrap();
+        nft_vault.stake(vec![nft("2")]).call(&user2).unwrap();
 
-        let _result = nft_vault.update_stake_amounts(
-            app_mut.storage_mut(),
-            &env,
-            config,
-            &user1,
-            collection_deltas,
+        app.update_block(|block| block.time = block.time.plus_seconds(50));
+
+        // The reward accounts are checkpointed, so rewards earned before the exit are kept
+        nft_vault.set_emergency_mode(true).unwrap();
+        let res = nft_vault.unstake(vec![nft("1")]).call(&user1).unwrap();
+        assert!(!res
+            .events
+            .iter()
+            .any(|event| event.ty == "wasm-reward-account-error"));
+
+        assert_eq!(
+            reward_account
+                .user_reward(user1.to_string(), RewardAsset::Native("ujuno".to_string()))
+                .unwrap()
+                .unwrap()
+                .pending_rewards,
+            Uint128::new(250)
         );
 
-        let user_collection_staked_amount_1 = nft_vault
-            .users_collection_staked_amounts
-            .load(app_mut.storage_mut(), (user1.clone(), collection1.clone()))
+        // The remaining staker earns the rest of the period alone
+        app.update_block(|block| block.time = block.time.plus_seconds(50));
+        let latest_pending_rewards = |staker: &Addr| {
+            reward_account
+                .latest_user_reward(staker.to_string())
+                .unwrap()[0]
+                .user_reward
+                .pending_rewards
+        };
+        assert_eq!(latest_pending_rewards(&user1), Uint128::new(250));
+        assert_eq!(latest_pending_rewards(&user2), Uint128::new(750));
+
+        // A failing reward account does not block the exit
+        let broken_code_id = app.app_mut().store_code(Box::new(
+            ContractWrapper::new(
+                |_, _, _, _: Empty| -> StdResult<Response> { Err(StdError::generic_err("broken")) },
+                |_, _, _, _: Empty| -> StdResult<Response> { Ok(Response::new()) },
+                |_, _, _: Empty| -> StdResult<Binary> { Ok(Default::default()) },
+            )
+            .with_migrate(|_, _, _: Empty| -> StdResult<Response> { Ok(Response::new()) }),
+        ));
+        app.app_mut()
+            .migrate_contract(
+                nft_vault.contract_addr.clone(),
+                reward_account.contract_addr.clone(),
+                &Empty {},
+                broken_code_id,
+            )
             .unwrap();
-        assert_eq!(user_collection_staked_amount_1, 10);
 
-        let user_collection_staked_amount_2 = nft_vault
-            .users_collection_staked_amounts
-            .load(app_mut.storage_mut(), (user1.clone(), collection2.clone()))
+        let res = nft_vault.unstake(vec![nft("2")]).call(&user2).unwrap();
+        assert!(res
+            .events
+            .iter()
+            .any(|event| event.ty == "wasm-reward-account-error"));
+        assert!(nft_vault
+            .users_staked_nfts(user2.to_string(), Default::default())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_loyalty_promotions() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+        let user1 = "user1".into_addr();
+
+        let collection1 = mock_collection(&app, &owner);
+        let nft_vault = instantiate_vault(&app, &owner, &collection1, 0);
+
+        let loyalty_tiers = |tiers: &[(u64, u64)]| {
+            Some(
+                tiers
+                    .iter()
+                    .map(|&(min_duration_sec, bonus_bps)| LoyaltyTier {
+                        min_duration_sec,
+                        bonus_bps,
+                    })
+                    .collect(),
+            )
+        };
+        nft_vault
+            .update_config(
+                None,
+                None,
+                None,
+                None,
+                None,
+                loyalty_tiers(&[(100, 1_000), (200, 5_000)]),
+                None,
+            )
+            .call(&owner)
             .unwrap();
-        assert_eq!(user_collection_staked_amount_2, 15);
 
-        let total_staked_amount = nft_vault
-            .total_staked_amount
-            .load(app_mut.storage_mut())
+        let nft = |token_id: &str| Nft {
+            collection: collection1.to_string(),
+            token_id: token_id.to_string(),
+        };
+        let latest_reward_weight = || {
+            nft_vault
+                .reward_weight(user1.to_string())
+                .unwrap()
+                .latest_reward_weight
+        };
+
+        nft_vault.stake(vec![nft("1")]).call(&user1).unwrap();
+        app.update_block(|block| block.time = block.time.plus_seconds(50));
+        nft_vault.stake(vec![nft("2")]).call(&user1).unwrap();
+
+        app.update_block(|block| block.time = block.time.plus_seconds(90));
+        assert_eq!(latest_reward_weight(), Uint128::new(21_000));
+
+        // NFTs may reach several tiers between two interactions
+        app.update_block(|block| block.time = block.time.plus_seconds(110));
+        assert_eq!(latest_reward_weight(), Uint128::new(30_000));
+
+        nft_vault.poke(None).call(&user1).unwrap();
+        let reward_weight = nft_vault.reward_weight(user1.to_string()).unwrap();
+        assert_eq!(reward_weight.reward_weight, Uint128::new(30_000));
+        assert_eq!(reward_weight.total_reward_weight, Uint128::new(30_000));
+
+        // A new stake starts its own streak
+        nft_vault.stake(vec![nft("3")]).call(&user1).unwrap();
+        assert_eq!(latest_reward_weight(), Uint128::new(40_000));
+        app.update_block(|block| block.time = block.time.plus_seconds(100));
+        assert_eq!(latest_reward_weight(), Uint128::new(41_000));
+
+        // Unstaking drops the NFT along with its upcoming promotions
+        nft_vault.unstake(vec![nft("3")]).call(&user1).unwrap();
+        assert_eq!(latest_reward_weight(), Uint128::new(30_000));
+        app.update_block(|block| block.time = block.time.plus_seconds(200));
+        assert_eq!(latest_reward_weight(), Uint128::new(30_000));
+
+        // Changing the bonuses applies right away, changing the durations recounts the streaks
+        nft_vault
+            .update_config(
+                None,
+                None,
+                None,
+                None,
+                None,
+                loyalty_tiers(&[(100, 1_000), (200, 3_000)]),
+                None,
+            )
+            .call(&owner)
             .unwrap();
-        assert_eq!(total_staked_amount, Uint128::new(10));
+        assert_eq!(latest_reward_weight(), Uint128::new(26_000));
+
+        nft_vault
+            .update_config(
+                None,
+                None,
+                None,
+                None,
+                None,
+                loyalty_tiers(&[(1_000, 2_000)]),
+                None,
+            )
+            .call(&owner)
+            .unwrap();
+        assert_eq!(latest_reward_weight(), Uint128::new(20_000));
+        nft_vault.poke(None).call(&user1).unwrap();
+        assert_eq!(
+            nft_vault
+                .reward_weight(user1.to_string())
+                .unwrap()
+                .reward_weight,
+            Uint128::new(20_000)
+        );
+
+        app.update_block(|block| block.time = block.time.plus_seconds(450));
+        assert_eq!(latest_reward_weight(), Uint128::new(22_000));
+        app.update_block(|block| block.time = block.time.plus_seconds(50));
+        assert_eq!(latest_reward_weight(), Uint128::new(24_000));
     }
 }
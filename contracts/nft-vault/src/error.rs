@@ -27,4 +27,16 @@ pub enum ContractError {
 
     #[error("ClaimableNftsNotFound")]
     ClaimableNftsNotFound,
+
+    #[error("Paused")]
+    Paused,
+
+    #[error("EmergencyMode")]
+    EmergencyMode,
+
+    #[error("AirdropAlreadyClaimed")]
+    AirdropAlreadyClaimed,
+
+    #[error("AirdropExpired")]
+    AirdropExpired,
 }
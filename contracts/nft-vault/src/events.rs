@@ -1,7 +1,7 @@
-use cosmwasm_std::{attr, Addr, Event};
+use cosmwasm_std::{attr, Addr, Event, Uint128};
 use std::vec;
 
-use crate::state::Config;
+use crate::state::{Airdrop, Config, Fee, Listing, Nft, NftUser, Status};
 
 pub struct ConfigEvent<'a> {
     pub ty: &'a str,
@@ -25,6 +25,85 @@ impl<'a> From<ConfigEvent<'a>> for Event {
                 "unstaking_duration_sec",
                 ce.config.unstaking_duration_sec.to_string(),
             ),
+            attr(
+                "receipt_code_id",
+                ce.config
+                    .receipt_code_id
+                    .map_or("none".to_string(), |code_id| code_id.to_string()),
+            ),
+            attr(
+                "derivative_code_id",
+                ce.config
+                    .derivative_code_id
+                    .map_or("none".to_string(), |code_id| code_id.to_string()),
+            ),
+            attr(
+                "warmup_duration_sec",
+                ce.config.warmup_duration_sec.to_string(),
+            ),
+            attr(
+                "loyalty_tiers",
+                ce.config
+                    .loyalty_tiers
+                    .iter()
+                    .map(|tier| format!("{}:{}", tier.min_duration_sec, tier.bonus_bps))
+                    .collect::<Vec<String>>()
+                    .join(","),
+            ),
+            attr(
+                "collection_unstaking_durations",
+                ce.config
+                    .collection_unstaking_durations
+                    .iter()
+                    .map(|cud| format!("{}:{}", cud.collection, cud.unstaking_duration_sec))
+                    .collect::<Vec<String>>()
+                    .join(","),
+            ),
+            attr(
+                "stake_fee",
+                ce.config
+                    .stake_fee
+                    .as_ref()
+                    .map_or("none".to_string(), |fee| fee.to_string()),
+            ),
+            attr(
+                "unstake_fee",
+                ce.config
+                    .unstake_fee
+                    .as_ref()
+                    .map_or("none".to_string(), |fee| fee.to_string()),
+            ),
+            attr(
+                "sale_fee",
+                ce.config
+                    .sale_fee
+                    .as_ref()
+                    .map_or("none".to_string(), |fee| fee.to_string()),
+            ),
+            attr(
+                "boost_collections",
+                ce.config
+                    .boost_collections
+                    .iter()
+                    .map(|bc| format!("{}:{}", bc.collection, bc.bonus_bps))
+                    .collect::<Vec<String>>()
+                    .join(","),
+            ),
+            attr("max_boosts", ce.config.max_boosts.to_string()),
+        ])
+    }
+}
+
+pub struct StatusEvent<'a> {
+    pub ty: &'a str,
+    pub status: &'a Status,
+}
+
+impl<'a> From<StatusEvent<'a>> for Event {
+    fn from(se: StatusEvent) -> Self {
+        Event::new(se.ty.to_string()).add_attributes(vec![
+            attr("paused", se.status.paused.to_string()),
+            attr("emergency_mode", se.status.emergency_mode.to_string()),
         ])
     }
 }
@@ -40,6 +119,55 @@ impl<'a> From<RewardAccountEvent<'a>> for Event {
     }
 }
 
+pub struct RewardTokenEvent<'a> {
+    pub ty: &'a str,
+    pub token: &'a Addr,
+}
+
+impl<'a> From<RewardTokenEvent<'a>> for Event {
+    fn from(rte: RewardTokenEvent) -> Self {
+        Event::new(rte.ty.to_string()).add_attribute("token", rte.token.to_string())
+    }
+}
+
+pub struct ReceiptCollectionEvent<'a> {
+    pub ty: &'a str,
+    pub address: &'a str,
+}
+
+impl<'a> From<ReceiptCollectionEvent<'a>> for Event {
+    fn from(rce: ReceiptCollectionEvent) -> Self {
+        Event::new(rce.ty.to_string()).add_attribute("address", rce.address.to_string())
+    }
+}
+
+pub struct DerivativeTokenEvent<'a> {
+    pub ty: &'a str,
+    pub address: &'a str,
+}
+
+impl<'a> From<DerivativeTokenEvent<'a>> for Event {
+    fn from(dte: DerivativeTokenEvent) -> Self {
+        Event::new(dte.ty.to_string()).add_attribute("address", dte.address.to_string())
+    }
+}
+
+pub struct DerivativeTransferEvent<'a> {
+    pub sender: &'a Addr,
+    pub recipient: &'a Addr,
+    pub amount: Uint128,
+}
+
+impl<'a> From<DerivativeTransferEvent<'a>> for Event {
+    fn from(dte: DerivativeTransferEvent) -> Self {
+        Event::new("transfer-derivative".to_string()).add_attributes(vec![
+            attr("sender", dte.sender.to_string()),
+            attr("recipient", dte.recipient.to_string()),
+            attr("amount", dte.amount.to_string()),
+        ])
+    }
+}
+
 pub struct StakeChangeEvent<'a> {
     pub ty: &'a str,
     pub sender: &'a str,
@@ -56,3 +184,182 @@ impl<'a> From<StakeChangeEvent<'a>> for Event {
         ])
     }
 }
+
+pub struct RewardWeightEvent<'a> {
+    pub staker: &'a Addr,
+    pub reward_weight: Uint128,
+    pub total_reward_weight: Uint128,
+}
+
+impl<'a> From<RewardWeightEvent<'a>> for Event {
+    fn from(rwe: RewardWeightEvent) -> Self {
+        Event::new("update-reward-weight".to_string()).add_attributes(vec![
+            attr("staker", rwe.staker.to_string()),
+            attr("reward_weight", rwe.reward_weight.to_string()),
+            attr("total_reward_weight", rwe.total_reward_weight.to_string()),
+        ])
+    }
+}
+
+pub struct ActivateNftsEvent<'a> {
+    pub staker: &'a Addr,
+    pub nfts: &'a [Nft<Addr>],
+}
+
+impl<'a> From<ActivateNftsEvent<'a>> for Event {
+    fn from(ane: ActivateNftsEvent) -> Self {
+        Event::new("activate-nfts".to_string()).add_attributes(vec![
+            attr("staker", ane.staker.to_string()),
+            attr("nfts", join_nfts(ane.nfts)),
+        ])
+    }
+}
+
+pub struct ForceUnstakeEvent<'a> {
+    pub staker: &'a Addr,
+    pub nfts: &'a [Nft<Addr>],
+    pub recipient: Option<&'a Addr>,
+    pub reason: &'a str,
+}
+
+impl<'a> From<ForceUnstakeEvent<'a>> for Event {
+    fn from(fue: ForceUnstakeEvent) -> Self {
+        Event::new("force-unstake".to_string()).add_attributes(vec![
+            attr("staker", fue.staker.to_string()),
+            attr("nfts", join_nfts(fue.nfts)),
+            attr(
+                "recipient",
+                fue.recipient
+                    .map_or("escrow".to_string(), |addr| addr.to_string()),
+            ),
+            attr("reason", fue.reason.to_string()),
+        ])
+    }
+}
+
+pub struct ReleaseEscrowEvent<'a> {
+    pub nfts: &'a [Nft<Addr>],
+    pub recipient: &'a Addr,
+}
+
+impl<'a> From<ReleaseEscrowEvent<'a>> for Event {
+    fn from(ree: ReleaseEscrowEvent) -> Self {
+        Event::new("release-escrow".to_string()).add_attributes(vec![
+            attr("nfts", join_nfts(ree.nfts)),
+            attr("recipient", ree.recipient.to_string()),
+        ])
+    }
+}
+
+pub struct FeeEvent<'a> {
+    pub operation: &'a str,
+    pub payer: &'a Addr,
+    pub fee: &'a Fee<Addr>,
+    pub amount: Uint128,
+}
+
+impl<'a> From<FeeEvent<'a>> for Event {
+    fn from(fe: FeeEvent) -> Self {
+        Event::new("collect-fee".to_string()).add_attributes(vec![
+            attr("operation", fe.operation),
+            attr("payer", fe.payer.to_string()),
+            attr("asset", fe.fee.asset.to_string()),
+            attr("amount", fe.amount.to_string()),
+            attr("recipient", fe.fee.recipient.to_string()),
+        ])
+    }
+}
+
+pub struct BoostEvent<'a> {
+    pub ty: &'a str,
+    pub staker: &'a Addr,
+    pub nft: &'a Nft<Addr>,
+}
+
+impl<'a> From<BoostEvent<'a>> for Event {
+    fn from(be: BoostEvent) -> Self {
+        Event::new(be.ty.to_string()).add_attributes(vec![
+            attr("staker", be.staker.to_string()),
+            attr("nft", be.nft.to_string()),
+        ])
+    }
+}
+
+pub struct NftUserEvent<'a> {
+    pub staker: &'a Addr,
+    pub nft: &'a Nft<Addr>,
+    pub user: Option<&'a NftUser<Addr>>,
+}
+
+impl<'a> From<NftUserEvent<'a>> for Event {
+    fn from(nue: NftUserEvent) -> Self {
+        Event::new("set-nft-user".to_string()).add_attributes(vec![
+            attr("staker", nue.staker.to_string()),
+            attr("nft", nue.nft.to_string()),
+            attr(
+                "user",
+                nue.user.map_or("none".to_string(), |user| user.to_string()),
+            ),
+        ])
+    }
+}
+
+pub struct ListingEvent<'a> {
+    pub ty: &'a str,
+    pub listing: &'a Listing,
+}
+
+impl<'a> From<ListingEvent<'a>> for Event {
+    fn from(le: ListingEvent) -> Self {
+        Event::new(le.ty.to_string()).add_attributes(vec![
+            attr("seller", le.listing.seller.to_string()),
+            attr("nft", le.listing.nft.to_string()),
+            attr("price", le.listing.price.to_string()),
+            attr("expires", le.listing.expires.to_string()),
+        ])
+    }
+}
+
+pub struct SaleEvent<'a> {
+    pub listing: &'a Listing,
+    pub buyer: &'a Addr,
+    pub fee_amount: Uint128,
+}
+
+impl<'a> From<SaleEvent<'a>> for Event {
+    fn from(se: SaleEvent) -> Self {
+        Event::new("buy-nft".to_string()).add_attributes(vec![
+            attr("seller", se.listing.seller.to_string()),
+            attr("buyer", se.buyer.to_string()),
+            attr("nft", se.listing.nft.to_string()),
+            attr("price", se.listing.price.to_string()),
+            attr("fee_amount", se.fee_amount.to_string()),
+        ])
+    }
+}
+
+pub struct AirdropEvent<'a> {
+    pub ty: &'a str,
+    pub airdrop: &'a Airdrop,
+    pub account: &'a Addr,
+    pub amount: Uint128,
+}
+
+impl<'a> From<AirdropEvent<'a>> for Event {
+    fn from(ae: AirdropEvent) -> Self {
+        Event::new(ae.ty.to_string()).add_attributes(vec![
+            attr("airdrop_id", ae.airdrop.id.to_string()),
+            attr("asset", ae.airdrop.asset.to_string()),
+            attr("height", ae.airdrop.height.to_string()),
+            attr("account", ae.account.to_string()),
+            attr("amount", ae.amount.to_string()),
+        ])
+    }
+}
+
+fn join_nfts(nfts: &[Nft<Addr>]) -> String {
+    nfts.iter()
+        .map(|nft| nft.to_string())
+        .collect::<Vec<String>>()
+        .join(",")
+}
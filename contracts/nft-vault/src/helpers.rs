@@ -1,11 +1,44 @@
-use cosmwasm_std::{to_json_binary, Addr, SubMsg, Uint128, WasmMsg};
+use cosmwasm_std::{coins, to_json_binary, Addr, BankMsg, CosmosMsg, SubMsg, Uint128, WasmMsg};
+use cw20::Cw20ExecuteMsg;
+use stake_derivative::contract::sv::ExecMsg as StakeDerivativeExecuteMsg;
+use stake_receipt::{contract::sv::ExecMsg as StakeReceiptExecuteMsg, state::StakedNftRef};
 use stake_rewards::contract::sv::ExecMsg as PassageRewardsExecuteMsg;
+use std::collections::HashMap;
 
-use crate::error::ContractError;
+use crate::{
+    error::ContractError,
+    state::{Fee, FeeAsset, FeeRecipient, LoyaltyPromotion, Nft},
+};
 
+#[derive(Clone, Copy)]
 pub struct UpdateStakeResult {
     pub user_staked_amount: Uint128,
     pub total_staked_amount: Uint128,
+    pub next_user_staked_amount: Uint128,
+}
+
+#[derive(Clone, Copy)]
+pub struct UpdateRewardWeightResult {
+    pub user_reward_weight: Uint128,
+    pub total_reward_weight: Uint128,
+    pub next_user_reward_weight: Uint128,
+}
+
+/// Changes to a staker's queued loyalty promotions, keyed by promotion time
+#[derive(Default)]
+pub struct LoyaltyPromotions {
+    /// Whether the queue was rebuilt, replacing every queued promotion
+    pub reset: bool,
+    pub removed: Vec<u64>,
+    pub added: Vec<(u64, LoyaltyPromotion)>,
+    pub cancelled: Vec<(u64, LoyaltyPromotion)>,
+}
+
+/// NFTs added to or removed from a single staker, along with the resulting collection deltas
+#[derive(Default)]
+pub struct StakerNfts {
+    pub collection_deltas: HashMap<Addr, i64>,
+    pub nfts: Vec<Nft<Addr>>,
 }
 
 pub fn setup_stake_change_messages(
@@ -33,3 +66,163 @@ pub fn setup_stake_change_messages(
 
     Ok(sub_msgs)
 }
+
+/// Lets the messages sent to the reward accounts fail without reverting the rest, replying
+/// with `reply_id` instead
+pub fn reply_on_reward_account_error(
+    sub_msgs: Vec<SubMsg>,
+    reward_accounts: &[Addr],
+    reply_id: u64,
+) -> Vec<SubMsg> {
+    sub_msgs
+        .into_iter()
+        .map(|sub_msg| match &sub_msg.msg {
+            CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, .. })
+                if reward_accounts
+                    .iter()
+                    .any(|addr| addr.as_str() == contract_addr) =>
+            {
+                SubMsg::reply_on_error(sub_msg.msg, reply_id)
+            }
+            _ => sub_msg,
+        })
+        .collect()
+}
+
+pub fn mint_receipt_message(
+    receipt_collection: &Addr,
+    nft: &Nft<Addr>,
+    owner: &Addr,
+) -> Result<SubMsg, ContractError> {
+    Ok(SubMsg::new(WasmMsg::Execute {
+        contract_addr: receipt_collection.to_string(),
+        msg: to_json_binary(&StakeReceiptExecuteMsg::Mint {
+            token_id: nft.to_string(),
+            owner: owner.to_string(),
+            staked_nft: StakedNftRef {
+                collection: nft.collection.clone(),
+                token_id: nft.token_id.clone(),
+            },
+        })?,
+        funds: vec![],
+    }))
+}
+
+pub fn burn_receipt_message(
+    receipt_collection: &Addr,
+    nft: &Nft<Addr>,
+) -> Result<SubMsg, ContractError> {
+    Ok(SubMsg::new(WasmMsg::Execute {
+        contract_addr: receipt_collection.to_string(),
+        msg: to_json_binary(&StakeReceiptExecuteMsg::Burn {
+            token_id: nft.to_string(),
+        })?,
+        funds: vec![],
+    }))
+}
+
+pub fn mint_derivative_message(
+    derivative_token: &Addr,
+    recipient: &Addr,
+    amount: Uint128,
+) -> Result<SubMsg, ContractError> {
+    Ok(SubMsg::new(WasmMsg::Execute {
+        contract_addr: derivative_token.to_string(),
+        msg: to_json_binary(&StakeDerivativeExecuteMsg::Mint {
+            recipient: recipient.to_string(),
+            amount,
+        })?,
+        funds: vec![],
+    }))
+}
+
+pub fn redeem_derivative_message(
+    derivative_token: &Addr,
+    owner: &Addr,
+    amount: Uint128,
+) -> Result<SubMsg, ContractError> {
+    Ok(SubMsg::new(WasmMsg::Execute {
+        contract_addr: derivative_token.to_string(),
+        msg: to_json_binary(&StakeDerivativeExecuteMsg::Redeem {
+            owner: owner.to_string(),
+            amount,
+        })?,
+        funds: vec![],
+    }))
+}
+
+/// Sends an airdropped asset out of the vault
+pub fn send_asset_message(
+    asset: &FeeAsset<Addr>,
+    recipient: &Addr,
+    amount: Uint128,
+) -> Result<SubMsg, ContractError> {
+    Ok(match asset {
+        FeeAsset::Native(denom) => SubMsg::new(BankMsg::Send {
+            to_address: recipient.to_string(),
+            amount: coins(amount.u128(), denom),
+        }),
+        FeeAsset::Cw20(cw20) => SubMsg::new(WasmMsg::Execute {
+            contract_addr: cw20.to_string(),
+            msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                recipient: recipient.to_string(),
+                amount,
+            })?,
+            funds: vec![],
+        }),
+    })
+}
+
+/// Pays the fee to its recipient, cw20 fees are pulled from the payer's allowance
+pub fn fee_messages(
+    fee: &Fee<Addr>,
+    payer: &Addr,
+    amount: Uint128,
+    total_staked: Uint128,
+) -> Result<Vec<SubMsg>, ContractError> {
+    let mut sub_msgs = vec![];
+
+    let recipient = match &fee.recipient {
+        FeeRecipient::Treasury(addr) | FeeRecipient::RewardAccount(addr) => addr,
+    };
+
+    let funds = match &fee.asset {
+        FeeAsset::Native(denom) => coins(amount.u128(), denom),
+        FeeAsset::Cw20(cw20) => {
+            sub_msgs.push(SubMsg::new(WasmMsg::Execute {
+                contract_addr: cw20.to_string(),
+                msg: to_json_binary(&Cw20ExecuteMsg::TransferFrom {
+                    owner: payer.to_string(),
+                    recipient: recipient.to_string(),
+                    amount,
+                })?,
+                funds: vec![],
+            }));
+            vec![]
+        }
+    };
+
+    match &fee.recipient {
+        FeeRecipient::Treasury(addr) => {
+            if !funds.is_empty() {
+                sub_msgs.push(SubMsg::new(BankMsg::Send {
+                    to_address: addr.to_string(),
+                    amount: funds,
+                }));
+            }
+        }
+        FeeRecipient::RewardAccount(addr) => {
+            sub_msgs.push(SubMsg::new(WasmMsg::Execute {
+                contract_addr: addr.to_string(),
+                msg: to_json_binary(&PassageRewardsExecuteMsg::TopUp {
+                    reward_asset: fee.asset.reward_asset(),
+                    amount,
+                    total_staked,
+                })?,
+                funds,
+            }));
+        }
+    }
+
+    Ok(sub_msgs)
+}
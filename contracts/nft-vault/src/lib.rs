@@ -1,6 +1,11 @@
 pub mod claim;
+// The generated message constructors take every optional config field
+#[allow(clippy::too_many_arguments)]
 pub mod contract;
 pub mod error;
 pub mod events;
 pub mod helpers;
+pub mod msg;
+pub mod ownership;
+pub mod roles;
 pub mod state;
@@ -0,0 +1,26 @@
+use cosmwasm_schema::cw_serde;
+use stake_rewards::{
+    curve::EmissionCurve,
+    state::{Accounting, EmissionSegment, UndistributedMode, Vesting},
+};
+
+/// Messages attached to cw20 tokens sent to the vault
+#[cw_serde]
+pub enum ReceiveMsg {
+    /// Creates a reward account paying out the sent token, funded with exactly the sent amount
+    CreateRewardAccount {
+        label: String,
+        schedule: Vec<EmissionSegment>,
+        curve: Option<EmissionCurve>,
+        undistributed_mode: UndistributedMode,
+        grace_period_sec: u64,
+        vesting: Option<Vesting>,
+        /// Pull accounting by default
+        accounting: Option<Accounting>,
+    },
+    /// Forwards the sent amount to a reward account paying out the sent token
+    FundRewardAccount {
+        reward_account: String,
+        extra_duration_sec: Option<u64>,
+    },
+}
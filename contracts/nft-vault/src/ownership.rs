@@ -0,0 +1,317 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{attr, Addr, BlockInfo, Event, StdResult, Storage};
+use cw_storage_plus::Item;
+use cw_utils::Expiration;
+use uju_cw2_common::error::CommonError;
+
+#[cw_serde]
+pub struct OwnershipInfo {
+    pub owner: Option<Addr>,
+    pub pending_owner: Option<Addr>,
+    pub pending_expiry: Option<Expiration>,
+}
+
+/// Two-step ownership, independent of the wasm contract admin
+pub struct Ownership(Item<OwnershipInfo>);
+
+impl Ownership {
+    pub const fn new(storage_key: &'static str) -> Self {
+        Ownership(Item::new(storage_key))
+    }
+
+    pub fn initialize(&self, storage: &mut dyn Storage, owner: &Addr) -> StdResult<OwnershipInfo> {
+        let ownership = OwnershipInfo {
+            owner: Some(owner.clone()),
+            pending_owner: None,
+            pending_expiry: None,
+        };
+        self.0.save(storage, &ownership)?;
+        Ok(ownership)
+    }
+
+    pub fn load(&self, storage: &dyn Storage) -> StdResult<OwnershipInfo> {
+        self.0.load(storage)
+    }
+
+    /// Errors unless the sender is the current owner.
+    pub fn assert_owner(&self, storage: &dyn Storage, sender: &Addr) -> Result<(), CommonError> {
+        let ownership = self.0.load(storage)?;
+        match ownership.owner {
+            Some(owner) if owner == sender => Ok(()),
+            Some(_) => Err(CommonError::Unauthorized(
+                "only the owner can perform this action".to_string(),
+            )),
+            None => Err(CommonError::Unauthorized("ownership renounced".to_string())),
+        }
+    }
+
+    /// Proposes a new owner, who must accept before the optional expiry.
+    pub fn transfer_ownership(
+        &self,
+        storage: &mut dyn Storage,
+        block: &BlockInfo,
+        sender: &Addr,
+        new_owner: Addr,
+        expiry: Option<Expiration>,
+    ) -> Result<OwnershipInfo, CommonError> {
+        self.assert_owner(storage, sender)?;
+
+        if let Some(expiry) = &expiry {
+            if expiry.is_expired(block) {
+                return Err(CommonError::InvalidInput(
+                    "expiry is in the past".to_string(),
+                ));
+            }
+        }
+
+        let mut ownership = self.0.load(storage)?;
+        ownership.pending_owner = Some(new_owner);
+        ownership.pending_expiry = expiry;
+        self.0.save(storage, &ownership)?;
+
+        Ok(ownership)
+    }
+
+    /// Completes a pending transfer, the sender must be the pending owner.
+    pub fn accept_ownership(
+        &self,
+        storage: &mut dyn Storage,
+        block: &BlockInfo,
+        sender: &Addr,
+    ) -> Result<OwnershipInfo, CommonError> {
+        let mut ownership = self.0.load(storage)?;
+
+        match &ownership.pending_owner {
+            Some(pending_owner) if pending_owner == sender => {}
+            Some(_) => {
+                return Err(CommonError::Unauthorized(
+                    "sender is not the pending owner".to_string(),
+                ))
+            }
+            None => {
+                return Err(CommonError::InvalidInput(
+                    "no pending ownership transfer".to_string(),
+                ))
+            }
+        }
+
+        if let Some(expiry) = &ownership.pending_expiry {
+            if expiry.is_expired(block) {
+                return Err(CommonError::InvalidInput(
+                    "pending ownership transfer expired".to_string(),
+                ));
+            }
+        }
+
+        ownership.owner = ownership.pending_owner.take();
+        ownership.pending_expiry = None;
+        self.0.save(storage, &ownership)?;
+
+        Ok(ownership)
+    }
+
+    /// Replaces the owner outright, clearing any pending transfer. Used by governance.
+    pub fn set_owner(
+        &self,
+        storage: &mut dyn Storage,
+        owner: Option<Addr>,
+    ) -> StdResult<OwnershipInfo> {
+        let ownership = OwnershipInfo {
+            owner,
+            pending_owner: None,
+            pending_expiry: None,
+        };
+        self.0.save(storage, &ownership)?;
+
+        Ok(ownership)
+    }
+
+    /// Removes the owner for good, along with any pending transfer.
+    pub fn renounce_ownership(
+        &self,
+        storage: &mut dyn Storage,
+        sender: &Addr,
+    ) -> Result<OwnershipInfo, CommonError> {
+        self.assert_owner(storage, sender)?;
+
+        let ownership = OwnershipInfo {
+            owner: None,
+            pending_owner: None,
+            pending_expiry: None,
+        };
+        self.0.save(storage, &ownership)?;
+
+        Ok(ownership)
+    }
+}
+
+pub struct OwnershipEvent<'a> {
+    pub ty: &'a str,
+    pub ownership: &'a OwnershipInfo,
+}
+
+impl<'a> From<OwnershipEvent<'a>> for Event {
+    fn from(oe: OwnershipEvent) -> Self {
+        let or_none =
+            |addr: &Option<Addr>| addr.as_ref().map_or("none".to_string(), |a| a.to_string());
+
+        Event::new(oe.ty.to_string()).add_attributes(vec![
+            attr("owner", or_none(&oe.ownership.owner)),
+            attr("pending_owner", or_none(&oe.ownership.pending_owner)),
+            attr(
+                "pending_expiry",
+                oe.ownership
+                    .pending_expiry
+                    .map_or("none".to_string(), |expiry| expiry.to_string()),
+            ),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+
+    #[test]
+    fn test_assert_owner() {
+        let mut deps = mock_dependencies();
+        let ownership = Ownership::new("ownership");
+
+        ownership
+            .initialize(deps.as_mut().storage, &Addr::unchecked("owner"))
+            .unwrap();
+
+        ownership
+            .assert_owner(deps.as_ref().storage, &Addr::unchecked("owner"))
+            .unwrap();
+        assert!(ownership
+            .assert_owner(deps.as_ref().storage, &Addr::unchecked("other"))
+            .is_err());
+    }
+
+    #[test]
+    fn test_transfer_and_accept_ownership() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let ownership = Ownership::new("ownership");
+
+        ownership
+            .initialize(deps.as_mut().storage, &Addr::unchecked("owner"))
+            .unwrap();
+
+        // Only the owner can propose a transfer
+        assert!(ownership
+            .transfer_ownership(
+                deps.as_mut().storage,
+                &env.block,
+                &Addr::unchecked("other"),
+                Addr::unchecked("other"),
+                None,
+            )
+            .is_err());
+
+        ownership
+            .transfer_ownership(
+                deps.as_mut().storage,
+                &env.block,
+                &Addr::unchecked("owner"),
+                Addr::unchecked("new_owner"),
+                None,
+            )
+            .unwrap();
+
+        // Ownership does not move until accepted
+        ownership
+            .assert_owner(deps.as_ref().storage, &Addr::unchecked("owner"))
+            .unwrap();
+
+        // Only the pending owner can accept
+        assert!(ownership
+            .accept_ownership(deps.as_mut().storage, &env.block, &Addr::unchecked("other"))
+            .is_err());
+
+        let info = ownership
+            .accept_ownership(
+                deps.as_mut().storage,
+                &env.block,
+                &Addr::unchecked("new_owner"),
+            )
+            .unwrap();
+        assert_eq!(info.owner, Some(Addr::unchecked("new_owner")));
+        assert_eq!(info.pending_owner, None);
+
+        assert!(ownership
+            .assert_owner(deps.as_ref().storage, &Addr::unchecked("owner"))
+            .is_err());
+    }
+
+    #[test]
+    fn test_accept_expired_ownership_transfer() {
+        let mut deps = mock_dependencies();
+        let mut env = mock_env();
+        let ownership = Ownership::new("ownership");
+
+        ownership
+            .initialize(deps.as_mut().storage, &Addr::unchecked("owner"))
+            .unwrap();
+
+        let expiry = Expiration::AtHeight(env.block.height + 10);
+        ownership
+            .transfer_ownership(
+                deps.as_mut().storage,
+                &env.block,
+                &Addr::unchecked("owner"),
+                Addr::unchecked("new_owner"),
+                Some(expiry),
+            )
+            .unwrap();
+
+        env.block.height += 10;
+        assert!(ownership
+            .accept_ownership(
+                deps.as_mut().storage,
+                &env.block,
+                &Addr::unchecked("new_owner"),
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_renounce_ownership() {
+        let mut deps = mock_dependencies();
+        let env = mock_env();
+        let ownership = Ownership::new("ownership");
+
+        ownership
+            .initialize(deps.as_mut().storage, &Addr::unchecked("owner"))
+            .unwrap();
+        ownership
+            .transfer_ownership(
+                deps.as_mut().storage,
+                &env.block,
+                &Addr::unchecked("owner"),
+                Addr::unchecked("new_owner"),
+                None,
+            )
+            .unwrap();
+
+        let info = ownership
+            .renounce_ownership(deps.as_mut().storage, &Addr::unchecked("owner"))
+            .unwrap();
+        assert_eq!(info.owner, None);
+        assert_eq!(info.pending_owner, None);
+
+        // The pending transfer is cancelled and nobody owns the contract
+        assert!(ownership
+            .accept_ownership(
+                deps.as_mut().storage,
+                &env.block,
+                &Addr::unchecked("new_owner"),
+            )
+            .is_err());
+        assert!(ownership
+            .assert_owner(deps.as_ref().storage, &Addr::unchecked("owner"))
+            .is_err());
+    }
+}
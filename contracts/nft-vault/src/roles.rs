@@ -0,0 +1,215 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{attr, Addr, Event, Order, StdResult, Storage};
+use cw_storage_plus::{Bound, Map};
+use uju_cw2_common::error::CommonError;
+
+use crate::ownership::Ownership;
+
+#[cw_serde]
+#[derive(Copy, Eq, PartialOrd, Ord)]
+pub enum Role {
+    ConfigManager,
+    RewardManager,
+    Pauser,
+    Moderator,
+    VaultCreator,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::ConfigManager => "config_manager",
+            Role::RewardManager => "reward_manager",
+            Role::Pauser => "pauser",
+            Role::Moderator => "moderator",
+            Role::VaultCreator => "vault_creator",
+        }
+    }
+}
+
+/// Roles granted by the owner, the owner implicitly holds every role
+pub struct Roles(Map<Addr, Vec<Role>>);
+
+impl Roles {
+    pub const fn new(storage_key: &'static str) -> Self {
+        Roles(Map::new(storage_key))
+    }
+
+    pub fn load(&self, storage: &dyn Storage, address: &Addr) -> StdResult<Vec<Role>> {
+        Ok(self
+            .0
+            .may_load(storage, address.clone())?
+            .unwrap_or_default())
+    }
+
+    pub fn has_role(&self, storage: &dyn Storage, address: &Addr, role: Role) -> StdResult<bool> {
+        Ok(self.load(storage, address)?.contains(&role))
+    }
+
+    pub fn grant(
+        &self,
+        storage: &mut dyn Storage,
+        address: &Addr,
+        role: Role,
+    ) -> Result<Vec<Role>, CommonError> {
+        let mut roles = self.load(storage, address)?;
+        if roles.contains(&role) {
+            return Err(CommonError::InvalidInput(format!(
+                "{} already has role {}",
+                address,
+                role.as_str()
+            )));
+        }
+
+        roles.push(role);
+        roles.sort();
+        self.0.save(storage, address.clone(), &roles)?;
+
+        Ok(roles)
+    }
+
+    pub fn revoke(
+        &self,
+        storage: &mut dyn Storage,
+        address: &Addr,
+        role: Role,
+    ) -> Result<Vec<Role>, CommonError> {
+        let mut roles = self.load(storage, address)?;
+        if !roles.contains(&role) {
+            return Err(CommonError::InvalidInput(format!(
+                "{} does not have role {}",
+                address,
+                role.as_str()
+            )));
+        }
+
+        roles.retain(|r| r != &role);
+        if roles.is_empty() {
+            self.0.remove(storage, address.clone());
+        } else {
+            self.0.save(storage, address.clone(), &roles)?;
+        }
+
+        Ok(roles)
+    }
+
+    /// Errors unless the sender is the owner or has been granted the role.
+    pub fn assert_owner_or_role(
+        &self,
+        storage: &dyn Storage,
+        ownership: &Ownership,
+        sender: &Addr,
+        role: Role,
+    ) -> Result<(), CommonError> {
+        if ownership.assert_owner(storage, sender).is_ok()
+            || self.has_role(storage, sender, role)?
+        {
+            return Ok(());
+        }
+
+        Err(CommonError::Unauthorized(format!(
+            "requires owner or role {}",
+            role.as_str()
+        )))
+    }
+
+    pub fn range(
+        &self,
+        storage: &dyn Storage,
+        min: Option<Bound<Addr>>,
+        max: Option<Bound<Addr>>,
+        order: Order,
+        limit: usize,
+    ) -> StdResult<Vec<(Addr, Vec<Role>)>> {
+        self.0
+            .range(storage, min, max, order)
+            .take(limit)
+            .collect::<StdResult<Vec<_>>>()
+    }
+}
+
+pub struct RoleEvent<'a> {
+    pub ty: &'a str,
+    pub address: &'a Addr,
+    pub role: Role,
+}
+
+impl<'a> From<RoleEvent<'a>> for Event {
+    fn from(re: RoleEvent) -> Self {
+        Event::new(re.ty.to_string()).add_attributes(vec![
+            attr("address", re.address.to_string()),
+            attr("role", re.role.as_str()),
+        ])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use cosmwasm_std::testing::mock_dependencies;
+
+    #[test]
+    fn test_grant_and_revoke_role() {
+        let mut deps = mock_dependencies();
+        let ownership = Ownership::new("ownership");
+        let roles = Roles::new("roles");
+
+        let owner = Addr::unchecked("owner");
+        let manager = Addr::unchecked("manager");
+
+        ownership.initialize(deps.as_mut().storage, &owner).unwrap();
+
+        // The owner implicitly holds every role
+        roles
+            .assert_owner_or_role(deps.as_ref().storage, &ownership, &owner, Role::Moderator)
+            .unwrap();
+        assert!(roles
+            .assert_owner_or_role(
+                deps.as_ref().storage,
+                &ownership,
+                &manager,
+                Role::RewardManager
+            )
+            .is_err());
+
+        roles
+            .grant(deps.as_mut().storage, &manager, Role::RewardManager)
+            .unwrap();
+        assert!(roles
+            .grant(deps.as_mut().storage, &manager, Role::RewardManager)
+            .is_err());
+
+        roles
+            .assert_owner_or_role(
+                deps.as_ref().storage,
+                &ownership,
+                &manager,
+                Role::RewardManager,
+            )
+            .unwrap();
+        assert!(roles
+            .assert_owner_or_role(
+                deps.as_ref().storage,
+                &ownership,
+                &manager,
+                Role::ConfigManager
+            )
+            .is_err());
+
+        let remaining = roles
+            .revoke(deps.as_mut().storage, &manager, Role::RewardManager)
+            .unwrap();
+        assert!(remaining.is_empty());
+        assert!(roles
+            .revoke(deps.as_mut().storage, &manager, Role::RewardManager)
+            .is_err());
+        assert!(roles
+            .assert_owner_or_role(
+                deps.as_ref().storage,
+                &ownership,
+                &manager,
+                Role::RewardManager
+            )
+            .is_err());
+    }
+}
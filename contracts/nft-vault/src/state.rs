@@ -1,17 +1,84 @@
 use std::fmt;
 
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Addr, Api, StdError};
+use cosmwasm_std::{ensure, Addr, Api, Coin, StdError, StdResult, Timestamp, Uint128};
 use cw_address_like::AddressLike;
 use cw_storage_plus::{Index, IndexList, MultiIndex};
+use cw_utils::Expiration;
+use stake_rewards::state::RewardAsset;
+use uju_cw2_common::error::CommonError;
 
-use crate::error::ContractError;
+use crate::{contract::BASIS_POINTS, error::ContractError};
 
 #[cw_serde]
 pub struct Config<T: AddressLike> {
     pub rewards_code_id: u64,
     pub collections: Vec<T>,
     pub unstaking_duration_sec: u64,
+    pub receipt_code_id: Option<u64>,
+    pub derivative_code_id: Option<u64>,
+    pub warmup_duration_sec: u64,
+    pub loyalty_tiers: Vec<LoyaltyTier>,
+    pub collection_unstaking_durations: Vec<CollectionUnstakingDuration<T>>,
+    pub stake_fee: Option<Fee<T>>,
+    pub unstake_fee: Option<Fee<T>>,
+    pub sale_fee: Option<SaleFee<T>>,
+    pub boost_collections: Vec<BoostCollection<T>>,
+    pub max_boosts: u32,
+}
+
+impl<T: AddressLike> Config<T> {
+    /// The unstaking duration of a collection, falling back to the global duration
+    pub fn collection_unstaking_duration_sec(&self, collection: &T) -> u64 {
+        self.collection_unstaking_durations
+            .iter()
+            .find(|cud| &cud.collection == collection)
+            .map_or(self.unstaking_duration_sec, |cud| {
+                cud.unstaking_duration_sec
+            })
+    }
+
+    /// The bonus of a booster NFT, zero once its collection stopped boosting
+    pub fn boost_bonus_bps(&self, collection: &T) -> u64 {
+        self.boost_collections
+            .iter()
+            .find(|bc| &bc.collection == collection)
+            .map_or(0, |bc| bc.bonus_bps)
+    }
+
+    /// Number of loyalty tiers reached by an NFT staked continuously for the given duration
+    pub fn loyalty_tier(&self, staked_duration_sec: u64) -> usize {
+        self.loyalty_tiers
+            .iter()
+            .filter(|tier| staked_duration_sec >= tier.min_duration_sec)
+            .count()
+    }
+
+    /// The weight of an active NFT that reached the given number of loyalty tiers
+    pub fn loyalty_weight(&self, tier: usize) -> u64 {
+        BASIS_POINTS
+            + self
+                .loyalty_tiers
+                .iter()
+                .take(tier)
+                .map(|tier| tier.bonus_bps)
+                .max()
+                .unwrap_or_default()
+    }
+
+    /// When an NFT staked at the given time reaches the tier after the given one, if any
+    pub fn next_loyalty_promotion(&self, staked_at: u64, tier: usize) -> Option<u64> {
+        self.loyalty_tiers
+            .get(tier)
+            .map(|next_tier| staked_at.saturating_add(next_tier.min_duration_sec))
+    }
+
+    pub fn loyalty_tier_durations(&self) -> Vec<u64> {
+        self.loyalty_tiers
+            .iter()
+            .map(|tier| tier.min_duration_sec)
+            .collect()
+    }
 }
 
 impl Config<String> {
@@ -23,12 +90,336 @@ impl Config<String> {
             .collect::<Result<Vec<Addr>, StdError>>()?;
         collections.sort();
 
+        let collection_unstaking_durations = validate_collection_unstaking_durations(
+            api,
+            &collections,
+            self.collection_unstaking_durations,
+        )?;
+        let boost_collections =
+            validate_boost_collections(api, &collections, self.boost_collections)?;
+
         Ok(Config {
             rewards_code_id: self.rewards_code_id,
             collections,
             unstaking_duration_sec: self.unstaking_duration_sec,
+            receipt_code_id: self.receipt_code_id,
+            derivative_code_id: self.derivative_code_id,
+            warmup_duration_sec: self.warmup_duration_sec,
+            loyalty_tiers: validate_loyalty_tiers(self.loyalty_tiers)?,
+            collection_unstaking_durations,
+            stake_fee: self.stake_fee.map(|fee| fee.str_to_addr(api)).transpose()?,
+            unstake_fee: self
+                .unstake_fee
+                .map(|fee| fee.str_to_addr(api))
+                .transpose()?,
+            sale_fee: self.sale_fee.map(|fee| fee.str_to_addr(api)).transpose()?,
+            boost_collections,
+            max_boosts: self.max_boosts,
+        })
+    }
+}
+
+/// A collection of booster NFTs, multiplying rewards without counting as stake
+#[cw_serde]
+pub struct BoostCollection<T: AddressLike> {
+    pub collection: T,
+    pub bonus_bps: u64,
+}
+
+pub fn validate_boost_collections(
+    api: &dyn Api,
+    collections: &[Addr],
+    boost_collections: Vec<BoostCollection<String>>,
+) -> Result<Vec<BoostCollection<Addr>>, ContractError> {
+    let mut boost_collections = boost_collections
+        .into_iter()
+        .map(|bc| {
+            let collection = api.addr_validate(&bc.collection)?;
+            ensure!(
+                !collections.contains(&collection),
+                CommonError::InvalidInput(format!("collection {} is staked", collection))
+            );
+            Ok(BoostCollection {
+                collection,
+                bonus_bps: bc.bonus_bps,
+            })
+        })
+        .collect::<Result<Vec<_>, ContractError>>()?;
+    boost_collections.sort_by(|a, b| a.collection.cmp(&b.collection));
+
+    for bcs in boost_collections.windows(2) {
+        ensure!(
+            bcs[0].collection != bcs[1].collection,
+            CommonError::InvalidInput("duplicate boost collection".to_string())
+        );
+    }
+
+    Ok(boost_collections)
+}
+
+#[cw_serde]
+pub enum FeeAsset<T: AddressLike> {
+    Native(String),
+    Cw20(T),
+}
+
+impl fmt::Display for FeeAsset<Addr> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FeeAsset::Native(denom) => write!(f, "{}", denom),
+            FeeAsset::Cw20(cw20) => write!(f, "{}", cw20),
+        }
+    }
+}
+
+impl FeeAsset<Addr> {
+    /// The reward asset a reward account pays the fee out as
+    pub fn reward_asset(&self) -> RewardAsset {
+        match self {
+            FeeAsset::Native(denom) => RewardAsset::Native(denom.clone()),
+            FeeAsset::Cw20(cw20) => RewardAsset::Cw20(cw20.clone()),
+        }
+    }
+}
+
+#[cw_serde]
+pub enum FeeRecipient<T: AddressLike> {
+    Treasury(T),
+    /// Tops up the rewards left in the reward account's current period
+    RewardAccount(T),
+}
+
+impl fmt::Display for FeeRecipient<Addr> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FeeRecipient::Treasury(addr) => write!(f, "treasury:{}", addr),
+            FeeRecipient::RewardAccount(addr) => write!(f, "reward_account:{}", addr),
+        }
+    }
+}
+
+/// A fee charged per NFT
+#[cw_serde]
+pub struct Fee<T: AddressLike> {
+    pub asset: FeeAsset<T>,
+    pub amount: Uint128,
+    pub recipient: FeeRecipient<T>,
+}
+
+impl fmt::Display for Fee<Addr> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}:{}", self.amount, self.asset, self.recipient)
+    }
+}
+
+impl Fee<String> {
+    pub fn str_to_addr(self, api: &dyn Api) -> Result<Fee<Addr>, ContractError> {
+        ensure!(
+            !self.amount.is_zero(),
+            CommonError::InvalidInput("fee amount must be greater than zero".to_string())
+        );
+
+        let asset = match self.asset {
+            FeeAsset::Native(denom) => FeeAsset::Native(denom),
+            FeeAsset::Cw20(cw20) => FeeAsset::Cw20(api.addr_validate(&cw20)?),
+        };
+        let recipient = match self.recipient {
+            FeeRecipient::Treasury(addr) => FeeRecipient::Treasury(api.addr_validate(&addr)?),
+            FeeRecipient::RewardAccount(addr) => {
+                FeeRecipient::RewardAccount(api.addr_validate(&addr)?)
+            }
+        };
+
+        Ok(Fee {
+            asset,
+            amount: self.amount,
+            recipient,
+        })
+    }
+}
+
+/// A share of the price of every sale of a listed NFT
+#[cw_serde]
+pub struct SaleFee<T: AddressLike> {
+    pub fee_bps: u64,
+    pub recipient: T,
+}
+
+impl fmt::Display for SaleFee<Addr> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}bps:{}", self.fee_bps, self.recipient)
+    }
+}
+
+impl SaleFee<String> {
+    pub fn str_to_addr(self, api: &dyn Api) -> Result<SaleFee<Addr>, ContractError> {
+        ensure!(
+            self.fee_bps <= BASIS_POINTS,
+            CommonError::InvalidInput("sale fee cannot exceed the price".to_string())
+        );
+
+        Ok(SaleFee {
+            fee_bps: self.fee_bps,
+            recipient: api.addr_validate(&self.recipient)?,
+        })
+    }
+}
+
+/// The fees `update_fees` can remove
+#[cw_serde]
+pub enum FeeKind {
+    Stake,
+    Unstake,
+    Sale,
+}
+
+#[cw_serde]
+pub struct CollectedFee {
+    pub asset: FeeAsset<Addr>,
+    pub amount: Uint128,
+}
+
+#[cw_serde]
+pub struct CollectionUnstakingDuration<T: AddressLike> {
+    pub collection: T,
+    pub unstaking_duration_sec: u64,
+}
+
+pub fn validate_collection_unstaking_durations(
+    api: &dyn Api,
+    collections: &[Addr],
+    collection_unstaking_durations: Vec<CollectionUnstakingDuration<String>>,
+) -> Result<Vec<CollectionUnstakingDuration<Addr>>, ContractError> {
+    let mut collection_unstaking_durations = collection_unstaking_durations
+        .into_iter()
+        .map(|cud| {
+            let collection = api.addr_validate(&cud.collection)?;
+            ensure!(
+                collections.contains(&collection),
+                CommonError::InvalidInput(format!("collection {} not in vault", collection))
+            );
+            Ok(CollectionUnstakingDuration {
+                collection,
+                unstaking_duration_sec: cud.unstaking_duration_sec,
+            })
         })
+        .collect::<Result<Vec<_>, ContractError>>()?;
+    collection_unstaking_durations.sort_by(|a, b| a.collection.cmp(&b.collection));
+
+    for cuds in collection_unstaking_durations.windows(2) {
+        ensure!(
+            cuds[0].collection != cuds[1].collection,
+            CommonError::InvalidInput("duplicate collection unstaking duration".to_string())
+        );
     }
+
+    Ok(collection_unstaking_durations)
+}
+
+#[cw_serde]
+pub struct ConfigResponse {
+    pub config: Config<Addr>,
+    /// The effective unstaking duration of every collection
+    pub unstaking_durations: Vec<CollectionUnstakingDuration<Addr>>,
+}
+
+#[cw_serde]
+pub struct LoyaltyTier {
+    pub min_duration_sec: u64,
+    pub bonus_bps: u64,
+}
+
+/// A staker's active NFTs counted per collection and number of loyalty tiers reached
+#[cw_serde]
+pub struct Loyalty {
+    /// Durations of the loyalty tiers the counts were made against
+    pub tier_durations: Vec<u64>,
+    pub tier_counts: Vec<(Addr, Vec<u64>)>,
+}
+
+impl Loyalty {
+    pub fn new(tier_durations: Vec<u64>) -> Self {
+        Loyalty {
+            tier_durations,
+            tier_counts: vec![],
+        }
+    }
+
+    pub fn add(&mut self, collection: &Addr, tier: usize, count: u64) -> StdResult<()> {
+        let tier_count = self.tier_count_mut(collection, tier);
+        *tier_count = tier_count
+            .checked_add(count)
+            .ok_or_else(|| StdError::generic_err("loyalty tier count overflow"))?;
+        Ok(())
+    }
+
+    pub fn remove(&mut self, collection: &Addr, tier: usize, count: u64) -> StdResult<()> {
+        let tier_count = self.tier_count_mut(collection, tier);
+        *tier_count = tier_count
+            .checked_sub(count)
+            .ok_or_else(|| StdError::generic_err("loyalty tier count underflow"))?;
+        Ok(())
+    }
+
+    /// The summed weight of the active NFTs of a collection
+    pub fn collection_weight(&self, config: &Config<Addr>, collection: &Addr) -> u64 {
+        self.tier_counts
+            .iter()
+            .find(|(c, _)| c == collection)
+            .map_or(0, |(_, counts)| {
+                counts
+                    .iter()
+                    .enumerate()
+                    .map(|(tier, count)| count * config.loyalty_weight(tier))
+                    .sum()
+            })
+    }
+
+    fn tier_count_mut(&mut self, collection: &Addr, tier: usize) -> &mut u64 {
+        let tiers_len = self.tier_durations.len() + 1;
+        let index = match self.tier_counts.iter().position(|(c, _)| c == collection) {
+            Some(index) => index,
+            None => {
+                self.tier_counts
+                    .push((collection.clone(), vec![0; tiers_len]));
+                self.tier_counts.len() - 1
+            }
+        };
+        &mut self.tier_counts[index].1[tier]
+    }
+}
+
+/// NFTs of a collection staked at the same time, reaching a loyalty tier together
+#[cw_serde]
+pub struct LoyaltyPromotion {
+    pub collection: Addr,
+    pub staked_at: u64,
+    /// Number of loyalty tiers reached with the promotion
+    pub tier: u32,
+    pub count: u64,
+}
+
+impl LoyaltyPromotion {
+    pub fn same_cohort(&self, other: &LoyaltyPromotion) -> bool {
+        self.collection == other.collection
+            && self.staked_at == other.staked_at
+            && self.tier == other.tier
+    }
+}
+
+pub fn validate_loyalty_tiers(
+    mut loyalty_tiers: Vec<LoyaltyTier>,
+) -> Result<Vec<LoyaltyTier>, ContractError> {
+    loyalty_tiers.sort_by_key(|tier| tier.min_duration_sec);
+
+    for tiers in loyalty_tiers.windows(2) {
+        ensure!(
+            tiers[0].min_duration_sec < tiers[1].min_duration_sec,
+            CommonError::InvalidInput("duplicate loyalty tier duration".to_string())
+        );
+    }
+
+    Ok(loyalty_tiers)
 }
 
 #[cw_serde]
@@ -57,11 +448,91 @@ impl Nft<String> {
 pub struct StakedNft {
     pub staker: Addr,
     pub nft: Nft<Addr>,
+    pub has_receipt: bool,
+    pub staked_at: Timestamp,
+    /// Holder of the usage rights lent by the staker, cleared when the position moves
+    pub user: Option<NftUser<Addr>>,
+}
+
+/// Usage rights of a staked NFT, following ERC-4907
+#[cw_serde]
+pub struct NftUser<T: AddressLike> {
+    pub user: T,
+    pub expires: Timestamp,
+}
+
+impl fmt::Display for NftUser<Addr> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.user, self.expires.seconds())
+    }
 }
 
 // Collection, token_id
 pub type StakedNftId = (Addr, String);
 
+// Staker, staked at seconds
+pub type PendingNftsId = (Addr, u64);
+
+#[cw_serde]
+pub struct RewardWeight {
+    pub reward_weight: Uint128,
+    pub latest_reward_weight: Uint128,
+    pub total_reward_weight: Uint128,
+}
+
+#[cw_serde]
+pub struct StakingPower {
+    pub active: Uint128,
+    pub pending: Uint128,
+    pub next_activation: Option<Timestamp>,
+}
+
+/// A lump sum shared between stakers pro rata to their staking power at the snapshot height
+#[cw_serde]
+pub struct Airdrop {
+    pub id: u64,
+    pub funder: Addr,
+    pub asset: FeeAsset<Addr>,
+    pub amount: Uint128,
+    /// Staking powers are read at the start of this height
+    pub height: u64,
+    pub total_staked_amount: Uint128,
+    pub claimed_amount: Uint128,
+    /// After expiry claims close and the funder can claw back what is left
+    pub expiration: Expiration,
+    pub clawed_back: bool,
+}
+
+#[cw_serde]
+pub struct AirdropShare {
+    pub amount: Uint128,
+    pub claimed: bool,
+}
+
+/// A staked NFT offered for sale by its staker
+#[cw_serde]
+pub struct Listing {
+    pub seller: Addr,
+    pub nft: Nft<Addr>,
+    pub price: Coin,
+    /// Expired listings can no longer be bought
+    pub expires: Expiration,
+}
+
+#[cw_serde]
+pub struct EscrowedNft {
+    pub staker: Addr,
+    pub nft: Nft<Addr>,
+    pub reason: String,
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub struct Status {
+    pub paused: bool,
+    pub emergency_mode: bool,
+}
+
 /// Defines indices for accessing staked NFTs
 pub struct StakedNftIndices {
     // Index StakedNft by staker and collection
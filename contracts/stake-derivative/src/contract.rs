@@ -0,0 +1,520 @@
+use cosmwasm_std::{
+    ensure, ensure_eq, Addr, Binary, BlockInfo, Response, StdResult, Storage, Uint128, WasmMsg,
+};
+use cw2::set_contract_version;
+use cw20::{
+    AllowanceResponse, BalanceResponse, Cw20ReceiveMsg, Expiration, MinterResponse,
+    TokenInfoResponse,
+};
+use cw_storage_plus::{Item, Map};
+use cw_utils::nonpayable;
+use sylvia::{
+    contract,
+    types::{ExecCtx, InstantiateCtx, QueryCtx},
+};
+use uju_cw2_common::error::CommonError;
+
+use crate::{
+    error::ContractError,
+    events::{AllowanceEvent, TransferEvent},
+    msg::DerivativeHookMsg,
+    state::TokenInfo,
+};
+
+pub const CONTRACT_NAME: &str = env!("CARGO_PKG_NAME");
+pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+pub struct StakeDerivativeContract {
+    pub token_info: Item<TokenInfo>,
+    pub balances: Map<Addr, Uint128>,
+    pub allowances: Map<(Addr, Addr), AllowanceResponse>,
+}
+
+impl Default for StakeDerivativeContract {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(not(feature = "library"))]
+use sylvia::entry_points;
+
+#[cfg_attr(not(feature = "library"), entry_points)]
+#[contract]
+#[sv::error(ContractError)]
+impl StakeDerivativeContract {
+    pub const fn new() -> Self {
+        Self {
+            token_info: Item::new("T"),
+            balances: Map::new("B"),
+            allowances: Map::new("A"),
+        }
+    }
+
+    #[sv::msg(instantiate)]
+    pub fn instantiate(
+        &self,
+        ctx: InstantiateCtx,
+        name: String,
+        symbol: String,
+        decimals: u8,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        set_contract_version(ctx.deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+        // The instantiating vault is the only account able to mint and redeem tokens
+        self.token_info.save(
+            ctx.deps.storage,
+            &TokenInfo {
+                name,
+                symbol,
+                decimals,
+                total_supply: Uint128::zero(),
+                minter: ctx.info.sender,
+            },
+        )?;
+
+        Ok(Response::new())
+    }
+
+    #[sv::msg(exec)]
+    pub fn mint(
+        &self,
+        ctx: ExecCtx,
+        recipient: String,
+        amount: Uint128,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let mut token_info = self.token_info.load(ctx.deps.storage)?;
+        ensure_eq!(
+            token_info.minter,
+            ctx.info.sender,
+            CommonError::Unauthorized("sender is not the minter".to_string())
+        );
+
+        let recipient = ctx.deps.api.addr_validate(&recipient)?;
+
+        token_info.total_supply = token_info.total_supply.checked_add(amount)?;
+        self.token_info.save(ctx.deps.storage, &token_info)?;
+
+        self.add_balance(ctx.deps.storage, &recipient, amount)?;
+
+        let response = Response::new().add_event(TransferEvent {
+            ty: "mint",
+            sender: ctx.info.sender.as_str(),
+            recipient: recipient.as_str(),
+            amount: &amount.to_string(),
+        });
+
+        Ok(response)
+    }
+
+    #[sv::msg(exec)]
+    pub fn redeem(
+        &self,
+        ctx: ExecCtx,
+        owner: String,
+        amount: Uint128,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let mut token_info = self.token_info.load(ctx.deps.storage)?;
+        ensure_eq!(
+            token_info.minter,
+            ctx.info.sender,
+            CommonError::Unauthorized("sender is not the minter".to_string())
+        );
+
+        let owner = ctx.deps.api.addr_validate(&owner)?;
+
+        self.sub_balance(ctx.deps.storage, &owner, amount)?;
+
+        token_info.total_supply = token_info.total_supply.checked_sub(amount)?;
+        self.token_info.save(ctx.deps.storage, &token_info)?;
+
+        let response = Response::new().add_event(TransferEvent {
+            ty: "redeem",
+            sender: owner.as_str(),
+            recipient: ctx.info.sender.as_str(),
+            amount: &amount.to_string(),
+        });
+
+        Ok(response)
+    }
+
+    #[sv::msg(exec)]
+    pub fn transfer(
+        &self,
+        ctx: ExecCtx,
+        recipient: String,
+        amount: Uint128,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let recipient = ctx.deps.api.addr_validate(&recipient)?;
+
+        let hook_msg =
+            self.transfer_tokens(ctx.deps.storage, &ctx.info.sender, &recipient, amount)?;
+
+        let response = Response::new()
+            .add_event(TransferEvent {
+                ty: "transfer",
+                sender: ctx.info.sender.as_str(),
+                recipient: recipient.as_str(),
+                amount: &amount.to_string(),
+            })
+            .add_message(hook_msg);
+
+        Ok(response)
+    }
+
+    #[sv::msg(exec)]
+    pub fn send(
+        &self,
+        ctx: ExecCtx,
+        contract: String,
+        amount: Uint128,
+        msg: Binary,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let contract = ctx.deps.api.addr_validate(&contract)?;
+
+        let hook_msg =
+            self.transfer_tokens(ctx.deps.storage, &ctx.info.sender, &contract, amount)?;
+
+        let receive_msg = Cw20ReceiveMsg {
+            sender: ctx.info.sender.to_string(),
+            amount,
+            msg,
+        }
+        .into_cosmos_msg(contract.clone())?;
+
+        let response = Response::new()
+            .add_event(TransferEvent {
+                ty: "send",
+                sender: ctx.info.sender.as_str(),
+                recipient: contract.as_str(),
+                amount: &amount.to_string(),
+            })
+            .add_message(hook_msg)
+            .add_message(receive_msg);
+
+        Ok(response)
+    }
+
+    #[sv::msg(exec)]
+    pub fn increase_allowance(
+        &self,
+        ctx: ExecCtx,
+        spender: String,
+        amount: Uint128,
+        expires: Option<Expiration>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let spender = ctx.deps.api.addr_validate(&spender)?;
+        ensure!(
+            spender != ctx.info.sender,
+            CommonError::InvalidInput("cannot set allowance to own account".to_string())
+        );
+
+        let block = ctx.env.block;
+        let allowance = self.allowances.update(
+            ctx.deps.storage,
+            (ctx.info.sender.clone(), spender.clone()),
+            |allowance| -> Result<_, ContractError> {
+                let mut allowance = allowance.unwrap_or_default();
+                if let Some(expires) = expires {
+                    ensure!(
+                        !expires.is_expired(&block),
+                        CommonError::InvalidInput("expiration is in the past".to_string())
+                    );
+                    allowance.expires = expires;
+                }
+                allowance.allowance = allowance.allowance.checked_add(amount)?;
+                Ok(allowance)
+            },
+        )?;
+
+        let response = Response::new().add_event(AllowanceEvent {
+            ty: "increase-allowance",
+            owner: ctx.info.sender.as_str(),
+            spender: spender.as_str(),
+            allowance: &allowance.allowance.to_string(),
+        });
+
+        Ok(response)
+    }
+
+    #[sv::msg(exec)]
+    pub fn decrease_allowance(
+        &self,
+        ctx: ExecCtx,
+        spender: String,
+        amount: Uint128,
+        expires: Option<Expiration>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let spender = ctx.deps.api.addr_validate(&spender)?;
+        let key = (ctx.info.sender.clone(), spender.clone());
+
+        let mut allowance = self
+            .allowances
+            .may_load(ctx.deps.storage, key.clone())?
+            .unwrap_or_default();
+
+        allowance.allowance = allowance.allowance.saturating_sub(amount);
+        if let Some(expires) = expires {
+            ensure!(
+                !expires.is_expired(&ctx.env.block),
+                CommonError::InvalidInput("expiration is in the past".to_string())
+            );
+            allowance.expires = expires;
+        }
+
+        if allowance.allowance.is_zero() {
+            self.allowances.remove(ctx.deps.storage, key);
+        } else {
+            self.allowances.save(ctx.deps.storage, key, &allowance)?;
+        }
+
+        let response = Response::new().add_event(AllowanceEvent {
+            ty: "decrease-allowance",
+            owner: ctx.info.sender.as_str(),
+            spender: spender.as_str(),
+            allowance: &allowance.allowance.to_string(),
+        });
+
+        Ok(response)
+    }
+
+    #[sv::msg(exec)]
+    pub fn transfer_from(
+        &self,
+        ctx: ExecCtx,
+        owner: String,
+        recipient: String,
+        amount: Uint128,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let owner = ctx.deps.api.addr_validate(&owner)?;
+        let recipient = ctx.deps.api.addr_validate(&recipient)?;
+
+        self.deduct_allowance(
+            ctx.deps.storage,
+            &ctx.env.block,
+            &owner,
+            &ctx.info.sender,
+            amount,
+        )?;
+
+        let hook_msg = self.transfer_tokens(ctx.deps.storage, &owner, &recipient, amount)?;
+
+        let response = Response::new()
+            .add_event(TransferEvent {
+                ty: "transfer-from",
+                sender: owner.as_str(),
+                recipient: recipient.as_str(),
+                amount: &amount.to_string(),
+            })
+            .add_message(hook_msg);
+
+        Ok(response)
+    }
+
+    #[sv::msg(exec)]
+    pub fn send_from(
+        &self,
+        ctx: ExecCtx,
+        owner: String,
+        contract: String,
+        amount: Uint128,
+        msg: Binary,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let owner = ctx.deps.api.addr_validate(&owner)?;
+        let contract = ctx.deps.api.addr_validate(&contract)?;
+
+        self.deduct_allowance(
+            ctx.deps.storage,
+            &ctx.env.block,
+            &owner,
+            &ctx.info.sender,
+            amount,
+        )?;
+
+        let hook_msg = self.transfer_tokens(ctx.deps.storage, &owner, &contract, amount)?;
+
+        let receive_msg = Cw20ReceiveMsg {
+            sender: ctx.info.sender.to_string(),
+            amount,
+            msg,
+        }
+        .into_cosmos_msg(contract.clone())?;
+
+        let response = Response::new()
+            .add_event(TransferEvent {
+                ty: "send-from",
+                sender: owner.as_str(),
+                recipient: contract.as_str(),
+                amount: &amount.to_string(),
+            })
+            .add_message(hook_msg)
+            .add_message(receive_msg);
+
+        Ok(response)
+    }
+
+    #[sv::msg(query)]
+    pub fn balance(&self, ctx: QueryCtx, address: String) -> StdResult<BalanceResponse> {
+        let address = ctx.deps.api.addr_validate(&address)?;
+
+        let balance = self
+            .balances
+            .may_load(ctx.deps.storage, address)?
+            .unwrap_or_default();
+
+        Ok(BalanceResponse { balance })
+    }
+
+    #[sv::msg(query)]
+    pub fn token_info(&self, ctx: QueryCtx) -> StdResult<TokenInfoResponse> {
+        let token_info = self.token_info.load(ctx.deps.storage)?;
+
+        Ok(TokenInfoResponse {
+            name: token_info.name,
+            symbol: token_info.symbol,
+            decimals: token_info.decimals,
+            total_supply: token_info.total_supply,
+        })
+    }
+
+    #[sv::msg(query)]
+    pub fn minter(&self, ctx: QueryCtx) -> StdResult<Option<MinterResponse>> {
+        let token_info = self.token_info.load(ctx.deps.storage)?;
+
+        Ok(Some(MinterResponse {
+            minter: token_info.minter.to_string(),
+            cap: None,
+        }))
+    }
+
+    #[sv::msg(query)]
+    pub fn allowance(
+        &self,
+        ctx: QueryCtx,
+        owner: String,
+        spender: String,
+    ) -> StdResult<AllowanceResponse> {
+        let owner = ctx.deps.api.addr_validate(&owner)?;
+        let spender = ctx.deps.api.addr_validate(&spender)?;
+
+        let allowance = self
+            .allowances
+            .may_load(ctx.deps.storage, (owner, spender))?
+            .unwrap_or_default();
+
+        Ok(allowance)
+    }
+
+    /// Moves tokens between accounts and returns the hook informing the vault of the transfer
+    pub fn transfer_tokens(
+        &self,
+        storage: &mut dyn Storage,
+        sender: &Addr,
+        recipient: &Addr,
+        amount: Uint128,
+    ) -> Result<WasmMsg, ContractError> {
+        ensure!(
+            !amount.is_zero(),
+            CommonError::InvalidInput("amount must be greater than zero".to_string())
+        );
+
+        let token_info = self.token_info.load(storage)?;
+
+        self.sub_balance(storage, sender, amount)?;
+        self.add_balance(storage, recipient, amount)?;
+
+        let hook_msg = DerivativeHookMsg::DerivativeTransfer {
+            sender: sender.to_string(),
+            recipient: recipient.to_string(),
+            amount,
+        }
+        .into_wasm_msg(token_info.minter)?;
+
+        Ok(hook_msg)
+    }
+
+    fn add_balance(
+        &self,
+        storage: &mut dyn Storage,
+        address: &Addr,
+        amount: Uint128,
+    ) -> Result<(), ContractError> {
+        self.balances.update(
+            storage,
+            address.clone(),
+            |balance| -> Result<_, ContractError> {
+                Ok(balance.unwrap_or_default().checked_add(amount)?)
+            },
+        )?;
+        Ok(())
+    }
+
+    fn sub_balance(
+        &self,
+        storage: &mut dyn Storage,
+        address: &Addr,
+        amount: Uint128,
+    ) -> Result<(), ContractError> {
+        self.balances.update(
+            storage,
+            address.clone(),
+            |balance| -> Result<_, ContractError> {
+                let balance = balance.unwrap_or_default();
+                ensure!(
+                    balance >= amount,
+                    CommonError::InsufficientFunds("insufficient balance".to_string())
+                );
+                Ok(balance.checked_sub(amount)?)
+            },
+        )?;
+        Ok(())
+    }
+
+    fn deduct_allowance(
+        &self,
+        storage: &mut dyn Storage,
+        block: &BlockInfo,
+        owner: &Addr,
+        spender: &Addr,
+        amount: Uint128,
+    ) -> Result<(), ContractError> {
+        self.allowances.update(
+            storage,
+            (owner.clone(), spender.clone()),
+            |allowance| -> Result<_, ContractError> {
+                let mut allowance = allowance.ok_or_else(|| {
+                    CommonError::Unauthorized("no allowance for spender".to_string())
+                })?;
+                ensure!(
+                    !allowance.expires.is_expired(block),
+                    CommonError::Unauthorized("allowance is expired".to_string())
+                );
+                ensure!(
+                    allowance.allowance >= amount,
+                    CommonError::InsufficientFunds("insufficient allowance".to_string())
+                );
+                allowance.allowance = allowance.allowance.checked_sub(amount)?;
+                Ok(allowance)
+            },
+        )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,36 @@
+use cosmwasm_std::{attr, Event};
+use std::vec;
+
+pub struct TransferEvent<'a> {
+    pub ty: &'a str,
+    pub sender: &'a str,
+    pub recipient: &'a str,
+    pub amount: &'a str,
+}
+
+impl<'a> From<TransferEvent<'a>> for Event {
+    fn from(te: TransferEvent) -> Self {
+        Event::new(te.ty.to_string()).add_attributes(vec![
+            attr("sender", te.sender.to_string()),
+            attr("recipient", te.recipient.to_string()),
+            attr("amount", te.amount.to_string()),
+        ])
+    }
+}
+
+pub struct AllowanceEvent<'a> {
+    pub ty: &'a str,
+    pub owner: &'a str,
+    pub spender: &'a str,
+    pub allowance: &'a str,
+}
+
+impl<'a> From<AllowanceEvent<'a>> for Event {
+    fn from(ae: AllowanceEvent) -> Self {
+        Event::new(ae.ty.to_string()).add_attributes(vec![
+            attr("owner", ae.owner.to_string()),
+            attr("spender", ae.spender.to_string()),
+            attr("allowance", ae.allowance.to_string()),
+        ])
+    }
+}
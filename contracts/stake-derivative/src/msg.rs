@@ -0,0 +1,22 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{to_json_binary, StdResult, Uint128, WasmMsg};
+
+/// Messages sent to the vault when staking power changes hands outside of the vault
+#[cw_serde]
+pub enum DerivativeHookMsg {
+    DerivativeTransfer {
+        sender: String,
+        recipient: String,
+        amount: Uint128,
+    },
+}
+
+impl DerivativeHookMsg {
+    pub fn into_wasm_msg(self, vault: impl Into<String>) -> StdResult<WasmMsg> {
+        Ok(WasmMsg::Execute {
+            contract_addr: vault.into(),
+            msg: to_json_binary(&self)?,
+            funds: vec![],
+        })
+    }
+}
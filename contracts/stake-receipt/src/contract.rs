@@ -0,0 +1,754 @@
+use cosmwasm_std::{
+    ensure, ensure_eq, Addr, Binary, BlockInfo, Order, Response, StdResult, Storage, WasmMsg,
+};
+use cw2::set_contract_version;
+use cw_storage_plus::{Bound, IndexedMap, Item, Map, MultiIndex};
+use cw_utils::{nonpayable, Expiration};
+use sylvia::{
+    contract,
+    types::{ExecCtx, InstantiateCtx, QueryCtx},
+};
+use uju_cw2_common::error::CommonError;
+
+use crate::{
+    error::ContractError,
+    events::{ApprovalEvent, ReceiptEvent, TransferEvent},
+    msg::{
+        AllNftInfoResponse, ContractInfoResponse, Cw721ReceiveMsg, MinterResponse, NftInfoResponse,
+        NumTokensResponse, OwnerOfResponse, ReceiptHookMsg, TokensResponse,
+    },
+    state::{Approval, ContractInfo, Receipt, ReceiptIndices, StakedNftRef},
+};
+
+pub const CONTRACT_NAME: &str = env!("CARGO_PKG_NAME");
+pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+const DEFAULT_QUERY_LIMIT: u32 = 10;
+const MAX_QUERY_LIMIT: u32 = 100;
+
+pub struct StakeReceiptContract {
+    pub contract_info: Item<ContractInfo>,
+    pub num_tokens: Item<u64>,
+    pub receipts: IndexedMap<String, Receipt, ReceiptIndices>,
+    pub operators: Map<(Addr, Addr), Expiration>,
+}
+
+impl Default for StakeReceiptContract {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(not(feature = "library"))]
+use sylvia::entry_points;
+
+#[cfg_attr(not(feature = "library"), entry_points)]
+#[contract]
+#[sv::error(ContractError)]
+impl StakeReceiptContract {
+    pub const fn new() -> Self {
+        let indexes = ReceiptIndices {
+            owner: MultiIndex::new(|_pk: &[u8], r: &Receipt| r.owner.clone(), "r", "r_o"),
+        };
+
+        Self {
+            contract_info: Item::new("C"),
+            num_tokens: Item::new("N"),
+            receipts: IndexedMap::new("r", indexes),
+            operators: Map::new("O"),
+        }
+    }
+
+    #[sv::msg(instantiate)]
+    pub fn instantiate(
+        &self,
+        ctx: InstantiateCtx,
+        name: String,
+        symbol: String,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        set_contract_version(ctx.deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+        // The instantiating vault is the only account able to mint receipts
+        self.contract_info.save(
+            ctx.deps.storage,
+            &ContractInfo {
+                name,
+                symbol,
+                minter: ctx.info.sender,
+            },
+        )?;
+        self.num_tokens.save(ctx.deps.storage, &0)?;
+
+        Ok(Response::new())
+    }
+
+    #[sv::msg(exec)]
+    pub fn mint(
+        &self,
+        ctx: ExecCtx,
+        token_id: String,
+        owner: String,
+        staked_nft: StakedNftRef,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let contract_info = self.contract_info.load(ctx.deps.storage)?;
+        ensure_eq!(
+            contract_info.minter,
+            ctx.info.sender,
+            CommonError::Unauthorized("sender is not the minter".to_string())
+        );
+
+        ensure!(
+            !self.receipts.has(ctx.deps.storage, token_id.clone()),
+            ContractError::ReceiptAlreadyExists
+        );
+
+        let owner = ctx.deps.api.addr_validate(&owner)?;
+
+        self.receipts.save(
+            ctx.deps.storage,
+            token_id.clone(),
+            &Receipt {
+                owner: owner.clone(),
+                approvals: vec![],
+                staked_nft,
+            },
+        )?;
+        self.num_tokens
+            .update(ctx.deps.storage, |count| -> StdResult<_> { Ok(count + 1) })?;
+
+        let response = Response::new().add_event(ReceiptEvent {
+            ty: "mint-receipt",
+            token_id: &token_id,
+            owner: owner.as_str(),
+        });
+
+        Ok(response)
+    }
+
+    #[sv::msg(exec)]
+    pub fn burn(&self, ctx: ExecCtx, token_id: String) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let contract_info = self.contract_info.load(ctx.deps.storage)?;
+        let receipt = self.load_receipt(ctx.deps.storage, &token_id)?;
+
+        let burned_by_minter = ctx.info.sender == contract_info.minter;
+        if !burned_by_minter {
+            self.check_can_send(ctx.deps.storage, &ctx.env.block, &ctx.info.sender, &receipt)?;
+        }
+
+        self.receipts.remove(ctx.deps.storage, token_id.clone())?;
+        self.num_tokens
+            .update(ctx.deps.storage, |count| -> StdResult<_> { Ok(count - 1) })?;
+
+        let mut response = Response::new().add_event(ReceiptEvent {
+            ty: "burn-receipt",
+            token_id: &token_id,
+            owner: receipt.owner.as_str(),
+        });
+
+        // Burning a receipt outside of the vault unstakes the underlying NFT
+        if !burned_by_minter {
+            response = response.add_message(
+                ReceiptHookMsg::ReceiptBurn {
+                    nft: receipt.staked_nft,
+                    owner: receipt.owner.to_string(),
+                }
+                .into_wasm_msg(contract_info.minter)?,
+            );
+        }
+
+        Ok(response)
+    }
+
+    #[sv::msg(exec)]
+    pub fn transfer_nft(
+        &self,
+        ctx: ExecCtx,
+        recipient: String,
+        token_id: String,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let recipient = ctx.deps.api.addr_validate(&recipient)?;
+
+        let hook_msg = self.transfer_receipt(
+            ctx.deps.storage,
+            &ctx.env.block,
+            &ctx.info.sender,
+            &recipient,
+            &token_id,
+        )?;
+
+        let response = Response::new()
+            .add_event(TransferEvent {
+                ty: "transfer-receipt",
+                sender: ctx.info.sender.as_str(),
+                recipient: recipient.as_str(),
+                token_id: &token_id,
+            })
+            .add_message(hook_msg);
+
+        Ok(response)
+    }
+
+    #[sv::msg(exec)]
+    pub fn send_nft(
+        &self,
+        ctx: ExecCtx,
+        contract: String,
+        token_id: String,
+        msg: Binary,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let contract = ctx.deps.api.addr_validate(&contract)?;
+
+        let hook_msg = self.transfer_receipt(
+            ctx.deps.storage,
+            &ctx.env.block,
+            &ctx.info.sender,
+            &contract,
+            &token_id,
+        )?;
+
+        let receive_msg = Cw721ReceiveMsg {
+            sender: ctx.info.sender.to_string(),
+            token_id: token_id.clone(),
+            msg,
+        }
+        .into_wasm_msg(contract.clone())?;
+
+        let response = Response::new()
+            .add_event(TransferEvent {
+                ty: "send-receipt",
+                sender: ctx.info.sender.as_str(),
+                recipient: contract.as_str(),
+                token_id: &token_id,
+            })
+            .add_message(hook_msg)
+            .add_message(receive_msg);
+
+        Ok(response)
+    }
+
+    #[sv::msg(exec)]
+    pub fn approve(
+        &self,
+        ctx: ExecCtx,
+        spender: String,
+        token_id: String,
+        expires: Option<Expiration>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let spender = ctx.deps.api.addr_validate(&spender)?;
+        let expires = expires.unwrap_or_default();
+        ensure!(
+            !expires.is_expired(&ctx.env.block),
+            CommonError::InvalidInput("expiration is in the past".to_string())
+        );
+
+        let mut receipt = self.load_receipt(ctx.deps.storage, &token_id)?;
+        self.check_can_approve(ctx.deps.storage, &ctx.env.block, &ctx.info.sender, &receipt)?;
+
+        receipt
+            .approvals
+            .retain(|approval| approval.spender != spender);
+        receipt.approvals.push(Approval {
+            spender: spender.clone(),
+            expires,
+        });
+        self.receipts.save(ctx.deps.storage, token_id, &receipt)?;
+
+        let response = Response::new().add_event(ApprovalEvent {
+            ty: "approve",
+            sender: ctx.info.sender.as_str(),
+            spender: spender.as_str(),
+        });
+
+        Ok(response)
+    }
+
+    #[sv::msg(exec)]
+    pub fn revoke(
+        &self,
+        ctx: ExecCtx,
+        spender: String,
+        token_id: String,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let spender = ctx.deps.api.addr_validate(&spender)?;
+
+        let mut receipt = self.load_receipt(ctx.deps.storage, &token_id)?;
+        self.check_can_approve(ctx.deps.storage, &ctx.env.block, &ctx.info.sender, &receipt)?;
+
+        receipt
+            .approvals
+            .retain(|approval| approval.spender != spender);
+        self.receipts.save(ctx.deps.storage, token_id, &receipt)?;
+
+        let response = Response::new().add_event(ApprovalEvent {
+            ty: "revoke",
+            sender: ctx.info.sender.as_str(),
+            spender: spender.as_str(),
+        });
+
+        Ok(response)
+    }
+
+    #[sv::msg(exec)]
+    pub fn approve_all(
+        &self,
+        ctx: ExecCtx,
+        operator: String,
+        expires: Option<Expiration>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let operator = ctx.deps.api.addr_validate(&operator)?;
+        let expires = expires.unwrap_or_default();
+        ensure!(
+            !expires.is_expired(&ctx.env.block),
+            CommonError::InvalidInput("expiration is in the past".to_string())
+        );
+
+        self.operators.save(
+            ctx.deps.storage,
+            (ctx.info.sender.clone(), operator.clone()),
+            &expires,
+        )?;
+
+        let response = Response::new().add_event(ApprovalEvent {
+            ty: "approve-all",
+            sender: ctx.info.sender.as_str(),
+            spender: operator.as_str(),
+        });
+
+        Ok(response)
+    }
+
+    #[sv::msg(exec)]
+    pub fn revoke_all(&self, ctx: ExecCtx, operator: String) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let operator = ctx.deps.api.addr_validate(&operator)?;
+
+        self.operators.remove(
+            ctx.deps.storage,
+            (ctx.info.sender.clone(), operator.clone()),
+        );
+
+        let response = Response::new().add_event(ApprovalEvent {
+            ty: "revoke-all",
+            sender: ctx.info.sender.as_str(),
+            spender: operator.as_str(),
+        });
+
+        Ok(response)
+    }
+
+    #[sv::msg(query)]
+    pub fn contract_info(&self, ctx: QueryCtx) -> StdResult<ContractInfoResponse> {
+        let contract_info = self.contract_info.load(ctx.deps.storage)?;
+
+        Ok(ContractInfoResponse {
+            name: contract_info.name,
+            symbol: contract_info.symbol,
+        })
+    }
+
+    #[sv::msg(query)]
+    pub fn minter(&self, ctx: QueryCtx) -> StdResult<MinterResponse> {
+        let contract_info = self.contract_info.load(ctx.deps.storage)?;
+
+        Ok(MinterResponse {
+            minter: Some(contract_info.minter.to_string()),
+        })
+    }
+
+    #[sv::msg(query)]
+    pub fn num_tokens(&self, ctx: QueryCtx) -> StdResult<NumTokensResponse> {
+        let count = self.num_tokens.load(ctx.deps.storage)?;
+
+        Ok(NumTokensResponse { count })
+    }
+
+    #[sv::msg(query)]
+    pub fn owner_of(
+        &self,
+        ctx: QueryCtx,
+        token_id: String,
+        include_expired: Option<bool>,
+    ) -> StdResult<OwnerOfResponse> {
+        let receipt = self.receipts.load(ctx.deps.storage, token_id)?;
+
+        Ok(owner_of_response(
+            receipt,
+            &ctx.env.block,
+            include_expired.unwrap_or(false),
+        ))
+    }
+
+    #[sv::msg(query)]
+    pub fn nft_info(&self, ctx: QueryCtx, token_id: String) -> StdResult<NftInfoResponse> {
+        let receipt = self.receipts.load(ctx.deps.storage, token_id)?;
+
+        Ok(NftInfoResponse {
+            token_uri: None,
+            extension: receipt.staked_nft,
+        })
+    }
+
+    #[sv::msg(query)]
+    pub fn all_nft_info(
+        &self,
+        ctx: QueryCtx,
+        token_id: String,
+        include_expired: Option<bool>,
+    ) -> StdResult<AllNftInfoResponse> {
+        let receipt = self.receipts.load(ctx.deps.storage, token_id)?;
+
+        Ok(AllNftInfoResponse {
+            info: NftInfoResponse {
+                token_uri: None,
+                extension: receipt.staked_nft.clone(),
+            },
+            access: owner_of_response(receipt, &ctx.env.block, include_expired.unwrap_or(false)),
+        })
+    }
+
+    #[sv::msg(query)]
+    pub fn tokens(
+        &self,
+        ctx: QueryCtx,
+        owner: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    ) -> StdResult<TokensResponse> {
+        let owner = ctx.deps.api.addr_validate(&owner)?;
+        let limit = limit.unwrap_or(DEFAULT_QUERY_LIMIT).min(MAX_QUERY_LIMIT) as usize;
+
+        let tokens = self
+            .receipts
+            .idx
+            .owner
+            .prefix(owner)
+            .keys(
+                ctx.deps.storage,
+                start_after.map(Bound::exclusive),
+                None,
+                Order::Ascending,
+            )
+            .take(limit)
+            .collect::<StdResult<Vec<String>>>()?;
+
+        Ok(TokensResponse { tokens })
+    }
+
+    #[sv::msg(query)]
+    pub fn all_tokens(
+        &self,
+        ctx: QueryCtx,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    ) -> StdResult<TokensResponse> {
+        let limit = limit.unwrap_or(DEFAULT_QUERY_LIMIT).min(MAX_QUERY_LIMIT) as usize;
+
+        let tokens = self
+            .receipts
+            .keys(
+                ctx.deps.storage,
+                start_after.map(Bound::exclusive),
+                None,
+                Order::Ascending,
+            )
+            .take(limit)
+            .collect::<StdResult<Vec<String>>>()?;
+
+        Ok(TokensResponse { tokens })
+    }
+
+    pub fn load_receipt(
+        &self,
+        storage: &dyn Storage,
+        token_id: &str,
+    ) -> Result<Receipt, ContractError> {
+        self.receipts
+            .may_load(storage, token_id.to_string())?
+            .ok_or(ContractError::ReceiptNotFound)
+    }
+
+    /// Moves the receipt to the recipient and returns the hook informing the vault of the new owner
+    pub fn transfer_receipt(
+        &self,
+        storage: &mut dyn Storage,
+        block: &BlockInfo,
+        sender: &Addr,
+        recipient: &Addr,
+        token_id: &str,
+    ) -> Result<WasmMsg, ContractError> {
+        let contract_info = self.contract_info.load(storage)?;
+        let mut receipt = self.load_receipt(storage, token_id)?;
+
+        self.check_can_send(storage, block, sender, &receipt)?;
+
+        let previous_owner = receipt.owner.clone();
+
+        receipt.owner = recipient.clone();
+        receipt.approvals = vec![];
+        self.receipts
+            .save(storage, token_id.to_string(), &receipt)?;
+
+        let hook_msg = ReceiptHookMsg::ReceiptTransfer {
+            nft: receipt.staked_nft,
+            previous_owner: previous_owner.to_string(),
+            recipient: recipient.to_string(),
+        }
+        .into_wasm_msg(contract_info.minter)?;
+
+        Ok(hook_msg)
+    }
+
+    fn check_can_approve(
+        &self,
+        storage: &dyn Storage,
+        block: &BlockInfo,
+        sender: &Addr,
+        receipt: &Receipt,
+    ) -> Result<(), ContractError> {
+        if receipt.owner == sender || self.is_operator(storage, block, &receipt.owner, sender)? {
+            return Ok(());
+        }
+
+        Err(CommonError::Unauthorized("sender cannot approve receipt".to_string()).into())
+    }
+
+    fn check_can_send(
+        &self,
+        storage: &dyn Storage,
+        block: &BlockInfo,
+        sender: &Addr,
+        receipt: &Receipt,
+    ) -> Result<(), ContractError> {
+        if receipt.owner == sender
+            || receipt
+                .approvals
+                .iter()
+                .any(|approval| approval.spender == sender && !approval.is_expired(block))
+            || self.is_operator(storage, block, &receipt.owner, sender)?
+        {
+            return Ok(());
+        }
+
+        Err(CommonError::Unauthorized("sender cannot send receipt".to_string()).into())
+    }
+
+    fn is_operator(
+        &self,
+        storage: &dyn Storage,
+        block: &BlockInfo,
+        owner: &Addr,
+        operator: &Addr,
+    ) -> StdResult<bool> {
+        let expires = self
+            .operators
+            .may_load(storage, (owner.clone(), operator.clone()))?;
+
+        Ok(expires.is_some_and(|expires| !expires.is_expired(block)))
+    }
+}
+
+fn owner_of_response(
+    receipt: Receipt,
+    block: &BlockInfo,
+    include_expired: bool,
+) -> OwnerOfResponse {
+    OwnerOfResponse {
+        owner: receipt.owner.to_string(),
+        approvals: receipt
+            .approvals
+            .into_iter()
+            .filter(|approval| include_expired || !approval.is_expired(block))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        contract::sv::mt::{CodeId, StakeReceiptContractProxy},
+        error::ContractError,
+        state::StakedNftRef,
+    };
+
+    use cosmwasm_std::{Addr, Binary, Empty, Response, StdResult};
+    use cw_utils::Expiration;
+    use sylvia::{
+        cw_multi_test::{App as CwApp, ContractWrapper, Executor, IntoAddr},
+        multitest::App,
+    };
+    use uju_cw2_common::error::CommonError;
+
+    /// Accepts any message, standing in for the vault receiving the receipt hooks
+    fn mock_vault(app: &App<CwApp>, owner: &Addr) -> Addr {
+        let code_id = app.app_mut().store_code(Box::new(ContractWrapper::new(
+            |_, _, _, _: Empty| -> StdResult<Response> { Ok(Response::new()) },
+            |_, _, _, _: Empty| -> StdResult<Response> { Ok(Response::new()) },
+            |_, _, _: Empty| -> StdResult<Binary> { Ok(Default::default()) },
+        )));
+
+        app.app_mut()
+            .instantiate_contract(code_id, owner.clone(), &Empty {}, &[], "vault", None)
+            .unwrap()
+    }
+
+    fn staked_nft(collection: &Addr, token_id: &str) -> StakedNftRef {
+        StakedNftRef {
+            collection: collection.clone(),
+            token_id: token_id.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_receipts() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+        let user1 = "user1".into_addr();
+        let user2 = "user2".into_addr();
+        let collection = "collection".into_addr();
+
+        let vault = mock_vault(&app, &owner);
+        let receipts = CodeId::store_code(&app)
+            .instantiate("Receipts".to_string(), "RCPT".to_string())
+            .call(&vault)
+            .unwrap();
+
+        // The instantiating vault is the minter
+        assert_eq!(receipts.minter().unwrap().minter, Some(vault.to_string()));
+
+        let err = receipts
+            .mint(
+                "1".to_string(),
+                user1.to_string(),
+                staked_nft(&collection, "1"),
+            )
+            .call(&user1)
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::CommonError(CommonError::Unauthorized(_))
+        ));
+
+        for token_id in ["1", "2"] {
+            receipts
+                .mint(
+                    token_id.to_string(),
+                    user1.to_string(),
+                    staked_nft(&collection, token_id),
+                )
+                .call(&vault)
+                .unwrap();
+        }
+
+        let err = receipts
+            .mint(
+                "1".to_string(),
+                user1.to_string(),
+                staked_nft(&collection, "1"),
+            )
+            .call(&vault)
+            .unwrap_err();
+        assert_eq!(err, ContractError::ReceiptAlreadyExists);
+
+        assert_eq!(receipts.num_tokens().unwrap().count, 2);
+        assert_eq!(
+            receipts.nft_info("1".to_string()).unwrap().extension,
+            staked_nft(&collection, "1")
+        );
+
+        // Only the owner, approved spenders and operators move receipts
+        assert!(receipts
+            .transfer_nft(user2.to_string(), "1".to_string())
+            .call(&user2)
+            .is_err());
+        assert!(receipts
+            .approve(user2.to_string(), "1".to_string(), None)
+            .call(&user2)
+            .is_err());
+
+        receipts
+            .approve(user2.to_string(), "1".to_string(), None)
+            .call(&user1)
+            .unwrap();
+        assert_eq!(
+            receipts
+                .owner_of("1".to_string(), None)
+                .unwrap()
+                .approvals
+                .len(),
+            1
+        );
+
+        // The transfer reports the new owner to the vault and clears the approvals
+        let res = receipts
+            .transfer_nft(user2.to_string(), "1".to_string())
+            .call(&user2)
+            .unwrap();
+        assert!(res
+            .events
+            .iter()
+            .any(|event| event.ty == "execute" && event.attributes[0].value == vault.as_str()));
+
+        let owner_of = receipts.owner_of("1".to_string(), None).unwrap();
+        assert_eq!(owner_of.owner, user2.to_string());
+        assert!(owner_of.approvals.is_empty());
+        assert_eq!(
+            receipts
+                .tokens(user2.to_string(), None, None)
+                .unwrap()
+                .tokens,
+            vec!["1".to_string()]
+        );
+
+        // Operators act on every receipt of the owner until they expire
+        assert!(receipts
+            .approve_all(user2.to_string(), Some(Expiration::AtHeight(1)))
+            .call(&user1)
+            .is_err());
+        receipts
+            .approve_all(user2.to_string(), None)
+            .call(&user1)
+            .unwrap();
+        receipts
+            .transfer_nft(user2.to_string(), "2".to_string())
+            .call(&user2)
+            .unwrap();
+        receipts.revoke_all(user2.to_string()).call(&user1).unwrap();
+
+        // Burning by the holder unstakes through the vault, burning by the vault does not
+        let res = receipts.burn("1".to_string()).call(&user2).unwrap();
+        assert!(res
+            .events
+            .iter()
+            .any(|event| event.ty == "execute" && event.attributes[0].value == vault.as_str()));
+
+        let res = receipts.burn("2".to_string()).call(&vault).unwrap();
+        assert!(!res
+            .events
+            .iter()
+            .any(|event| event.ty == "execute" && event.attributes[0].value == vault.as_str()));
+
+        assert_eq!(receipts.num_tokens().unwrap().count, 0);
+        assert_eq!(
+            receipts.burn("1".to_string()).call(&user2).unwrap_err(),
+            ContractError::ReceiptNotFound
+        );
+    }
+}
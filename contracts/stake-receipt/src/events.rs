@@ -0,0 +1,49 @@
+use cosmwasm_std::{attr, Event};
+use std::vec;
+
+pub struct ReceiptEvent<'a> {
+    pub ty: &'a str,
+    pub token_id: &'a str,
+    pub owner: &'a str,
+}
+
+impl<'a> From<ReceiptEvent<'a>> for Event {
+    fn from(re: ReceiptEvent) -> Self {
+        Event::new(re.ty.to_string()).add_attributes(vec![
+            attr("token_id", re.token_id.to_string()),
+            attr("owner", re.owner.to_string()),
+        ])
+    }
+}
+
+pub struct TransferEvent<'a> {
+    pub ty: &'a str,
+    pub sender: &'a str,
+    pub recipient: &'a str,
+    pub token_id: &'a str,
+}
+
+impl<'a> From<TransferEvent<'a>> for Event {
+    fn from(te: TransferEvent) -> Self {
+        Event::new(te.ty.to_string()).add_attributes(vec![
+            attr("sender", te.sender.to_string()),
+            attr("recipient", te.recipient.to_string()),
+            attr("token_id", te.token_id.to_string()),
+        ])
+    }
+}
+
+pub struct ApprovalEvent<'a> {
+    pub ty: &'a str,
+    pub sender: &'a str,
+    pub spender: &'a str,
+}
+
+impl<'a> From<ApprovalEvent<'a>> for Event {
+    fn from(ae: ApprovalEvent) -> Self {
+        Event::new(ae.ty.to_string()).add_attributes(vec![
+            attr("sender", ae.sender.to_string()),
+            attr("spender", ae.spender.to_string()),
+        ])
+    }
+}
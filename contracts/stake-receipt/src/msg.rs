@@ -0,0 +1,90 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{to_json_binary, Binary, StdResult, WasmMsg};
+
+use crate::state::{Approval, StakedNftRef};
+
+/// Messages sent to the vault when a receipt changes hands outside of the vault
+#[cw_serde]
+pub enum ReceiptHookMsg {
+    ReceiptTransfer {
+        nft: StakedNftRef,
+        previous_owner: String,
+        recipient: String,
+    },
+    ReceiptBurn {
+        nft: StakedNftRef,
+        owner: String,
+    },
+}
+
+impl ReceiptHookMsg {
+    pub fn into_wasm_msg(self, vault: impl Into<String>) -> StdResult<WasmMsg> {
+        Ok(WasmMsg::Execute {
+            contract_addr: vault.into(),
+            msg: to_json_binary(&self)?,
+            funds: vec![],
+        })
+    }
+}
+
+/// Cw721ReceiveMsg should be de/serialized under `Receive()` variant in a ExecuteMsg
+#[cw_serde]
+pub struct Cw721ReceiveMsg {
+    pub sender: String,
+    pub token_id: String,
+    pub msg: Binary,
+}
+
+#[cw_serde]
+enum ReceiverExecuteMsg {
+    ReceiveNft(Cw721ReceiveMsg),
+}
+
+impl Cw721ReceiveMsg {
+    pub fn into_wasm_msg(self, contract_addr: impl Into<String>) -> StdResult<WasmMsg> {
+        Ok(WasmMsg::Execute {
+            contract_addr: contract_addr.into(),
+            msg: to_json_binary(&ReceiverExecuteMsg::ReceiveNft(self))?,
+            funds: vec![],
+        })
+    }
+}
+
+#[cw_serde]
+pub struct ContractInfoResponse {
+    pub name: String,
+    pub symbol: String,
+}
+
+#[cw_serde]
+pub struct MinterResponse {
+    pub minter: Option<String>,
+}
+
+#[cw_serde]
+pub struct NumTokensResponse {
+    pub count: u64,
+}
+
+#[cw_serde]
+pub struct OwnerOfResponse {
+    pub owner: String,
+    pub approvals: Vec<Approval>,
+}
+
+#[cw_serde]
+pub struct NftInfoResponse {
+    pub token_uri: Option<String>,
+    pub extension: StakedNftRef,
+}
+
+#[cw_serde]
+pub struct AllNftInfoResponse {
+    pub access: OwnerOfResponse,
+    pub info: NftInfoResponse,
+}
+
+#[cw_serde]
+pub struct TokensResponse {
+    pub tokens: Vec<String>,
+}
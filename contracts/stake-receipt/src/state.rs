@@ -0,0 +1,50 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{Addr, BlockInfo};
+use cw_storage_plus::{Index, IndexList, MultiIndex};
+use cw_utils::Expiration;
+
+#[cw_serde]
+pub struct ContractInfo {
+    pub name: String,
+    pub symbol: String,
+    pub minter: Addr,
+}
+
+/// The staked NFT a receipt represents
+#[cw_serde]
+pub struct StakedNftRef {
+    pub collection: Addr,
+    pub token_id: String,
+}
+
+#[cw_serde]
+pub struct Approval {
+    pub spender: Addr,
+    pub expires: Expiration,
+}
+
+impl Approval {
+    pub fn is_expired(&self, block: &BlockInfo) -> bool {
+        self.expires.is_expired(block)
+    }
+}
+
+#[cw_serde]
+pub struct Receipt {
+    pub owner: Addr,
+    pub approvals: Vec<Approval>,
+    pub staked_nft: StakedNftRef,
+}
+
+/// Defines indices for accessing receipts
+pub struct ReceiptIndices {
+    // Index Receipt by owner
+    pub owner: MultiIndex<'static, Addr, Receipt, String>,
+}
+
+impl IndexList<Receipt> for ReceiptIndices {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Receipt>> + '_> {
+        let v: Vec<&dyn Index<Receipt>> = vec![&self.owner];
+        Box::new(v.into_iter())
+    }
+}
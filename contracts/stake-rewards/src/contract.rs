@@ -1,31 +1,49 @@
 use cosmwasm_std::{
-    coin, ensure, ensure_eq, to_json_binary, Addr, BankMsg, CosmosMsg, Response, StdResult,
-    Timestamp, Uint128, Uint256, WasmMsg,
+    ensure, ensure_eq, from_json, Addr, Binary, Deps, DepsMut, Empty, Env, Order, QuerierWrapper,
+    Response, StdResult, Storage, Uint128, Uint256,
 };
 use cw2::set_contract_version;
-use cw20::{BalanceResponse, Cw20ExecuteMsg, Cw20QueryMsg};
 use cw_storage_plus::{Item, Map};
-use cw_utils::{must_pay, nonpayable};
+use cw_utils::{maybe_addr, must_pay, nonpayable, one_coin};
 use sylvia::{
     contract,
-    types::{ExecCtx, InstantiateCtx, QueryCtx},
+    types::{ExecCtx, InstantiateCtx, QueryCtx, SudoCtx},
 };
-use uju_cw2_common::error::CommonError;
+use uju_cw2_common::{address::address_or, error::CommonError};
 
 use crate::state::RewardAsset;
 use crate::{
+    curve::EmissionCurve,
     error::ContractError,
-    events::{ConfigEvent, UpdateRewardsEvent, UpdateUserRewardsEvent},
-    state::{Config, CumulativeRewards, UserReward},
+    events::{
+        ConfigEvent, DistributionEvent, FundEvent, ReclaimEvent, RewardTokenEvent, StatusEvent,
+        SweepDustEvent, UpdateRewardsEvent, UpdateUserRewardsEvent, VestRewardsEvent,
+        WithdrawUndistributedEvent, WithdrawVestedEvent,
+    },
+    msg::{ReceiveMsg, StakeQueryMsg},
+    state::{
+        scale_factor, validate_schedule, Accounting, AssetUserReward, BalanceSheet, Config,
+        CumulativeRewards, Distribution, EmissionSegment, Ledger, ScheduleResponse, Status,
+        UndistributedMode, UserReward, Vesting, VestingAccount, VestingResponse,
+        MAX_RECLAIM_ADDRESSES, MAX_REWARD_ASSETS,
+    },
 };
+use std::convert::TryInto;
 
 const CONTRACT_NAME: &str = env!("CARGO_PKG_NAME");
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 pub struct StakeExternalRewardsContract {
     pub config: Item<Config>,
-    pub rewards: Item<CumulativeRewards>,
-    pub user_rewards: Map<Addr, UserReward>,
+    /// Per reward asset, keyed by its denom or cw20 address
+    pub distributions: Map<String, Distribution>,
+    pub rewards: Map<String, CumulativeRewards>,
+    pub user_rewards: Map<(Addr, String), UserReward>,
+    pub status: Item<Status>,
+    pub ledgers: Map<String, Ledger>,
+    pub vesting_accounts: Map<(Addr, String), VestingAccount>,
+    /// Cw20 tokens the funder approved to be added as reward assets
+    pub reward_tokens: Map<Addr, Empty>,
 }
 
 #[cfg(not(feature = "library"))]
@@ -38,8 +56,13 @@ impl StakeExternalRewardsContract {
     pub const fn new() -> Self {
         Self {
             config: Item::new("C"),
-            rewards: Item::new("R"),
+            distributions: Map::new("D"),
+            rewards: Map::new("R"),
             user_rewards: Map::new("U"),
+            status: Item::new("S"),
+            ledgers: Map::new("L"),
+            vesting_accounts: Map::new("V"),
+            reward_tokens: Map::new("T"),
         }
     }
 
@@ -49,69 +72,99 @@ impl StakeExternalRewardsContract {
         ctx: InstantiateCtx,
         stake: String,
         reward_asset: RewardAsset,
-        period_start: Timestamp,
-        duration_sec: u64,
+        schedule: Vec<EmissionSegment>,
+        curve: Option<EmissionCurve>,
+        undistributed_mode: UndistributedMode,
+        funder: Option<String>,
+        grace_period_sec: u64,
+        accounting: Accounting,
+        vesting: Option<Vesting>,
+        cw20_amount: Option<Uint128>,
     ) -> Result<Response, ContractError> {
         set_contract_version(ctx.deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
-        ensure!(
-            period_start >= ctx.env.block.time,
-            CommonError::InvalidInput("period start must be at least current time".to_string())
-        );
-
         let stake = ctx.deps.api.addr_validate(&stake)?;
 
-        let fund_amount = match &reward_asset {
-            RewardAsset::Native(denom) => must_pay(&ctx.info, &denom)?,
-            RewardAsset::Cw20(cw20) => {
-                let balance_response: BalanceResponse = ctx.deps.querier.query_wasm_smart(
-                    cw20.to_string(),
-                    &Cw20QueryMsg::Balance {
-                        address: ctx.env.contract.address.to_string(),
-                    },
-                )?;
-                balance_response.balance
-            }
-        };
-        ensure!(
-            fund_amount > Uint128::zero(),
-            CommonError::InvalidInput("fund amount must be greater than zero".to_string())
-        );
-
-        let period_finish = Timestamp::from_seconds(ctx.env.block.time.seconds() + duration_sec);
-        let rewards_per_second = fund_amount.checked_div(Uint128::from(duration_sec))?;
-
-        ensure!(
-            rewards_per_second > Uint128::zero(),
-            CommonError::InvalidInput("reward rate must be greater than zero".to_string())
-        );
+        if let Some(vesting) = &vesting {
+            vesting.validate()?;
+        }
 
         let config = &Config {
             stake,
-            reward_asset,
-            period_start,
-            duration_sec,
-            period_finish,
-            rewards_per_second,
+            accounting,
+            vesting,
+            funder: address_or(&ctx.info.sender, maybe_addr(ctx.deps.api, funder)?.as_ref()),
+            undistributed_mode,
+            grace_period_sec,
         };
         self.config.save(ctx.deps.storage, &config)?;
 
-        self.rewards.save(
-            ctx.deps.storage,
-            &CumulativeRewards {
-                rewards_per_token: Uint256::zero(),
-                last_update: ctx.env.block.time,
-            },
+        self.status.save(ctx.deps.storage, &Status::default())?;
+
+        // Cw20 rewards are transferred beforehand and their amount passed along
+        let fund_amount = match &reward_asset {
+            RewardAsset::Native(denom) => must_pay(&ctx.info, denom)?,
+            RewardAsset::Cw20(_) => {
+                nonpayable(&ctx.info)?;
+                cw20_amount.ok_or_else(|| {
+                    CommonError::InvalidInput("cw20 amount is required".to_string())
+                })?
+            }
+        };
+
+        let distribution = self.create_distribution(
+            ctx.deps,
+            &ctx.env,
+            reward_asset,
+            schedule,
+            curve,
+            fund_amount,
         )?;
 
-        let response = Response::new().add_event(ConfigEvent {
-            ty: "set-config",
-            config: &config,
-        });
+        let response = Response::new()
+            .add_event(ConfigEvent {
+                ty: "set-config",
+                config: &config,
+            })
+            .add_event(DistributionEvent {
+                ty: "add-reward-asset",
+                distribution: &distribution,
+            });
 
         Ok(response)
     }
 
+    /// Adds another native reward asset with its own schedule, funded like on instantiation.
+    /// Cw20 reward assets are added by sending approved tokens with a `ReceiveMsg::AddRewardAsset`.
+    #[sv::msg(exec)]
+    pub fn add_reward_asset(
+        &self,
+        ctx: ExecCtx,
+        reward_asset: RewardAsset,
+        schedule: Vec<EmissionSegment>,
+        curve: Option<EmissionCurve>,
+    ) -> Result<Response, ContractError> {
+        let fund_amount = match &reward_asset {
+            RewardAsset::Native(denom) => must_pay(&ctx.info, denom)?,
+            RewardAsset::Cw20(_) => {
+                return Err(CommonError::InvalidInput(
+                    "cw20 reward assets are added by sending the tokens".to_string(),
+                )
+                .into())
+            }
+        };
+
+        self.add_distribution(
+            ctx.deps,
+            &ctx.env,
+            &ctx.info.sender,
+            reward_asset,
+            schedule,
+            curve,
+            fund_amount,
+        )
+    }
+
     #[sv::msg(exec)]
     pub fn stake_change(
         &self,
@@ -129,40 +182,242 @@ impl StakeExternalRewardsContract {
             CommonError::Unauthorized("sender is not the stake contract".to_string())
         );
 
-        let rewards = self.update_rewards(&mut ctx, &config, total_staked)?;
-
         let recipient_addr = ctx.deps.api.addr_validate(&recipient)?;
 
-        let current_user_reward = self
-            .user_rewards
-            .may_load(ctx.deps.storage, recipient_addr.clone())?
-            .unwrap_or_default();
-
-        let next_user_reward =
-            current_user_reward.get_next_user_reward(rewards.rewards_per_token, staked_amount)?;
+        let mut response = Response::new();
+        for mut distribution in self.load_distributions(ctx.deps.storage)? {
+            let (rewards, next_user_reward) = self.checkpoint_user_reward(
+                &mut ctx,
+                &config,
+                &mut distribution,
+                &recipient_addr,
+                staked_amount,
+                total_staked,
+            )?;
 
-        self.user_rewards
-            .save(ctx.deps.storage, recipient_addr, &next_user_reward)?;
+            self.user_rewards.save(
+                ctx.deps.storage,
+                (
+                    recipient_addr.clone(),
+                    distribution.reward_asset.to_string(),
+                ),
+                &next_user_reward,
+            )?;
 
-        let response = Response::new()
-            .add_event(UpdateRewardsEvent { rewards: &rewards })
-            .add_event(UpdateUserRewardsEvent {
-                user_reward: &next_user_reward,
-            });
+            response = response
+                .add_event(UpdateRewardsEvent {
+                    reward_asset: &distribution.reward_asset,
+                    rewards: &rewards,
+                })
+                .add_event(UpdateUserRewardsEvent {
+                    reward_asset: &distribution.reward_asset,
+                    user_reward: &next_user_reward,
+                });
+        }
 
         Ok(response)
     }
 
+    /// Pays the pending rewards of every reward asset, or locks them in the recipient's vesting
+    /// accounts when vesting. The stake contract reports the staked amounts, stakers claiming
+    /// directly in pull accounting have them queried.
     #[sv::msg(exec)]
     pub fn claim_rewards(
         &self,
         mut ctx: ExecCtx,
         recipient: String,
-        staked_amount: Uint128,
-        total_staked: Uint128,
+        staked_amount: Option<Uint128>,
+        total_staked: Option<Uint128>,
     ) -> Result<Response, ContractError> {
         nonpayable(&ctx.info)?;
 
+        // Claims stay open in emergency mode so users can exit
+        let status = self.status.load(ctx.deps.storage)?;
+        ensure!(
+            !status.paused || status.emergency_mode,
+            ContractError::Paused
+        );
+
+        let config = self.config.load(ctx.deps.storage)?;
+
+        let recipient_addr = ctx.deps.api.addr_validate(&recipient)?;
+
+        let (staked_amount, total_staked) = if ctx.info.sender == config.stake {
+            match (staked_amount, total_staked) {
+                (Some(staked_amount), Some(total_staked)) => (staked_amount, total_staked),
+                _ => {
+                    return Err(CommonError::InvalidInput(
+                        "stake contract must report the staked amounts".to_string(),
+                    )
+                    .into())
+                }
+            }
+        } else {
+            ensure!(
+                config.accounting == Accounting::Pull,
+                CommonError::Unauthorized("sender is not the stake contract".to_string())
+            );
+            ensure_eq!(
+                recipient_addr,
+                ctx.info.sender,
+                CommonError::Unauthorized("stakers can only claim their own rewards".to_string())
+            );
+            ensure!(
+                staked_amount.is_none() && total_staked.is_none(),
+                CommonError::InvalidInput(
+                    "staked amounts are queried from the stake contract".to_string()
+                )
+            );
+
+            (
+                self.query_staked_amount(ctx.deps.querier, &config, &recipient_addr)?,
+                self.query_total_staked(ctx.deps.querier, &config)?,
+            )
+        };
+
+        let mut response = Response::new();
+        for mut distribution in self.load_distributions(ctx.deps.storage)? {
+            let (rewards, mut next_user_reward) = self.checkpoint_user_reward(
+                &mut ctx,
+                &config,
+                &mut distribution,
+                &recipient_addr,
+                staked_amount,
+                total_staked,
+            )?;
+
+            let key = distribution.reward_asset.to_string();
+            if next_user_reward.pending_rewards > Uint128::zero() {
+                let claim_amount = next_user_reward.claim_rewards()?;
+                self.ledgers
+                    .update(ctx.deps.storage, key.clone(), |ledger| {
+                        let mut ledger = ledger.unwrap_or_default();
+                        ledger.claimed = ledger.claimed.checked_add(claim_amount)?;
+                        if config.vesting.is_some() {
+                            ledger.vesting = ledger.vesting.checked_add(claim_amount)?;
+                        }
+                        Ok::<_, ContractError>(ledger)
+                    })?;
+
+                response = match &config.vesting {
+                    Some(vesting) => {
+                        let now = ctx.env.block.time;
+                        let vesting_account = self.vesting_accounts.update(
+                            ctx.deps.storage,
+                            (recipient_addr.clone(), key.clone()),
+                            |vesting_account| {
+                                let mut vesting_account = vesting_account.unwrap_or_default();
+                                vesting_account.add(vesting, now, claim_amount)?;
+                                Ok::<_, ContractError>(vesting_account)
+                            },
+                        )?;
+
+                        response.add_event(VestRewardsEvent {
+                            reward_asset: &distribution.reward_asset,
+                            recipient: &recipient_addr,
+                            amount: claim_amount,
+                            vesting_account: &vesting_account,
+                        })
+                    }
+                    None => response.add_message(
+                        distribution
+                            .reward_asset
+                            .send_message(&recipient_addr, claim_amount)?,
+                    ),
+                };
+            }
+
+            self.user_rewards.save(
+                ctx.deps.storage,
+                (recipient_addr.clone(), key),
+                &next_user_reward,
+            )?;
+
+            response = response
+                .add_event(UpdateRewardsEvent {
+                    reward_asset: &distribution.reward_asset,
+                    rewards: &rewards,
+                })
+                .add_event(UpdateUserRewardsEvent {
+                    reward_asset: &distribution.reward_asset,
+                    user_reward: &next_user_reward,
+                });
+        }
+
+        Ok(response)
+    }
+
+    /// Sends the sender the rewards vested in every reward asset and not withdrawn yet
+    #[sv::msg(exec)]
+    pub fn withdraw_vested(&self, ctx: ExecCtx) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let status = self.status.load(ctx.deps.storage)?;
+        ensure!(
+            !status.paused || status.emergency_mode,
+            ContractError::Paused
+        );
+
+        let config = self.config.load(ctx.deps.storage)?;
+        let vesting = config
+            .vesting
+            .ok_or_else(|| CommonError::InvalidInput("claimed rewards do not vest".to_string()))?;
+
+        let mut response = Response::new();
+        for distribution in self.load_distributions(ctx.deps.storage)? {
+            let key = distribution.reward_asset.to_string();
+            let Some(mut vesting_account) = self
+                .vesting_accounts
+                .may_load(ctx.deps.storage, (ctx.info.sender.clone(), key.clone()))?
+            else {
+                continue;
+            };
+
+            let amount = vesting_account.withdraw(&vesting, ctx.env.block.time)?;
+            if amount.is_zero() {
+                continue;
+            }
+
+            self.vesting_accounts.save(
+                ctx.deps.storage,
+                (ctx.info.sender.clone(), key.clone()),
+                &vesting_account,
+            )?;
+            self.ledgers.update(ctx.deps.storage, key, |ledger| {
+                let mut ledger = ledger.unwrap_or_default();
+                ledger.vesting = ledger.vesting.checked_sub(amount)?;
+                Ok::<_, ContractError>(ledger)
+            })?;
+
+            response = response
+                .add_event(WithdrawVestedEvent {
+                    reward_asset: &distribution.reward_asset,
+                    recipient: &ctx.info.sender,
+                    amount,
+                })
+                .add_message(
+                    distribution
+                        .reward_asset
+                        .send_message(&ctx.info.sender, amount)?,
+                );
+        }
+        ensure!(
+            !response.messages.is_empty(),
+            CommonError::InvalidInput("nothing vested to withdraw".to_string())
+        );
+
+        Ok(response)
+    }
+
+    /// Adds fees collected by the stake contract to the rewards left in the current period
+    #[sv::msg(exec)]
+    pub fn top_up(
+        &self,
+        mut ctx: ExecCtx,
+        reward_asset: RewardAsset,
+        amount: Uint128,
+        total_staked: Uint128,
+    ) -> Result<Response, ContractError> {
         let config = self.config.load(ctx.deps.storage)?;
         ensure_eq!(
             config.stake,
@@ -170,115 +425,2778 @@ impl StakeExternalRewardsContract {
             CommonError::Unauthorized("sender is not the stake contract".to_string())
         );
 
-        let rewards = self.update_rewards(&mut ctx, &config, total_staked)?;
+        let mut distribution = self.load_distribution(ctx.deps.storage, &reward_asset)?;
 
-        let recipient_addr = ctx.deps.api.addr_validate(&recipient)?;
+        // Cw20 tokens are transferred by the stake contract before the top up
+        match &reward_asset {
+            RewardAsset::Native(denom) => ensure_eq!(
+                must_pay(&ctx.info, denom)?,
+                amount,
+                CommonError::InvalidInput("funds do not match top up amount".to_string())
+            ),
+            RewardAsset::Cw20(_) => nonpayable(&ctx.info)?,
+        };
 
-        let current_user_reward = self
-            .user_rewards
-            .may_load(ctx.deps.storage, recipient_addr.clone())?
+        // Fees are paid on stake changes, so a top up the schedule cannot take must not fail
+        let (from, to) = distribution.funding_window(ctx.env.block.time, 0);
+        let rewards_per_second = amount
+            .checked_div(Uint128::from(to.seconds() - from.seconds()))
             .unwrap_or_default();
+        if rewards_per_second.is_zero() || self.status.load(ctx.deps.storage)?.emergency_mode {
+            return self.add_undistributed(
+                &mut ctx,
+                &config,
+                &mut distribution,
+                amount,
+                total_staked,
+            );
+        }
 
-        let mut next_user_reward =
-            current_user_reward.get_next_user_reward(rewards.rewards_per_token, staked_amount)?;
+        self.add_rewards(
+            &mut ctx,
+            &config,
+            &mut distribution,
+            amount,
+            0,
+            total_staked,
+            "top-up",
+        )
+    }
 
-        let send_msg: Option<CosmosMsg> = if next_user_reward.pending_rewards > Uint128::zero() {
-            let claim_amount = next_user_reward.claim_rewards()?;
+    /// Adds native rewards to the current period of their asset, optionally extending it
+    #[sv::msg(exec)]
+    pub fn fund(
+        &self,
+        mut ctx: ExecCtx,
+        extra_duration_sec: Option<u64>,
+    ) -> Result<Response, ContractError> {
+        let config = self.config.load(ctx.deps.storage)?;
 
-            match &config.reward_asset {
-                RewardAsset::Native(denom) => Some(
-                    BankMsg::Send {
-                        to_address: recipient_addr.to_string(),
-                        amount: vec![coin(claim_amount.u128(), denom)],
-                    }
-                    .into(),
-                ),
-                RewardAsset::Cw20(cw20) => Some(
-                    WasmMsg::Execute {
-                        contract_addr: cw20.to_string(),
-                        msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
-                            recipient,
-                            amount: claim_amount,
-                        })?,
-                        funds: vec![],
-                    }
-                    .into(),
-                ),
+        // Cw20 rewards are funded by sending tokens
+        let coin = one_coin(&ctx.info)?;
+        let mut distribution =
+            self.load_distribution(ctx.deps.storage, &RewardAsset::Native(coin.denom))?;
+
+        let funder = ctx.info.sender.clone();
+        self.fund_rewards(
+            &mut ctx,
+            &config,
+            &mut distribution,
+            &funder,
+            coin.amount,
+            extra_duration_sec.unwrap_or_default(),
+        )
+    }
+
+    /// Adds cw20 rewards sent with a `ReceiveMsg` to the current period of the token
+    #[sv::msg(exec)]
+    pub fn receive(
+        &self,
+        mut ctx: ExecCtx,
+        sender: String,
+        amount: Uint128,
+        msg: Binary,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let funder = ctx.deps.api.addr_validate(&sender)?;
+        let reward_asset = RewardAsset::Cw20(ctx.info.sender.clone());
+
+        match from_json(&msg)? {
+            ReceiveMsg::Fund { extra_duration_sec } => {
+                let config = self.config.load(ctx.deps.storage)?;
+                let mut distribution = self
+                    .distributions
+                    .may_load(ctx.deps.storage, reward_asset.to_string())?
+                    .filter(|distribution| distribution.reward_asset == reward_asset)
+                    .ok_or_else(|| {
+                        CommonError::Unauthorized("sender is not a reward token".to_string())
+                    })?;
+
+                self.fund_rewards(
+                    &mut ctx,
+                    &config,
+                    &mut distribution,
+                    &funder,
+                    amount,
+                    extra_duration_sec.unwrap_or_default(),
+                )
             }
-        } else {
-            None
-        };
+            ReceiveMsg::AddRewardAsset { schedule, curve } => {
+                // The hook's sender is reported by the token, so only approved tokens are trusted
+                ensure!(
+                    self.reward_tokens
+                        .has(ctx.deps.storage, ctx.info.sender.clone()),
+                    CommonError::Unauthorized("sender is not an approved reward token".to_string())
+                );
 
-        self.user_rewards
-            .save(ctx.deps.storage, recipient_addr, &next_user_reward)?;
+                self.add_distribution(
+                    ctx.deps,
+                    &ctx.env,
+                    &funder,
+                    reward_asset,
+                    schedule,
+                    curve,
+                    amount,
+                )
+            }
+        }
+    }
 
-        let mut response = Response::new()
-            .add_event(UpdateRewardsEvent { rewards: &rewards })
-            .add_event(UpdateUserRewardsEvent {
-                user_reward: &next_user_reward,
-            });
+    /// Sends the rewards emitted while nothing was staked to the funder, rolled over rewards
+    /// can only be withdrawn in emergency mode
+    #[sv::msg(exec)]
+    pub fn withdraw_undistributed(
+        &self,
+        mut ctx: ExecCtx,
+        recipient: Option<String>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let config = self.config.load(ctx.deps.storage)?;
+        ensure_eq!(
+            config.funder,
+            ctx.info.sender,
+            CommonError::Unauthorized("only the funder can withdraw".to_string())
+        );
+        ensure!(
+            config.undistributed_mode == UndistributedMode::Withdraw
+                || self.status.load(ctx.deps.storage)?.emergency_mode,
+            CommonError::InvalidInput("undistributed rewards are rolled over".to_string())
+        );
+
+        let recipient = address_or(
+            &ctx.info.sender,
+            maybe_addr(ctx.deps.api, recipient)?.as_ref(),
+        );
+
+        let total_staked = self.query_total_staked(ctx.deps.querier, &config)?;
+
+        let mut response = Response::new();
+        for mut distribution in self.load_distributions(ctx.deps.storage)? {
+            let mut rewards =
+                self.update_rewards(&mut ctx, &config, &mut distribution, total_staked)?;
+
+            let amount = rewards.undistributed;
+            if amount.is_zero() {
+                continue;
+            }
+
+            let key = distribution.reward_asset.to_string();
+            rewards.undistributed = Uint128::zero();
+            self.rewards.save(ctx.deps.storage, key.clone(), &rewards)?;
+
+            self.ledgers.update(ctx.deps.storage, key, |ledger| {
+                let mut ledger = ledger.unwrap_or_default();
+                ledger.withdrawn = ledger.withdrawn.checked_add(amount)?;
+                Ok::<_, ContractError>(ledger)
+            })?;
 
-        if let Some(send_msg) = send_msg {
-            response = response.add_message(send_msg);
+            response = response
+                .add_event(UpdateRewardsEvent {
+                    reward_asset: &distribution.reward_asset,
+                    rewards: &rewards,
+                })
+                .add_event(WithdrawUndistributedEvent {
+                    reward_asset: &distribution.reward_asset,
+                    recipient: &recipient,
+                    amount,
+                })
+                .add_message(distribution.reward_asset.send_message(&recipient, amount)?);
         }
+        ensure!(
+            !response.messages.is_empty(),
+            CommonError::InvalidInput("nothing to withdraw".to_string())
+        );
 
         Ok(response)
     }
 
-    #[sv::msg(query)]
-    pub fn config(&self, ctx: QueryCtx) -> StdResult<Config> {
-        self.config.load(ctx.deps.storage)
-    }
+    /// Sends the balance beyond the liabilities to the funder, for every reward asset whose
+    /// schedule finished
+    #[sv::msg(exec)]
+    pub fn sweep_dust(&self, mut ctx: ExecCtx) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
 
-    #[sv::msg(query)]
-    pub fn rewards(&self, ctx: QueryCtx) -> StdResult<CumulativeRewards> {
-        self.rewards.load(ctx.deps.storage)
-    }
+        let config = self.config.load(ctx.deps.storage)?;
 
-    #[sv::msg(query)]
-    pub fn user_reward(&self, ctx: QueryCtx, address: String) -> StdResult<Option<UserReward>> {
-        self.user_rewards
-            .may_load(ctx.deps.storage, ctx.deps.api.addr_validate(&address)?)
+        let distributions = self
+            .load_distributions(ctx.deps.storage)?
+            .into_iter()
+            .filter(|distribution| {
+                distribution
+                    .period_finish()
+                    .is_none_or(|period_finish| ctx.env.block.time >= period_finish)
+            })
+            .collect::<Vec<Distribution>>();
+        ensure!(
+            !distributions.is_empty(),
+            CommonError::InvalidInput("reward period has not finished".to_string())
+        );
+
+        let total_staked = self.query_total_staked(ctx.deps.querier, &config)?;
+
+        let mut response = Response::new();
+        for mut distribution in distributions {
+            let rewards =
+                self.update_rewards(&mut ctx, &config, &mut distribution, total_staked)?;
+
+            let amount = self
+                .balance_sheet_of(ctx.deps.as_ref(), &ctx.env, &distribution)?
+                .dust;
+            if amount.is_zero() {
+                continue;
+            }
+
+            self.ledgers.update(
+                ctx.deps.storage,
+                distribution.reward_asset.to_string(),
+                |ledger| {
+                    let mut ledger = ledger.unwrap_or_default();
+                    ledger.swept = ledger.swept.checked_add(amount)?;
+                    Ok::<_, ContractError>(ledger)
+                },
+            )?;
+
+            response = response
+                .add_event(UpdateRewardsEvent {
+                    reward_asset: &distribution.reward_asset,
+                    rewards: &rewards,
+                })
+                .add_event(SweepDustEvent {
+                    reward_asset: &distribution.reward_asset,
+                    recipient: &config.funder,
+                    amount,
+                })
+                .add_message(
+                    distribution
+                        .reward_asset
+                        .send_message(&config.funder, amount)?,
+                );
+        }
+        ensure!(
+            !response.messages.is_empty(),
+            CommonError::InvalidInput("no dust to sweep".to_string())
+        );
+
+        Ok(response)
     }
 
-    #[sv::msg(query)]
-    pub fn latest_user_reward(
+    /// Sends the undistributed rewards, the dust and the rewards left unclaimed by the given
+    /// stakers back to the funder, for every reward asset whose grace period after the schedule
+    /// passed. Only stakers with nothing staked and no checkpoint within the grace period
+    /// forfeit their rewards, the others are skipped.
+    #[sv::msg(exec)]
+    pub fn reclaim(
         &self,
-        ctx: QueryCtx,
-        address: String,
-        staked_amount: Uint128,
-        total_staked: Uint128,
-    ) -> Result<Option<UserReward>, ContractError> {
+        mut ctx: ExecCtx,
+        addresses: Vec<String>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
         let config = self.config.load(ctx.deps.storage)?;
+        ensure_eq!(
+            config.funder,
+            ctx.info.sender,
+            CommonError::Unauthorized("only the funder can reclaim".to_string())
+        );
+        ensure!(
+            addresses.len() <= MAX_RECLAIM_ADDRESSES,
+            CommonError::InvalidInput(format!(
+                "at most {MAX_RECLAIM_ADDRESSES} addresses per reclaim"
+            ))
+        );
 
-        let rewards = self.rewards.load(ctx.deps.storage)?;
-        let rewards_per_token = rewards.calc_rewards_per_token(&ctx.env, &config, total_staked)?;
+        let now = ctx.env.block.time;
+        let distributions = self
+            .load_distributions(ctx.deps.storage)?
+            .into_iter()
+            .filter(|distribution| {
+                distribution.period_finish().is_some_and(|period_finish| {
+                    now >= period_finish.plus_seconds(config.grace_period_sec)
+                })
+            })
+            .collect::<Vec<Distribution>>();
+        ensure!(
+            !distributions.is_empty(),
+            CommonError::InvalidInput("grace period has not passed".to_string())
+        );
 
-        let current_user_reward = self
-            .user_rewards
-            .may_load(ctx.deps.storage, ctx.deps.api.addr_validate(&address)?)?
-            .unwrap_or_default();
+        let mut unstaked_addresses: Vec<Addr> = vec![];
+        for address in addresses {
+            let address = ctx.deps.api.addr_validate(&address)?;
+            if !unstaked_addresses.contains(&address)
+                && self
+                    .query_staked_amount(ctx.deps.querier, &config, &address)?
+                    .is_zero()
+            {
+                unstaked_addresses.push(address);
+            }
+        }
+
+        let total_staked = self.query_total_staked(ctx.deps.querier, &config)?;
 
-        let next_user_reward =
-            current_user_reward.get_next_user_reward(rewards_per_token, staked_amount)?;
+        let mut response = Response::new();
+        for mut distribution in distributions {
+            let mut rewards =
+                self.update_rewards(&mut ctx, &config, &mut distribution, total_staked)?;
+            let key = distribution.reward_asset.to_string();
 
-        Ok(Some(next_user_reward))
+            let mut forfeited = Uint128::zero();
+            for address in &unstaked_addresses {
+                let user_reward =
+                    self.load_user_reward(ctx.deps.storage, address, &distribution.reward_asset)?;
+                if user_reward.last_update.is_some_and(|last_update| {
+                    now < last_update.plus_seconds(config.grace_period_sec)
+                }) {
+                    continue;
+                }
+
+                // Nothing is staked, so nothing accrued since the last checkpoint
+                let mut next_user_reward =
+                    user_reward.get_next_user_reward(rewards.rewards_per_token, Uint128::zero())?;
+                forfeited = forfeited.checked_add(next_user_reward.pending_rewards)?;
+                next_user_reward.pending_rewards = Uint128::zero();
+                self.user_rewards.save(
+                    ctx.deps.storage,
+                    (address.clone(), key.clone()),
+                    &next_user_reward,
+                )?;
+            }
+
+            let undistributed = rewards.undistributed;
+            rewards.undistributed = Uint128::zero();
+            self.rewards.save(ctx.deps.storage, key.clone(), &rewards)?;
+            self.ledgers
+                .update(ctx.deps.storage, key.clone(), |ledger| {
+                    let mut ledger = ledger.unwrap_or_default();
+                    ledger.forfeited = ledger.forfeited.checked_add(forfeited)?;
+                    ledger.withdrawn = ledger.withdrawn.checked_add(undistributed)?;
+                    Ok::<_, ContractError>(ledger)
+                })?;
+
+            // What is still owed to the other stakers stays
+            let balance_sheet =
+                self.balance_sheet_of(ctx.deps.as_ref(), &ctx.env, &distribution)?;
+            let liabilities = balance_sheet
+                .owed
+                .checked_add(balance_sheet.vesting)?
+                .checked_add(balance_sheet.remaining)?;
+            let amount = balance_sheet.balance.saturating_sub(liabilities);
+            if amount.is_zero() {
+                continue;
+            }
+
+            self.ledgers.update(ctx.deps.storage, key, |ledger| {
+                let mut ledger = ledger.unwrap_or_default();
+                ledger.reclaimed = ledger.reclaimed.checked_add(amount)?;
+                Ok::<_, ContractError>(ledger)
+            })?;
+
+            response = response
+                .add_event(UpdateRewardsEvent {
+                    reward_asset: &distribution.reward_asset,
+                    rewards: &rewards,
+                })
+                .add_event(ReclaimEvent {
+                    reward_asset: &distribution.reward_asset,
+                    recipient: &config.funder,
+                    amount,
+                    forfeited,
+                    liabilities,
+                })
+                .add_message(
+                    distribution
+                        .reward_asset
+                        .send_message(&config.funder, amount)?,
+                );
+        }
+        ensure!(
+            !response.messages.is_empty(),
+            CommonError::InvalidInput("nothing to reclaim".to_string())
+        );
+
+        Ok(response)
     }
 
-    pub fn update_rewards(
+    /// Approves a cw20 token to be added as a reward asset by sending it
+    #[sv::msg(exec)]
+    pub fn add_reward_token(&self, ctx: ExecCtx, token: String) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let config = self.config.load(ctx.deps.storage)?;
+        ensure_eq!(
+            config.funder,
+            ctx.info.sender,
+            CommonError::Unauthorized("only the funder can manage reward tokens".to_string())
+        );
+
+        let token = ctx.deps.api.addr_validate(&token)?;
+        ensure!(
+            !self.reward_tokens.has(ctx.deps.storage, token.clone()),
+            CommonError::InvalidInput(format!("{} is already a reward token", token))
+        );
+        ensure!(
+            self.reward_tokens
+                .keys(ctx.deps.storage, None, None, Order::Ascending)
+                .count()
+                < MAX_REWARD_ASSETS,
+            CommonError::InvalidInput(format!(
+                "at most {} reward tokens are allowed",
+                MAX_REWARD_ASSETS
+            ))
+        );
+        self.reward_tokens
+            .save(ctx.deps.storage, token.clone(), &Empty {})?;
+
+        Ok(Response::new().add_event(RewardTokenEvent {
+            ty: "add-reward-token",
+            token: &token,
+        }))
+    }
+
+    #[sv::msg(exec)]
+    pub fn remove_reward_token(
         &self,
-        ctx: &mut ExecCtx,
-        config: &Config,
-        total_staked: Uint128,
-    ) -> Result<CumulativeRewards, ContractError> {
-        let mut rewards = self.rewards.load(ctx.deps.storage)?;
+        ctx: ExecCtx,
+        token: String,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let config = self.config.load(ctx.deps.storage)?;
+        ensure_eq!(
+            config.funder,
+            ctx.info.sender,
+            CommonError::Unauthorized("only the funder can manage reward tokens".to_string())
+        );
+
+        let token = ctx.deps.api.addr_validate(&token)?;
+        ensure!(
+            self.reward_tokens.has(ctx.deps.storage, token.clone()),
+            CommonError::InvalidInput(format!("{} is not a reward token", token))
+        );
+        self.reward_tokens.remove(ctx.deps.storage, token.clone());
 
-        rewards.rewards_per_token =
-            rewards.calc_rewards_per_token(&ctx.env, &config, total_staked)?;
-        rewards.last_update = ctx.env.block.time;
+        Ok(Response::new().add_event(RewardTokenEvent {
+            ty: "remove-reward-token",
+            token: &token,
+        }))
+    }
 
-        self.rewards.save(ctx.deps.storage, &rewards)?;
+    #[sv::msg(sudo)]
+    pub fn set_paused(&self, ctx: SudoCtx, paused: bool) -> Result<Response, ContractError> {
+        let mut status = self.status.load(ctx.deps.storage)?;
+        status.paused = paused;
+        self.status.save(ctx.deps.storage, &status)?;
 
-        Ok(rewards)
+        Ok(Response::new().add_event(StatusEvent {
+            ty: "sudo-set-paused",
+            status: &status,
+        }))
+    }
+
+    #[sv::msg(sudo)]
+    pub fn set_emergency_mode(
+        &self,
+        ctx: SudoCtx,
+        emergency_mode: bool,
+    ) -> Result<Response, ContractError> {
+        let mut status = self.status.load(ctx.deps.storage)?;
+        status.emergency_mode = emergency_mode;
+        self.status.save(ctx.deps.storage, &status)?;
+
+        let mut response = Response::new().add_event(StatusEvent {
+            ty: "sudo-set-emergency-mode",
+            status: &status,
+        });
+
+        // Emissions end at the current block, rewards already accrued remain claimable
+        if emergency_mode {
+            for mut distribution in self.load_distributions(ctx.deps.storage)? {
+                let previous_distribution = distribution.clone();
+                distribution.truncate(ctx.env.block.time);
+                if distribution != previous_distribution {
+                    self.distributions.save(
+                        ctx.deps.storage,
+                        distribution.reward_asset.to_string(),
+                        &distribution,
+                    )?;
+
+                    response = response.add_event(DistributionEvent {
+                        ty: "update-distribution",
+                        distribution: &distribution,
+                    });
+                }
+            }
+        }
+
+        Ok(response)
+    }
+
+    #[sv::msg(sudo)]
+    pub fn set_stake(&self, ctx: SudoCtx, stake: String) -> Result<Response, ContractError> {
+        let mut config = self.config.load(ctx.deps.storage)?;
+        config.stake = ctx.deps.api.addr_validate(&stake)?;
+        self.config.save(ctx.deps.storage, &config)?;
+
+        Ok(Response::new().add_event(ConfigEvent {
+            ty: "update-config",
+            config: &config,
+        }))
+    }
+
+    #[sv::msg(query)]
+    pub fn config(&self, ctx: QueryCtx) -> StdResult<Config> {
+        self.config.load(ctx.deps.storage)
+    }
+
+    #[sv::msg(query)]
+    pub fn distributions(&self, ctx: QueryCtx) -> StdResult<Vec<Distribution>> {
+        self.load_distributions(ctx.deps.storage)
+    }
+
+    /// The cw20 tokens approved to be added as reward assets
+    #[sv::msg(query)]
+    pub fn reward_tokens(&self, ctx: QueryCtx) -> StdResult<Vec<Addr>> {
+        self.reward_tokens
+            .keys(ctx.deps.storage, None, None, Order::Ascending)
+            .collect()
+    }
+
+    #[sv::msg(query)]
+    pub fn schedule(
+        &self,
+        ctx: QueryCtx,
+        reward_asset: RewardAsset,
+    ) -> Result<ScheduleResponse, ContractError> {
+        let distribution = self.load_distribution(ctx.deps.storage, &reward_asset)?;
+
+        let mut rewards_per_second = distribution.rewards_per_second_at(ctx.env.block.time);
+        if let Some(curve) = &distribution.curve {
+            rewards_per_second =
+                rewards_per_second.checked_add(curve.rewards_per_second_at(ctx.env.block.time)?)?;
+        }
+
+        Ok(ScheduleResponse {
+            rewards_per_second,
+            segments: distribution
+                .schedule
+                .into_iter()
+                .filter(|segment| segment.end > ctx.env.block.time)
+                .collect(),
+        })
+    }
+
+    #[sv::msg(query)]
+    pub fn status(&self, ctx: QueryCtx) -> StdResult<Status> {
+        self.status.load(ctx.deps.storage)
+    }
+
+    #[sv::msg(query)]
+    pub fn rewards(
+        &self,
+        ctx: QueryCtx,
+        reward_asset: RewardAsset,
+    ) -> Result<CumulativeRewards, ContractError> {
+        self.rewards
+            .may_load(ctx.deps.storage, reward_asset.to_string())?
+            .ok_or(ContractError::RewardAssetNotFound)
+    }
+
+    #[sv::msg(query)]
+    pub fn user_reward(
+        &self,
+        ctx: QueryCtx,
+        address: String,
+        reward_asset: RewardAsset,
+    ) -> StdResult<Option<UserReward>> {
+        self.user_rewards.may_load(
+            ctx.deps.storage,
+            (
+                ctx.deps.api.addr_validate(&address)?,
+                reward_asset.to_string(),
+            ),
+        )
+    }
+
+    #[sv::msg(query)]
+    pub fn balance_sheet(
+        &self,
+        ctx: QueryCtx,
+        reward_asset: RewardAsset,
+    ) -> Result<BalanceSheet, ContractError> {
+        let distribution = self.load_distribution(ctx.deps.storage, &reward_asset)?;
+        self.balance_sheet_of(ctx.deps, &ctx.env, &distribution)
+    }
+
+    /// The vesting of the rewards the staker claimed in a reward asset as of the current block
+    #[sv::msg(query)]
+    pub fn vesting(
+        &self,
+        ctx: QueryCtx,
+        address: String,
+        reward_asset: RewardAsset,
+    ) -> Result<VestingResponse, ContractError> {
+        let config = self.config.load(ctx.deps.storage)?;
+        let vesting_account = self
+            .vesting_accounts
+            .may_load(
+                ctx.deps.storage,
+                (
+                    ctx.deps.api.addr_validate(&address)?,
+                    reward_asset.to_string(),
+                ),
+            )?
+            .unwrap_or_default();
+
+        Ok(match &config.vesting {
+            Some(vesting) => VestingResponse {
+                vested: vesting_account.vested_at(vesting, ctx.env.block.time)?,
+                locked: vesting_account.locked_at(vesting, ctx.env.block.time)?,
+                withdrawn: vesting_account.withdrawn,
+            },
+            None => VestingResponse {
+                vested: Uint128::zero(),
+                locked: Uint128::zero(),
+                withdrawn: Uint128::zero(),
+            },
+        })
+    }
+
+    /// The rewards of the staker in every reward asset as of the current block, against the
+    /// staked amounts queried from the stake contract
+    #[sv::msg(query)]
+    pub fn latest_user_reward(
+        &self,
+        ctx: QueryCtx,
+        address: String,
+    ) -> Result<Vec<AssetUserReward>, ContractError> {
+        let config = self.config.load(ctx.deps.storage)?;
+        let address = ctx.deps.api.addr_validate(&address)?;
+
+        let staked_amount = self.query_staked_amount(ctx.deps.querier, &config, &address)?;
+        let total_staked = self.query_total_staked(ctx.deps.querier, &config)?;
+
+        self.load_distributions(ctx.deps.storage)?
+            .into_iter()
+            .map(|distribution| {
+                let key = distribution.reward_asset.to_string();
+                let rewards = self.rewards.load(ctx.deps.storage, key)?;
+                let rewards_per_token =
+                    rewards.calc_rewards_per_token(&ctx.env, &distribution, total_staked)?;
+
+                let current_user_reward =
+                    self.load_user_reward(ctx.deps.storage, &address, &distribution.reward_asset)?;
+
+                Ok(AssetUserReward {
+                    user_reward: current_user_reward
+                        .get_next_user_reward(rewards_per_token, staked_amount)?,
+                    reward_asset: distribution.reward_asset,
+                })
+            })
+            .collect()
+    }
+
+    /// Adds a reward asset for the funder, outside of emergency mode
+    pub fn add_distribution(
+        &self,
+        deps: DepsMut,
+        env: &Env,
+        sender: &Addr,
+        reward_asset: RewardAsset,
+        schedule: Vec<EmissionSegment>,
+        curve: Option<EmissionCurve>,
+        fund_amount: Uint128,
+    ) -> Result<Response, ContractError> {
+        let config = self.config.load(deps.storage)?;
+        ensure_eq!(
+            &config.funder,
+            sender,
+            CommonError::Unauthorized("only the funder can add reward assets".to_string())
+        );
+        ensure!(
+            !self.status.load(deps.storage)?.emergency_mode,
+            CommonError::InvalidInput("emissions ended by emergency mode".to_string())
+        );
+
+        let distribution =
+            self.create_distribution(deps, env, reward_asset, schedule, curve, fund_amount)?;
+
+        Ok(Response::new().add_event(DistributionEvent {
+            ty: "add-reward-asset",
+            distribution: &distribution,
+        }))
+    }
+
+    /// Validates and stores a new reward asset, funded with the native rewards sent along or
+    /// with exactly the scheduled cw20 rewards transferred beforehand
+    pub fn create_distribution(
+        &self,
+        deps: DepsMut,
+        env: &Env,
+        reward_asset: RewardAsset,
+        schedule: Vec<EmissionSegment>,
+        curve: Option<EmissionCurve>,
+        fund_amount: Uint128,
+    ) -> Result<Distribution, ContractError> {
+        let key = reward_asset.to_string();
+        ensure!(
+            !self.distributions.has(deps.storage, key.clone()),
+            CommonError::InvalidInput(format!("{} is already a reward asset", reward_asset))
+        );
+        ensure!(
+            self.distributions
+                .keys(deps.storage, None, None, Order::Ascending)
+                .count()
+                < MAX_REWARD_ASSETS,
+            CommonError::InvalidInput(format!(
+                "at most {} reward assets are allowed",
+                MAX_REWARD_ASSETS
+            ))
+        );
+
+        let (schedule, mut scheduled_amount) = validate_schedule(env.block.time, schedule)?;
+        if let Some(curve) = &curve {
+            curve.validate(env.block.time)?;
+            scheduled_amount = scheduled_amount.checked_add(curve.amount)?;
+        }
+        ensure!(
+            !scheduled_amount.is_zero(),
+            CommonError::InvalidInput("schedule or curve must emit rewards".to_string())
+        );
+
+        ensure!(
+            fund_amount >= scheduled_amount,
+            CommonError::InvalidInput(format!(
+                "schedule emits {} but only {} was funded",
+                scheduled_amount, fund_amount
+            ))
+        );
+        // Anything else held by the contract is dust
+        if let RewardAsset::Cw20(_) = &reward_asset {
+            ensure_eq!(
+                fund_amount,
+                scheduled_amount,
+                CommonError::InvalidInput(format!(
+                    "schedule emits {} but {} was sent",
+                    scheduled_amount, fund_amount
+                ))
+            );
+            let balance = reward_asset.query_balance(deps.querier, &env.contract.address)?;
+            ensure!(
+                balance >= fund_amount,
+                CommonError::InvalidInput(format!("only {} was transferred", balance))
+            );
+        }
+
+        let distribution = Distribution {
+            reward_asset,
+            schedule,
+            curve,
+        };
+        self.distributions
+            .save(deps.storage, key.clone(), &distribution)?;
+
+        self.rewards.save(
+            deps.storage,
+            key.clone(),
+            &CumulativeRewards {
+                rewards_per_token: Uint256::zero(),
+                last_update: env.block.time,
+                undistributed: Uint128::zero(),
+            },
+        )?;
+
+        self.ledgers.save(
+            deps.storage,
+            key,
+            &Ledger {
+                funded: fund_amount,
+                ..Ledger::default()
+            },
+        )?;
+
+        Ok(distribution)
+    }
+
+    /// Funds from outside the stake contract are checkpointed against the total it reports
+    pub fn fund_rewards(
+        &self,
+        ctx: &mut ExecCtx,
+        config: &Config,
+        distribution: &mut Distribution,
+        funder: &Addr,
+        amount: Uint128,
+        extra_duration_sec: u64,
+    ) -> Result<Response, ContractError> {
+        let total_staked = self.query_total_staked(ctx.deps.querier, config)?;
+
+        let response = self
+            .add_rewards(
+                ctx,
+                config,
+                distribution,
+                amount,
+                extra_duration_sec,
+                total_staked,
+                "fund",
+            )?
+            .add_event(FundEvent {
+                reward_asset: &distribution.reward_asset,
+                funder,
+                amount,
+            });
+
+        Ok(response)
+    }
+
+    /// Checkpoints the rewards, then spreads the amount evenly over the rest of the schedule,
+    /// extended by the extra duration, on top of the scheduled emissions. A finished schedule
+    /// restarts from the current time.
+    pub fn add_rewards(
+        &self,
+        ctx: &mut ExecCtx,
+        config: &Config,
+        distribution: &mut Distribution,
+        amount: Uint128,
+        extra_duration_sec: u64,
+        total_staked: Uint128,
+        ty: &str,
+    ) -> Result<Response, ContractError> {
+        ensure!(
+            !self.status.load(ctx.deps.storage)?.emergency_mode,
+            CommonError::InvalidInput("emissions ended by emergency mode".to_string())
+        );
+        ensure!(
+            !amount.is_zero(),
+            CommonError::InvalidInput("fund amount must be greater than zero".to_string())
+        );
+
+        let mut rewards = self.update_rewards(ctx, config, distribution, total_staked)?;
+
+        let key = distribution.reward_asset.to_string();
+        self.ledgers
+            .update(ctx.deps.storage, key.clone(), |ledger| {
+                let mut ledger = ledger.unwrap_or_default();
+                ledger.funded = ledger.funded.checked_add(amount)?;
+                Ok::<_, ContractError>(ledger)
+            })?;
+
+        // Rolled over rewards left once the schedule ended go out with the funding
+        let mut amount = amount;
+        if config.undistributed_mode == UndistributedMode::Rollover {
+            amount = amount.checked_add(rewards.undistributed)?;
+        }
+
+        let now = ctx.env.block.time;
+        let (from, to) = distribution.funding_window(now, extra_duration_sec);
+        ensure!(
+            to > from,
+            CommonError::InvalidInput("reward period has finished".to_string())
+        );
+
+        let duration_sec = Uint128::from(to.seconds() - from.seconds());
+        let rewards_per_second = amount.checked_div(duration_sec)?;
+        ensure!(
+            rewards_per_second > Uint128::zero(),
+            CommonError::InvalidInput("reward rate must be greater than zero".to_string())
+        );
+
+        if config.undistributed_mode == UndistributedMode::Rollover {
+            rewards.undistributed =
+                amount.checked_sub(rewards_per_second.checked_mul(duration_sec)?)?;
+            self.rewards.save(ctx.deps.storage, key.clone(), &rewards)?;
+        }
+
+        // Rewards are checkpointed, so the segments that ended are no longer needed
+        distribution.prune(now);
+        distribution.add_rewards_per_second(from, to, rewards_per_second)?;
+        self.distributions
+            .save(ctx.deps.storage, key, distribution)?;
+
+        let response = Response::new()
+            .add_event(UpdateRewardsEvent {
+                reward_asset: &distribution.reward_asset,
+                rewards: &rewards,
+            })
+            .add_event(DistributionEvent { ty, distribution });
+
+        Ok(response)
+    }
+
+    /// Checkpoints the rewards, then keeps the amount undistributed for the funder to withdraw,
+    /// or to go out with the next funding in rollover mode
+    pub fn add_undistributed(
+        &self,
+        ctx: &mut ExecCtx,
+        config: &Config,
+        distribution: &mut Distribution,
+        amount: Uint128,
+        total_staked: Uint128,
+    ) -> Result<Response, ContractError> {
+        let mut rewards = self.update_rewards(ctx, config, distribution, total_staked)?;
+        rewards.undistributed = rewards.undistributed.checked_add(amount)?;
+
+        let key = distribution.reward_asset.to_string();
+        self.rewards.save(ctx.deps.storage, key.clone(), &rewards)?;
+        self.ledgers.update(ctx.deps.storage, key, |ledger| {
+            let mut ledger = ledger.unwrap_or_default();
+            ledger.funded = ledger.funded.checked_add(amount)?;
+            Ok::<_, ContractError>(ledger)
+        })?;
+
+        Ok(Response::new().add_event(UpdateRewardsEvent {
+            reward_asset: &distribution.reward_asset,
+            rewards: &rewards,
+        }))
+    }
+
+    /// Checkpoints the rewards of an asset, rolling undistributed rewards over the rest of its
+    /// schedule in rollover mode
+    pub fn update_rewards(
+        &self,
+        ctx: &mut ExecCtx,
+        config: &Config,
+        distribution: &mut Distribution,
+        total_staked: Uint128,
+    ) -> Result<CumulativeRewards, ContractError> {
+        let key = distribution.reward_asset.to_string();
+
+        let mut rewards = self.rewards.load(ctx.deps.storage, key.clone())?;
+        let credited = rewards.checkpoint(&ctx.env, distribution, total_staked)?;
+        if !credited.is_zero() {
+            self.ledgers
+                .update(ctx.deps.storage, key.clone(), |ledger| {
+                    let mut ledger = ledger.unwrap_or_default();
+                    ledger.credited = ledger.credited.checked_add(credited)?;
+                    Ok::<_, ContractError>(ledger)
+                })?;
+        }
+
+        let now = ctx.env.block.time;
+        if config.undistributed_mode == UndistributedMode::Rollover
+            && !rewards.undistributed.is_zero()
+        {
+            if let Some(finish) = distribution.period_finish() {
+                let duration_sec = Uint128::from(finish.seconds().saturating_sub(now.seconds()));
+                let rewards_per_second = rewards
+                    .undistributed
+                    .checked_div(duration_sec)
+                    .unwrap_or_default();
+
+                // Stays undistributed when the schedule cannot take another segment
+                let mut rolled_distribution = distribution.clone();
+                rolled_distribution.prune(now);
+                if !rewards_per_second.is_zero()
+                    && rolled_distribution
+                        .add_rewards_per_second(now, finish, rewards_per_second)
+                        .is_ok()
+                {
+                    *distribution = rolled_distribution;
+                    self.distributions
+                        .save(ctx.deps.storage, key.clone(), distribution)?;
+
+                    rewards.undistributed = rewards
+                        .undistributed
+                        .checked_sub(rewards_per_second.checked_mul(duration_sec)?)?;
+                }
+            }
+        }
+
+        self.rewards.save(ctx.deps.storage, key, &rewards)?;
+
+        Ok(rewards)
+    }
+
+    /// Checkpoints the rewards of an asset and the staker's share of them
+    pub fn checkpoint_user_reward(
+        &self,
+        ctx: &mut ExecCtx,
+        config: &Config,
+        distribution: &mut Distribution,
+        address: &Addr,
+        staked_amount: Uint128,
+        total_staked: Uint128,
+    ) -> Result<(CumulativeRewards, UserReward), ContractError> {
+        let rewards = self.update_rewards(ctx, config, distribution, total_staked)?;
+
+        let current_user_reward =
+            self.load_user_reward(ctx.deps.storage, address, &distribution.reward_asset)?;
+
+        let mut next_user_reward =
+            current_user_reward.get_next_user_reward(rewards.rewards_per_token, staked_amount)?;
+        next_user_reward.last_update = Some(ctx.env.block.time);
+        self.record_rounding(
+            ctx.deps.storage,
+            &distribution.reward_asset,
+            &current_user_reward,
+            &rewards,
+            staked_amount,
+        )?;
+
+        Ok((rewards, next_user_reward))
+    }
+
+    pub fn load_distributions(&self, storage: &dyn Storage) -> StdResult<Vec<Distribution>> {
+        self.distributions
+            .range(storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(_, distribution)| distribution))
+            .collect()
+    }
+
+    pub fn load_distribution(
+        &self,
+        storage: &dyn Storage,
+        reward_asset: &RewardAsset,
+    ) -> Result<Distribution, ContractError> {
+        self.distributions
+            .may_load(storage, reward_asset.to_string())?
+            .filter(|distribution| &distribution.reward_asset == reward_asset)
+            .ok_or(ContractError::RewardAssetNotFound)
+    }
+
+    pub fn load_user_reward(
+        &self,
+        storage: &dyn Storage,
+        address: &Addr,
+        reward_asset: &RewardAsset,
+    ) -> StdResult<UserReward> {
+        Ok(self
+            .user_rewards
+            .may_load(storage, (address.clone(), reward_asset.to_string()))?
+            .unwrap_or_default())
+    }
+
+    /// Records the rewards the staker lost to flooring on checkpoint
+    pub fn record_rounding(
+        &self,
+        storage: &mut dyn Storage,
+        reward_asset: &RewardAsset,
+        user_reward: &UserReward,
+        rewards: &CumulativeRewards,
+        staked_amount: Uint128,
+    ) -> Result<(), ContractError> {
+        let rounding = user_reward.rounding(rewards.rewards_per_token, staked_amount)?;
+        if !rounding.is_zero() {
+            self.ledgers
+                .update(storage, reward_asset.to_string(), |ledger| {
+                    let mut ledger = ledger.unwrap_or_default();
+                    ledger.rounding = ledger.rounding.checked_add(rounding)?;
+                    Ok::<_, ContractError>(ledger)
+                })?;
+        }
+
+        Ok(())
+    }
+
+    /// Reconciles the ledger of an asset against its token balance as of the last checkpoint
+    pub fn balance_sheet_of(
+        &self,
+        deps: Deps,
+        env: &Env,
+        distribution: &Distribution,
+    ) -> Result<BalanceSheet, ContractError> {
+        let key = distribution.reward_asset.to_string();
+        let ledger = self.ledgers.load(deps.storage, key.clone())?;
+        let rewards = self.rewards.load(deps.storage, key)?;
+
+        let credited: Uint128 = ledger
+            .credited
+            .checked_sub(ledger.rounding)?
+            .checked_add(scale_factor().checked_sub(Uint256::one())?)?
+            .checked_div(scale_factor())?
+            .try_into()?;
+        let owed = credited
+            .saturating_sub(ledger.claimed)
+            .saturating_sub(ledger.forfeited);
+        let remaining = distribution.remaining_emissions(rewards.last_update)?;
+
+        let liabilities = owed
+            .checked_add(remaining)?
+            .checked_add(rewards.undistributed)?;
+        let balance = distribution
+            .reward_asset
+            .query_balance(deps.querier, &env.contract.address)?;
+
+        Ok(BalanceSheet {
+            funded: ledger.funded,
+            // The vesting rewards are part of the claimed ones
+            distributable: liabilities
+                .checked_add(ledger.claimed)?
+                .checked_add(ledger.withdrawn)?
+                .checked_add(ledger.forfeited)?,
+            claimed: ledger.claimed,
+            vesting: ledger.vesting,
+            withdrawn: ledger.withdrawn,
+            swept: ledger.swept,
+            forfeited: ledger.forfeited,
+            reclaimed: ledger.reclaimed,
+            owed,
+            remaining,
+            undistributed: rewards.undistributed,
+            balance,
+            dust: balance.saturating_sub(liabilities.checked_add(ledger.vesting)?),
+        })
+    }
+
+    pub fn query_total_staked(
+        &self,
+        querier: QuerierWrapper,
+        config: &Config,
+    ) -> StdResult<Uint128> {
+        querier.query_wasm_smart(&config.stake, &StakeQueryMsg::RewardsTotalStaked {})
+    }
+
+    pub fn query_staked_amount(
+        &self,
+        querier: QuerierWrapper,
+        config: &Config,
+        address: &Addr,
+    ) -> StdResult<Uint128> {
+        querier.query_wasm_smart(
+            &config.stake,
+            &StakeQueryMsg::RewardsStakedAmount {
+                address: address.to_string(),
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        contract::sv::mt::{CodeId, StakeExternalRewardsContractProxy},
+        curve::{Decay, EmissionCurve},
+        error::ContractError,
+        msg::{ReceiveMsg, StakeQueryMsg},
+        state::{
+            Accounting, Emission, EmissionSegment, RewardAsset, ScheduleSegment, UndistributedMode,
+            Vesting, MAX_RECLAIM_ADDRESSES,
+        },
+    };
+
+    use cosmwasm_std::{
+        coin, coins, to_json_binary, Addr, Binary, Empty, Response, StdResult, Timestamp, Uint128,
+    };
+    use cw20::BalanceResponse;
+    use cw_storage_plus::{Item, Map};
+    use sylvia::{
+        cw_multi_test::{App as CwApp, ContractWrapper, Executor, IntoAddr},
+        multitest::App,
+    };
+    use uju_cw2_common::error::CommonError;
+
+    fn ujuno() -> RewardAsset {
+        RewardAsset::Native("ujuno".to_string())
+    }
+
+    /// Emits the amount evenly over the duration
+    fn flat_schedule(start: Timestamp, duration_sec: u64, amount: u128) -> Vec<EmissionSegment> {
+        vec![EmissionSegment {
+            start,
+            end: start.plus_seconds(duration_sec),
+            emission: Emission::Amount(Uint128::new(amount)),
+        }]
+    }
+
+    /// Stands in for the stake contract, reporting a rewards total of 10
+    fn mock_stake(app: &App<CwApp>, owner: &Addr) -> Addr {
+        let code_id = app.app_mut().store_code(Box::new(ContractWrapper::new(
+            |_, _, _, _: Empty| -> StdResult<Response> { Ok(Response::new()) },
+            |_, _, _, _: Empty| -> StdResult<Response> { Ok(Response::new()) },
+            |_, _, _: Empty| -> StdResult<Binary> { to_json_binary(&Uint128::new(10)) },
+        )));
+
+        app.app_mut()
+            .instantiate_contract(code_id, owner.clone(), &Empty {}, &[], "stake", None)
+            .unwrap()
+    }
+
+    /// Stands in for the stake contract, reporting the amounts set by executing `(address, amount)`
+    fn mock_stake_amounts(app: &App<CwApp>, owner: &Addr) -> Addr {
+        const STAKED_AMOUNTS: Map<&str, Uint128> = Map::new("staked_amounts");
+        const TOTAL_STAKED: Item<Uint128> = Item::new("total_staked");
+
+        let code_id = app.app_mut().store_code(Box::new(ContractWrapper::new(
+            |deps, _, _, (address, amount): (String, Uint128)| -> StdResult<Response> {
+                let staked_amount = STAKED_AMOUNTS
+                    .may_load(deps.storage, &address)?
+                    .unwrap_or_default();
+                let total_staked = TOTAL_STAKED.may_load(deps.storage)?.unwrap_or_default();
+
+                STAKED_AMOUNTS.save(deps.storage, &address, &amount)?;
+                TOTAL_STAKED.save(deps.storage, &(total_staked - staked_amount + amount))?;
+                Ok(Response::new())
+            },
+            |_, _, _, _: Empty| -> StdResult<Response> { Ok(Response::new()) },
+            |deps, _, msg: StakeQueryMsg| -> StdResult<Binary> {
+                let amount = match msg {
+                    StakeQueryMsg::RewardsTotalStaked {} => TOTAL_STAKED.may_load(deps.storage)?,
+                    StakeQueryMsg::RewardsStakedAmount { address } => {
+                        STAKED_AMOUNTS.may_load(deps.storage, &address)?
+                    }
+                };
+                to_json_binary(&amount.unwrap_or_default())
+            },
+        )));
+
+        app.app_mut()
+            .instantiate_contract(code_id, owner.clone(), &Empty {}, &[], "stake", None)
+            .unwrap()
+    }
+
+    /// Stands in for a cw20 token, reporting a balance of 1000
+    fn mock_cw20(app: &App<CwApp>, owner: &Addr) -> Addr {
+        let code_id = app.app_mut().store_code(Box::new(ContractWrapper::new(
+            |_, _, _, _: Empty| -> StdResult<Response> { Ok(Response::new()) },
+            |_, _, _, _: Empty| -> StdResult<Response> { Ok(Response::new()) },
+            |_, _, _: Empty| -> StdResult<Binary> {
+                to_json_binary(&BalanceResponse {
+                    balance: Uint128::new(1000),
+                })
+            },
+        )));
+
+        app.app_mut()
+            .instantiate_contract(code_id, owner.clone(), &Empty {}, &[], "cw20", None)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_sudo() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+        let vault = "vault".into_addr();
+        let new_vault = "new_vault".into_addr();
+        let user1 = "user1".into_addr();
+
+        app.app_mut().init_modules(|router, _, storage| {
+            router
+                .bank
+                .init_balance(storage, &owner, coins(1000, "ujuno"))
+                .unwrap();
+        });
+
+        let code_id = CodeId::store_code(&app);
+        let stake_rewards = code_id
+            .instantiate(
+                vault.to_string(),
+                RewardAsset::Native("ujuno".to_string()),
+                flat_schedule(app.block_info().time, 100, 1000),
+                None,
+                UndistributedMode::Withdraw,
+                None,
+                0,
+                Accounting::Push,
+                None,
+                None,
+            )
+            .with_funds(&coins(1000, "ujuno"))
+            .call(&owner)
+            .unwrap();
+
+        let staked = Uint128::new(10);
+
+        stake_rewards
+            .stake_change(user1.to_string(), Uint128::zero(), Uint128::zero())
+            .call(&vault)
+            .unwrap();
+
+        // Paused by governance
+        stake_rewards.set_paused(true).unwrap();
+        app.update_block(|block| block.time = block.time.plus_seconds(10));
+
+        let err = stake_rewards
+            .claim_rewards(user1.to_string(), Some(staked), Some(staked))
+            .call(&vault)
+            .unwrap_err();
+        assert_eq!(err, ContractError::Paused);
+
+        // Emergency mode ends emissions but lets accrued rewards be claimed
+        stake_rewards.set_emergency_mode(true).unwrap();
+        assert_eq!(
+            stake_rewards.distributions().unwrap()[0].period_finish(),
+            Some(app.block_info().time)
+        );
+
+        stake_rewards
+            .claim_rewards(user1.to_string(), Some(staked), Some(staked))
+            .call(&vault)
+            .unwrap();
+
+        app.update_block(|block| block.time = block.time.plus_seconds(10));
+        stake_rewards
+            .claim_rewards(user1.to_string(), Some(staked), Some(staked))
+            .call(&vault)
+            .unwrap();
+
+        let balance = app.app().wrap().query_balance(&user1, "ujuno").unwrap();
+        assert_eq!(balance.amount, Uint128::new(100));
+
+        // Stake contract replaced by governance
+        stake_rewards.set_stake(new_vault.to_string()).unwrap();
+
+        let err = stake_rewards
+            .stake_change(user1.to_string(), staked, staked)
+            .call(&vault)
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CommonError(CommonError::Unauthorized(
+                "sender is not the stake contract".to_string()
+            ))
+        );
+
+        stake_rewards
+            .stake_change(user1.to_string(), staked, staked)
+            .call(&new_vault)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_schedule() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+        let vault = "vault".into_addr();
+        let user1 = "user1".into_addr();
+
+        app.app_mut().init_modules(|router, _, storage| {
+            router
+                .bank
+                .init_balance(storage, &owner, coins(1500, "ujuno"))
+                .unwrap();
+        });
+
+        // Halved after a 50 second pause
+        let start = app.block_info().time;
+        let schedule = vec![
+            EmissionSegment {
+                start,
+                end: start.plus_seconds(100),
+                emission: Emission::RewardsPerSecond(Uint128::new(10)),
+            },
+            EmissionSegment {
+                start: start.plus_seconds(150),
+                end: start.plus_seconds(250),
+                emission: Emission::Amount(Uint128::new(500)),
+            },
+        ];
+
+        let code_id = CodeId::store_code(&app);
+
+        let mut overlapping = schedule.clone();
+        overlapping[1].start = start.plus_seconds(50);
+        assert!(code_id
+            .instantiate(
+                vault.to_string(),
+                RewardAsset::Native("ujuno".to_string()),
+                overlapping,
+                None,
+                UndistributedMode::Withdraw,
+                None,
+                0,
+                Accounting::Push,
+                None,
+                None,
+            )
+            .with_funds(&coins(1500, "ujuno"))
+            .call(&owner)
+            .is_err());
+
+        // Funds must cover the whole schedule
+        assert!(code_id
+            .instantiate(
+                vault.to_string(),
+                RewardAsset::Native("ujuno".to_string()),
+                schedule.clone(),
+                None,
+                UndistributedMode::Withdraw,
+                None,
+                0,
+                Accounting::Push,
+                None,
+                None,
+            )
+            .with_funds(&coins(1000, "ujuno"))
+            .call(&owner)
+            .is_err());
+
+        let stake_rewards = code_id
+            .instantiate(
+                vault.to_string(),
+                RewardAsset::Native("ujuno".to_string()),
+                schedule,
+                None,
+                UndistributedMode::Withdraw,
+                None,
+                0,
+                Accounting::Push,
+                None,
+                None,
+            )
+            .with_funds(&coins(1500, "ujuno"))
+            .call(&owner)
+            .unwrap();
+
+        let staked = Uint128::new(10);
+        stake_rewards
+            .stake_change(user1.to_string(), Uint128::zero(), Uint128::zero())
+            .call(&vault)
+            .unwrap();
+
+        app.update_block(|block| block.time = block.time.plus_seconds(120));
+        assert_eq!(
+            stake_rewards.schedule(ujuno()).unwrap().rewards_per_second,
+            Uint128::zero()
+        );
+
+        app.update_block(|block| block.time = block.time.plus_seconds(80));
+        assert_eq!(
+            stake_rewards.schedule(ujuno()).unwrap().rewards_per_second,
+            Uint128::new(5)
+        );
+
+        stake_rewards
+            .claim_rewards(user1.to_string(), Some(staked), Some(staked))
+            .call(&vault)
+            .unwrap();
+
+        let balance = app.app().wrap().query_balance(&user1, "ujuno").unwrap();
+        assert_eq!(balance.amount, Uint128::new(1250));
+    }
+
+    #[test]
+    fn test_curve() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+        let vault = "vault".into_addr();
+        let user1 = "user1".into_addr();
+
+        app.app_mut().init_modules(|router, _, storage| {
+            router
+                .bank
+                .init_balance(storage, &owner, coins(1000, "ujuno"))
+                .unwrap();
+        });
+
+        let curve = EmissionCurve {
+            start: app.block_info().time,
+            amount: Uint128::new(1000),
+            decay: Decay::Exponential { half_life_sec: 100 },
+            cutoff: None,
+        };
+
+        let code_id = CodeId::store_code(&app);
+
+        // Nothing to emit
+        assert!(code_id
+            .instantiate(
+                vault.to_string(),
+                RewardAsset::Native("ujuno".to_string()),
+                vec![],
+                None,
+                UndistributedMode::Withdraw,
+                None,
+                0,
+                Accounting::Push,
+                None,
+                None,
+            )
+            .with_funds(&coins(1000, "ujuno"))
+            .call(&owner)
+            .is_err());
+
+        let stake_rewards = code_id
+            .instantiate(
+                vault.to_string(),
+                RewardAsset::Native("ujuno".to_string()),
+                vec![],
+                Some(curve),
+                UndistributedMode::Withdraw,
+                None,
+                0,
+                Accounting::Push,
+                None,
+                None,
+            )
+            .with_funds(&coins(1000, "ujuno"))
+            .call(&owner)
+            .unwrap();
+
+        let staked = Uint128::new(10);
+        stake_rewards
+            .stake_change(user1.to_string(), Uint128::zero(), Uint128::zero())
+            .call(&vault)
+            .unwrap();
+
+        // Half of the curve is emitted after a half-life
+        app.update_block(|block| block.time = block.time.plus_seconds(100));
+        assert_eq!(
+            stake_rewards.schedule(ujuno()).unwrap().rewards_per_second,
+            Uint128::new(4)
+        );
+
+        stake_rewards
+            .claim_rewards(user1.to_string(), Some(staked), Some(staked))
+            .call(&vault)
+            .unwrap();
+
+        let balance = app.app().wrap().query_balance(&user1, "ujuno").unwrap();
+        assert_eq!(balance.amount, Uint128::new(500));
+
+        // Emergency mode cuts the curve off
+        stake_rewards.set_emergency_mode(true).unwrap();
+        assert_eq!(
+            stake_rewards.distributions().unwrap()[0]
+                .curve
+                .clone()
+                .unwrap()
+                .cutoff,
+            Some(app.block_info().time)
+        );
+
+        app.update_block(|block| block.time = block.time.plus_seconds(100));
+        assert_eq!(
+            stake_rewards.schedule(ujuno()).unwrap().rewards_per_second,
+            Uint128::zero()
+        );
+    }
+
+    #[test]
+    fn test_withdraw_undistributed() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+        let user1 = "user1".into_addr();
+
+        app.app_mut().init_modules(|router, _, storage| {
+            router
+                .bank
+                .init_balance(storage, &owner, coins(1000, "ujuno"))
+                .unwrap();
+        });
+
+        let stake = mock_stake(&app, &owner);
+        let stake_rewards = CodeId::store_code(&app)
+            .instantiate(
+                stake.to_string(),
+                RewardAsset::Native("ujuno".to_string()),
+                flat_schedule(app.block_info().time, 100, 1000),
+                None,
+                UndistributedMode::Withdraw,
+                None,
+                0,
+                Accounting::Push,
+                None,
+                None,
+            )
+            .with_funds(&coins(1000, "ujuno"))
+            .call(&owner)
+            .unwrap();
+
+        let staked = Uint128::new(10);
+
+        // Nothing staked at the start
+        app.update_block(|block| block.time = block.time.plus_seconds(20));
+        stake_rewards
+            .stake_change(user1.to_string(), Uint128::zero(), Uint128::zero())
+            .call(&stake)
+            .unwrap();
+        assert_eq!(
+            stake_rewards.rewards(ujuno()).unwrap().undistributed,
+            Uint128::new(200)
+        );
+
+        // Nor in the middle
+        app.update_block(|block| block.time = block.time.plus_seconds(20));
+        stake_rewards
+            .claim_rewards(user1.to_string(), Some(staked), Some(staked))
+            .call(&stake)
+            .unwrap();
+        stake_rewards
+            .stake_change(user1.to_string(), staked, staked)
+            .call(&stake)
+            .unwrap();
+
+        app.update_block(|block| block.time = block.time.plus_seconds(20));
+        stake_rewards
+            .stake_change(user1.to_string(), Uint128::zero(), Uint128::zero())
+            .call(&stake)
+            .unwrap();
+        assert_eq!(
+            stake_rewards.rewards(ujuno()).unwrap().undistributed,
+            Uint128::new(400)
+        );
+
+        // Nor at the end
+        app.update_block(|block| block.time = block.time.plus_seconds(20));
+        stake_rewards
+            .claim_rewards(user1.to_string(), Some(staked), Some(staked))
+            .call(&stake)
+            .unwrap();
+        stake_rewards
+            .stake_change(user1.to_string(), staked, staked)
+            .call(&stake)
+            .unwrap();
+
+        app.update_block(|block| block.time = block.time.plus_seconds(40));
+        stake_rewards
+            .stake_change(user1.to_string(), Uint128::zero(), Uint128::zero())
+            .call(&stake)
+            .unwrap();
+        assert_eq!(
+            stake_rewards.rewards(ujuno()).unwrap().undistributed,
+            Uint128::new(600)
+        );
+
+        let err = stake_rewards
+            .withdraw_undistributed(None)
+            .call(&user1)
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CommonError(CommonError::Unauthorized(
+                "only the funder can withdraw".to_string()
+            ))
+        );
+
+        stake_rewards
+            .withdraw_undistributed(None)
+            .call(&owner)
+            .unwrap();
+        assert!(stake_rewards
+            .withdraw_undistributed(None)
+            .call(&owner)
+            .is_err());
+
+        let balance = app.app().wrap().query_balance(&user1, "ujuno").unwrap();
+        assert_eq!(balance.amount, Uint128::new(400));
+        let balance = app.app().wrap().query_balance(&owner, "ujuno").unwrap();
+        assert_eq!(balance.amount, Uint128::new(600));
+    }
+
+    #[test]
+    fn test_rollover_undistributed() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+        let funder = "funder".into_addr();
+        let user1 = "user1".into_addr();
+
+        app.app_mut().init_modules(|router, _, storage| {
+            router
+                .bank
+                .init_balance(storage, &owner, coins(1000, "ujuno"))
+                .unwrap();
+            router
+                .bank
+                .init_balance(storage, &funder, coins(500, "ujuno"))
+                .unwrap();
+        });
+
+        let stake = mock_stake(&app, &owner);
+        let stake_rewards = CodeId::store_code(&app)
+            .instantiate(
+                stake.to_string(),
+                RewardAsset::Native("ujuno".to_string()),
+                flat_schedule(app.block_info().time, 100, 1000),
+                None,
+                UndistributedMode::Rollover,
+                None,
+                0,
+                Accounting::Push,
+                None,
+                None,
+            )
+            .with_funds(&coins(1000, "ujuno"))
+            .call(&owner)
+            .unwrap();
+
+        let staked = Uint128::new(10);
+
+        // Re-emitted over the rest of the schedule
+        app.update_block(|block| block.time = block.time.plus_seconds(50));
+        stake_rewards
+            .stake_change(user1.to_string(), Uint128::zero(), Uint128::zero())
+            .call(&stake)
+            .unwrap();
+        assert_eq!(
+            stake_rewards.rewards(ujuno()).unwrap().undistributed,
+            Uint128::zero()
+        );
+        assert_eq!(
+            stake_rewards.schedule(ujuno()).unwrap().rewards_per_second,
+            Uint128::new(20)
+        );
+
+        let err = stake_rewards
+            .withdraw_undistributed(None)
+            .call(&owner)
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CommonError(CommonError::InvalidInput(
+                "undistributed rewards are rolled over".to_string()
+            ))
+        );
+
+        app.update_block(|block| block.time = block.time.plus_seconds(25));
+        stake_rewards
+            .claim_rewards(user1.to_string(), Some(staked), Some(staked))
+            .call(&stake)
+            .unwrap();
+        stake_rewards
+            .stake_change(user1.to_string(), staked, staked)
+            .call(&stake)
+            .unwrap();
+
+        app.update_block(|block| block.time = block.time.plus_seconds(15));
+        stake_rewards
+            .stake_change(user1.to_string(), Uint128::zero(), Uint128::zero())
+            .call(&stake)
+            .unwrap();
+        assert_eq!(
+            stake_rewards.schedule(ujuno()).unwrap().rewards_per_second,
+            Uint128::new(50)
+        );
+
+        app.update_block(|block| block.time = block.time.plus_seconds(5));
+        stake_rewards
+            .claim_rewards(user1.to_string(), Some(staked), Some(staked))
+            .call(&stake)
+            .unwrap();
+        stake_rewards
+            .stake_change(user1.to_string(), staked, staked)
+            .call(&stake)
+            .unwrap();
+
+        // Kept for the next funding once the schedule ended
+        app.update_block(|block| block.time = block.time.plus_seconds(15));
+        stake_rewards
+            .stake_change(user1.to_string(), Uint128::zero(), Uint128::zero())
+            .call(&stake)
+            .unwrap();
+        assert_eq!(
+            stake_rewards.rewards(ujuno()).unwrap().undistributed,
+            Uint128::new(250)
+        );
+
+        stake_rewards
+            .fund(Some(50))
+            .with_funds(&coins(500, "ujuno"))
+            .call(&funder)
+            .unwrap();
+        assert_eq!(
+            stake_rewards.rewards(ujuno()).unwrap().undistributed,
+            Uint128::zero()
+        );
+        assert_eq!(
+            stake_rewards.schedule(ujuno()).unwrap().rewards_per_second,
+            Uint128::new(15)
+        );
+
+        let balance = app.app().wrap().query_balance(&user1, "ujuno").unwrap();
+        assert_eq!(balance.amount, Uint128::new(750));
+    }
+
+    #[test]
+    fn test_balance_sheet() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+        let user1 = "user1".into_addr();
+        let user2 = "user2".into_addr();
+
+        app.app_mut().init_modules(|router, _, storage| {
+            router
+                .bank
+                .init_balance(storage, &owner, coins(105, "ujuno"))
+                .unwrap();
+        });
+
+        // 100 are scheduled, the 5 left over from the rate are dust
+        let stake = mock_stake(&app, &owner);
+        let stake_rewards = CodeId::store_code(&app)
+            .instantiate(
+                stake.to_string(),
+                RewardAsset::Native("ujuno".to_string()),
+                flat_schedule(app.block_info().time, 10, 105),
+                None,
+                UndistributedMode::Withdraw,
+                None,
+                0,
+                Accounting::Push,
+                None,
+                None,
+            )
+            .with_funds(&coins(105, "ujuno"))
+            .call(&owner)
+            .unwrap();
+
+        stake_rewards
+            .stake_change(user1.to_string(), Uint128::zero(), Uint128::zero())
+            .call(&stake)
+            .unwrap();
+        stake_rewards
+            .stake_change(user2.to_string(), Uint128::zero(), Uint128::one())
+            .call(&stake)
+            .unwrap();
+
+        app.update_block(|block| block.time = block.time.plus_seconds(5));
+        let err = stake_rewards.sweep_dust().call(&user1).unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CommonError(CommonError::InvalidInput(
+                "reward period has not finished".to_string()
+            ))
+        );
+
+        // Splitting 100 by thirds floors away another 1
+        app.update_block(|block| block.time = block.time.plus_seconds(5));
+        stake_rewards
+            .claim_rewards(
+                user1.to_string(),
+                Some(Uint128::one()),
+                Some(Uint128::new(3)),
+            )
+            .call(&stake)
+            .unwrap();
+        stake_rewards
+            .claim_rewards(
+                user2.to_string(),
+                Some(Uint128::new(2)),
+                Some(Uint128::new(3)),
+            )
+            .call(&stake)
+            .unwrap();
+
+        let balance_sheet = stake_rewards.balance_sheet(ujuno()).unwrap();
+        assert_eq!(balance_sheet.funded, Uint128::new(105));
+        assert_eq!(balance_sheet.claimed, Uint128::new(99));
+        assert_eq!(balance_sheet.owed, Uint128::zero());
+        assert_eq!(balance_sheet.remaining, Uint128::zero());
+        assert_eq!(balance_sheet.balance, Uint128::new(6));
+        assert_eq!(balance_sheet.dust, Uint128::new(6));
+
+        stake_rewards.sweep_dust().call(&user1).unwrap();
+        assert!(stake_rewards.sweep_dust().call(&user1).is_err());
+
+        let balance = app.app().wrap().query_balance(&owner, "ujuno").unwrap();
+        assert_eq!(balance.amount, Uint128::new(6));
+
+        let balance_sheet = stake_rewards.balance_sheet(ujuno()).unwrap();
+        assert_eq!(balance_sheet.distributable, Uint128::new(99));
+        assert_eq!(balance_sheet.swept, Uint128::new(6));
+        assert_eq!(balance_sheet.balance, Uint128::zero());
+        assert_eq!(balance_sheet.dust, Uint128::zero());
+    }
+
+    #[test]
+    fn test_reclaim() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+        let user1 = "user1".into_addr();
+        let user2 = "user2".into_addr();
+
+        app.app_mut().init_modules(|router, _, storage| {
+            router
+                .bank
+                .init_balance(storage, &owner, coins(1000, "ujuno"))
+                .unwrap();
+        });
+
+        let stake = mock_stake_amounts(&app, &owner);
+        let stake_rewards = CodeId::store_code(&app)
+            .instantiate(
+                stake.to_string(),
+                RewardAsset::Native("ujuno".to_string()),
+                flat_schedule(app.block_info().time, 100, 1000),
+                None,
+                UndistributedMode::Withdraw,
+                None,
+                50,
+                Accounting::Push,
+                None,
+                None,
+            )
+            .with_funds(&coins(1000, "ujuno"))
+            .call(&owner)
+            .unwrap();
+
+        let set_staked_amount = |address: &Addr, amount: u128| {
+            app.app_mut()
+                .execute_contract(
+                    owner.clone(),
+                    stake.clone(),
+                    &(address.to_string(), Uint128::new(amount)),
+                    &[],
+                )
+                .unwrap();
+        };
+
+        for (user, total_staked) in [(&user1, 0), (&user2, 1)] {
+            stake_rewards
+                .stake_change(
+                    user.to_string(),
+                    Uint128::zero(),
+                    Uint128::new(total_staked),
+                )
+                .call(&stake)
+                .unwrap();
+            set_staked_amount(user, 1);
+        }
+
+        // user2 unstakes once the schedule finished, user1 stays staked
+        app.update_block(|block| block.time = block.time.plus_seconds(100));
+        stake_rewards
+            .stake_change(user2.to_string(), Uint128::one(), Uint128::new(2))
+            .call(&stake)
+            .unwrap();
+        set_staked_amount(&user2, 0);
+
+        let addresses = vec![user1.to_string(), user2.to_string()];
+
+        app.update_block(|block| block.time = block.time.plus_seconds(20));
+        let err = stake_rewards
+            .reclaim(addresses.clone())
+            .call(&owner)
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CommonError(CommonError::InvalidInput(
+                "grace period has not passed".to_string()
+            ))
+        );
+
+        app.update_block(|block| block.time = block.time.plus_seconds(30));
+        let err = stake_rewards
+            .reclaim(addresses.clone())
+            .call(&user1)
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CommonError(CommonError::Unauthorized(
+                "only the funder can reclaim".to_string()
+            ))
+        );
+        assert!(stake_rewards
+            .reclaim(vec![user2.to_string(); MAX_RECLAIM_ADDRESSES + 1])
+            .call(&owner)
+            .is_err());
+
+        // Only the rewards left by the inactive user2 go back to the funder
+        stake_rewards
+            .reclaim(addresses.clone())
+            .call(&owner)
+            .unwrap();
+        assert!(stake_rewards.reclaim(addresses).call(&owner).is_err());
+
+        let balance = app.app().wrap().query_balance(&owner, "ujuno").unwrap();
+        assert_eq!(balance.amount, Uint128::new(500));
+
+        let balance_sheet = stake_rewards.balance_sheet(ujuno()).unwrap();
+        assert_eq!(balance_sheet.forfeited, Uint128::new(500));
+        assert_eq!(balance_sheet.reclaimed, Uint128::new(500));
+        assert_eq!(balance_sheet.owed, Uint128::new(500));
+        assert_eq!(balance_sheet.distributable, Uint128::new(1000));
+
+        // The active staker keeps the rewards accrued
+        for user in [&user1, &user2] {
+            stake_rewards
+                .claim_rewards(user.to_string(), Some(Uint128::one()), Some(Uint128::one()))
+                .call(&stake)
+                .unwrap();
+        }
+
+        let balance = app.app().wrap().query_balance(&user1, "ujuno").unwrap();
+        assert_eq!(balance.amount, Uint128::new(500));
+        let balance = app.app().wrap().query_balance(&user2, "ujuno").unwrap();
+        assert_eq!(balance.amount, Uint128::zero());
+
+        let balance_sheet = stake_rewards.balance_sheet(ujuno()).unwrap();
+        assert_eq!(balance_sheet.owed, Uint128::zero());
+        assert_eq!(balance_sheet.dust, Uint128::zero());
+    }
+
+    #[test]
+    fn test_multiple_reward_assets() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+        let user1 = "user1".into_addr();
+
+        app.app_mut().init_modules(|router, _, storage| {
+            router
+                .bank
+                .init_balance(
+                    storage,
+                    &owner,
+                    vec![coin(1500, "ujuno"), coin(1000, "uatom")],
+                )
+                .unwrap();
+        });
+
+        let stake = mock_stake(&app, &owner);
+
+        let start = app.block_info().time;
+        let stake_rewards = CodeId::store_code(&app)
+            .instantiate(
+                stake.to_string(),
+                ujuno(),
+                flat_schedule(start, 100, 1000),
+                None,
+                UndistributedMode::Withdraw,
+                None,
+                0,
+                Accounting::Push,
+                None,
+                None,
+            )
+            .with_funds(&coins(1000, "ujuno"))
+            .call(&owner)
+            .unwrap();
+
+        let uatom = RewardAsset::Native("uatom".to_string());
+
+        // Only the funder adds reward assets, each asset once
+        assert!(stake_rewards
+            .add_reward_asset(uatom.clone(), flat_schedule(start, 50, 500), None)
+            .call(&user1)
+            .is_err());
+        assert!(stake_rewards
+            .add_reward_asset(ujuno(), flat_schedule(start, 50, 500), None)
+            .with_funds(&coins(500, "ujuno"))
+            .call(&owner)
+            .is_err());
+
+        stake_rewards
+            .add_reward_asset(uatom.clone(), flat_schedule(start, 50, 500), None)
+            .with_funds(&coins(500, "uatom"))
+            .call(&owner)
+            .unwrap();
+        assert_eq!(stake_rewards.distributions().unwrap().len(), 2);
+
+        stake_rewards
+            .stake_change(user1.to_string(), Uint128::zero(), Uint128::zero())
+            .call(&stake)
+            .unwrap();
+
+        app.update_block(|block| block.time = block.time.plus_seconds(100));
+
+        // Every asset is paid in one claim
+        stake_rewards
+            .claim_rewards(
+                user1.to_string(),
+                Some(Uint128::new(10)),
+                Some(Uint128::new(10)),
+            )
+            .call(&stake)
+            .unwrap();
+
+        let balances = app.app().wrap().query_all_balances(&user1).unwrap();
+        assert_eq!(balances, vec![coin(500, "uatom"), coin(1000, "ujuno")]);
+
+        let latest_user_rewards = stake_rewards.latest_user_reward(user1.to_string()).unwrap();
+        assert_eq!(latest_user_rewards.len(), 2);
+        assert!(latest_user_rewards
+            .iter()
+            .all(|reward| reward.user_reward.pending_rewards.is_zero()));
+
+        // Fundings go to the schedule of their asset
+        stake_rewards
+            .fund(Some(100))
+            .with_funds(&coins(300, "uatom"))
+            .call(&owner)
+            .unwrap();
+        assert_eq!(
+            stake_rewards.schedule(uatom).unwrap().rewards_per_second,
+            Uint128::new(3)
+        );
+        assert_eq!(
+            stake_rewards.schedule(ujuno()).unwrap().rewards_per_second,
+            Uint128::zero()
+        );
+    }
+
+    #[test]
+    fn test_pull_accounting() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+        let user1 = "user1".into_addr();
+        let user2 = "user2".into_addr();
+
+        app.app_mut().init_modules(|router, _, storage| {
+            router
+                .bank
+                .init_balance(storage, &owner, coins(2000, "ujuno"))
+                .unwrap();
+        });
+
+        let stake = mock_stake(&app, &owner);
+        let code_id = CodeId::store_code(&app);
+
+        let instantiate = |accounting: Accounting| {
+            code_id
+                .instantiate(
+                    stake.to_string(),
+                    ujuno(),
+                    flat_schedule(app.block_info().time, 100, 1000),
+                    None,
+                    UndistributedMode::Withdraw,
+                    None,
+                    0,
+                    accounting,
+                    None,
+                    None,
+                )
+                .with_funds(&coins(1000, "ujuno"))
+                .call(&owner)
+                .unwrap()
+        };
+        let push_rewards = instantiate(Accounting::Push);
+        let pull_rewards = instantiate(Accounting::Pull);
+
+        for stake_rewards in [&push_rewards, &pull_rewards] {
+            stake_rewards
+                .stake_change(user1.to_string(), Uint128::zero(), Uint128::zero())
+                .call(&stake)
+                .unwrap();
+        }
+
+        app.update_block(|block| block.time = block.time.plus_seconds(100));
+
+        // Stakers only claim directly in pull accounting
+        assert!(push_rewards
+            .claim_rewards(user1.to_string(), None, None)
+            .call(&user1)
+            .is_err());
+
+        // The stake contract must report the staked amounts
+        assert!(pull_rewards
+            .claim_rewards(user1.to_string(), None, None)
+            .call(&stake)
+            .is_err());
+
+        // Stakers cannot claim for others or report their own amounts
+        assert!(pull_rewards
+            .claim_rewards(user1.to_string(), None, None)
+            .call(&user2)
+            .is_err());
+        assert!(pull_rewards
+            .claim_rewards(
+                user1.to_string(),
+                Some(Uint128::new(20)),
+                Some(Uint128::new(20))
+            )
+            .call(&user1)
+            .is_err());
+
+        // The stake contract reports 10 of 10 staked
+        let latest_user_rewards = pull_rewards.latest_user_reward(user1.to_string()).unwrap();
+        assert_eq!(
+            latest_user_rewards[0].user_reward.pending_rewards,
+            Uint128::new(1000)
+        );
+
+        pull_rewards
+            .claim_rewards(user1.to_string(), None, None)
+            .call(&user1)
+            .unwrap();
+
+        let balance = app.app().wrap().query_balance(&user1, "ujuno").unwrap();
+        assert_eq!(balance.amount, Uint128::new(1000));
+    }
+
+    #[test]
+    fn test_vesting() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+        let user1 = "user1".into_addr();
+
+        app.app_mut().init_modules(|router, _, storage| {
+            router
+                .bank
+                .init_balance(storage, &owner, coins(1000, "ujuno"))
+                .unwrap();
+        });
+
+        let stake = mock_stake(&app, &owner);
+        let code_id = CodeId::store_code(&app);
+
+        let instantiate = |vesting: Vesting| {
+            code_id
+                .instantiate(
+                    stake.to_string(),
+                    ujuno(),
+                    flat_schedule(app.block_info().time, 100, 1000),
+                    None,
+                    UndistributedMode::Withdraw,
+                    None,
+                    0,
+                    Accounting::Push,
+                    Some(vesting),
+                    None,
+                )
+                .with_funds(&coins(1000, "ujuno"))
+                .call(&owner)
+        };
+
+        // The cliff must not exceed the duration
+        assert!(instantiate(Vesting {
+            duration_sec: 100,
+            cliff_sec: Some(200),
+        })
+        .is_err());
+
+        let stake_rewards = instantiate(Vesting {
+            duration_sec: 100,
+            cliff_sec: Some(20),
+        })
+        .unwrap();
+
+        stake_rewards
+            .stake_change(user1.to_string(), Uint128::zero(), Uint128::zero())
+            .call(&stake)
+            .unwrap();
+
+        app.update_block(|block| block.time = block.time.plus_seconds(100));
+
+        // Claimed rewards are locked instead of paid out
+        stake_rewards
+            .claim_rewards(
+                user1.to_string(),
+                Some(Uint128::new(10)),
+                Some(Uint128::new(10)),
+            )
+            .call(&stake)
+            .unwrap();
+
+        let balance = app.app().wrap().query_balance(&user1, "ujuno").unwrap();
+        assert_eq!(balance.amount, Uint128::zero());
+
+        let vesting = stake_rewards.vesting(user1.to_string(), ujuno()).unwrap();
+        assert_eq!(vesting.vested, Uint128::zero());
+        assert_eq!(vesting.locked, Uint128::new(1000));
+
+        // Nothing vests before the cliff
+        app.update_block(|block| block.time = block.time.plus_seconds(10));
+        assert!(stake_rewards.withdraw_vested().call(&user1).is_err());
+
+        app.update_block(|block| block.time = block.time.plus_seconds(40));
+        stake_rewards.withdraw_vested().call(&user1).unwrap();
+
+        let balance = app.app().wrap().query_balance(&user1, "ujuno").unwrap();
+        assert_eq!(balance.amount, Uint128::new(500));
+
+        let vesting = stake_rewards.vesting(user1.to_string(), ujuno()).unwrap();
+        assert_eq!(vesting.vested, Uint128::new(500));
+        assert_eq!(vesting.locked, Uint128::new(500));
+        assert_eq!(vesting.withdrawn, Uint128::new(500));
+
+        // Locked rewards are owed to the staker, not dust or reclaimable
+        let balance_sheet = stake_rewards.balance_sheet(ujuno()).unwrap();
+        assert_eq!(balance_sheet.claimed, Uint128::new(1000));
+        assert_eq!(balance_sheet.vesting, Uint128::new(500));
+        assert_eq!(balance_sheet.dust, Uint128::zero());
+        assert!(stake_rewards.reclaim(vec![]).call(&owner).is_err());
+
+        app.update_block(|block| block.time = block.time.plus_seconds(100));
+        stake_rewards.withdraw_vested().call(&user1).unwrap();
+
+        let balance = app.app().wrap().query_balance(&user1, "ujuno").unwrap();
+        assert_eq!(balance.amount, Uint128::new(1000));
+
+        let vesting = stake_rewards.vesting(user1.to_string(), ujuno()).unwrap();
+        assert_eq!(vesting.locked, Uint128::zero());
+        assert_eq!(vesting.withdrawn, Uint128::new(1000));
+        assert!(stake_rewards.withdraw_vested().call(&user1).is_err());
+    }
+
+    #[test]
+    fn test_vesting_tranches() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+        let user1 = "user1".into_addr();
+
+        app.app_mut().init_modules(|router, _, storage| {
+            router
+                .bank
+                .init_balance(storage, &owner, coins(1000, "ujuno"))
+                .unwrap();
+        });
+
+        let stake = mock_stake(&app, &owner);
+        let stake_rewards = CodeId::store_code(&app)
+            .instantiate(
+                stake.to_string(),
+                ujuno(),
+                flat_schedule(app.block_info().time, 100, 1000),
+                None,
+                UndistributedMode::Withdraw,
+                None,
+                0,
+                Accounting::Push,
+                Some(Vesting {
+                    duration_sec: 100,
+                    cliff_sec: Some(20),
+                }),
+                None,
+            )
+            .with_funds(&coins(1000, "ujuno"))
+            .call(&owner)
+            .unwrap();
+
+        stake_rewards
+            .stake_change(user1.to_string(), Uint128::zero(), Uint128::zero())
+            .call(&stake)
+            .unwrap();
+
+        let claim = || {
+            stake_rewards
+                .claim_rewards(
+                    user1.to_string(),
+                    Some(Uint128::new(10)),
+                    Some(Uint128::new(10)),
+                )
+                .call(&stake)
+                .unwrap();
+        };
+
+        // Two claims closer together than the cliff
+        app.update_block(|block| block.time = block.time.plus_seconds(50));
+        claim();
+        app.update_block(|block| block.time = block.time.plus_seconds(10));
+        claim();
+
+        // The first claim keeps vesting past its cliff, the second one is still before its own
+        app.update_block(|block| block.time = block.time.plus_seconds(10));
+        let vesting = stake_rewards.vesting(user1.to_string(), ujuno()).unwrap();
+        assert_eq!(vesting.vested, Uint128::new(100));
+        assert_eq!(vesting.locked, Uint128::new(500));
+
+        app.update_block(|block| block.time = block.time.plus_seconds(10));
+        stake_rewards.withdraw_vested().call(&user1).unwrap();
+        let balance = app.app().wrap().query_balance(&user1, "ujuno").unwrap();
+        assert_eq!(balance.amount, Uint128::new(170));
+
+        // Fully vested tranches are dropped on the next claim
+        app.update_block(|block| block.time = block.time.plus_seconds(100));
+        claim();
+        let vesting = stake_rewards.vesting(user1.to_string(), ujuno()).unwrap();
+        assert_eq!(vesting.vested, Uint128::new(600));
+        assert_eq!(vesting.locked, Uint128::new(400));
+        assert_eq!(vesting.withdrawn, Uint128::new(170));
+    }
+
+    #[test]
+    fn test_top_up() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+        let vault = "vault".into_addr();
+
+        app.app_mut().init_modules(|router, _, storage| {
+            router
+                .bank
+                .init_balance(storage, &owner, coins(1500, "ujuno"))
+                .unwrap();
+            router
+                .bank
+                .init_balance(storage, &vault, coins(1000, "ujuno"))
+                .unwrap();
+        });
+
+        let stake_rewards = CodeId::store_code(&app)
+            .instantiate(
+                vault.to_string(),
+                RewardAsset::Native("ujuno".to_string()),
+                flat_schedule(app.block_info().time, 100, 1000),
+                None,
+                UndistributedMode::Withdraw,
+                None,
+                0,
+                Accounting::Push,
+                None,
+                None,
+            )
+            .with_funds(&coins(1000, "ujuno"))
+            .call(&owner)
+            .unwrap();
+
+        // Only the stake contract tops up
+        assert!(stake_rewards
+            .top_up(ujuno(), Uint128::new(500), Uint128::zero())
+            .with_funds(&coins(500, "ujuno"))
+            .call(&owner)
+            .is_err());
+
+        // Funds must match the amount
+        assert!(stake_rewards
+            .top_up(ujuno(), Uint128::new(500), Uint128::zero())
+            .with_funds(&coins(400, "ujuno"))
+            .call(&vault)
+            .is_err());
+
+        // Half way through, the 500 left are topped up with another 500
+        app.update_block(|block| block.time = block.time.plus_seconds(50));
+        stake_rewards
+            .top_up(ujuno(), Uint128::new(500), Uint128::zero())
+            .with_funds(&coins(500, "ujuno"))
+            .call(&vault)
+            .unwrap();
+
+        assert_eq!(
+            stake_rewards.schedule(ujuno()).unwrap().rewards_per_second,
+            Uint128::new(20)
+        );
+
+        // Once the period finished, top ups are kept undistributed for the funder
+        app.update_block(|block| block.time = block.time.plus_seconds(50));
+        stake_rewards
+            .top_up(ujuno(), Uint128::new(500), Uint128::zero())
+            .with_funds(&coins(500, "ujuno"))
+            .call(&vault)
+            .unwrap();
+
+        let balance_sheet = stake_rewards.balance_sheet(ujuno()).unwrap();
+        assert_eq!(balance_sheet.funded, Uint128::new(2000));
+        // Nothing was staked, so the whole funding is undistributed
+        assert_eq!(balance_sheet.undistributed, Uint128::new(2000));
+        assert_eq!(balance_sheet.dust, Uint128::zero());
+    }
+
+    #[test]
+    fn test_fund() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+        let funder = "funder".into_addr();
+
+        app.app_mut().init_modules(|router, _, storage| {
+            router
+                .bank
+                .init_balance(storage, &owner, coins(1000, "ujuno"))
+                .unwrap();
+            router
+                .bank
+                .init_balance(storage, &funder, coins(2000, "ujuno"))
+                .unwrap();
+        });
+
+        let stake = mock_stake(&app, &owner);
+        let start = app.block_info().time;
+
+        let stake_rewards = CodeId::store_code(&app)
+            .instantiate(
+                stake.to_string(),
+                RewardAsset::Native("ujuno".to_string()),
+                flat_schedule(start, 100, 1000),
+                None,
+                UndistributedMode::Withdraw,
+                None,
+                0,
+                Accounting::Push,
+                None,
+                None,
+            )
+            .with_funds(&coins(1000, "ujuno"))
+            .call(&owner)
+            .unwrap();
+
+        assert!(stake_rewards.fund(None).call(&funder).is_err());
+
+        // Half way through, another 500 are spread over the 50 seconds left and 50 more
+        app.update_block(|block| block.time = block.time.plus_seconds(50));
+        let now = app.block_info().time;
+        stake_rewards
+            .fund(Some(50))
+            .with_funds(&coins(500, "ujuno"))
+            .call(&funder)
+            .unwrap();
+
+        let schedule = stake_rewards.schedule(ujuno()).unwrap();
+        assert_eq!(schedule.rewards_per_second, Uint128::new(15));
+        assert_eq!(
+            schedule.segments,
+            vec![
+                ScheduleSegment {
+                    start: now,
+                    end: start.plus_seconds(100),
+                    rewards_per_second: Uint128::new(15),
+                },
+                ScheduleSegment {
+                    start: start.plus_seconds(100),
+                    end: start.plus_seconds(150),
+                    rewards_per_second: Uint128::new(5),
+                },
+            ]
+        );
+
+        // Checkpointed against the total reported by the stake contract
+        let rewards = stake_rewards.rewards(ujuno()).unwrap();
+        assert_eq!(rewards.last_update, app.block_info().time);
+        assert!(!rewards.rewards_per_token.is_zero());
+
+        // A finished period can only restart with an extension
+        app.update_block(|block| block.time = block.time.plus_seconds(100));
+        assert!(stake_rewards
+            .fund(None)
+            .with_funds(&coins(500, "ujuno"))
+            .call(&funder)
+            .is_err());
+
+        stake_rewards
+            .fund(Some(100))
+            .with_funds(&coins(500, "ujuno"))
+            .call(&funder)
+            .unwrap();
+
+        let schedule = stake_rewards.schedule(ujuno()).unwrap();
+        assert_eq!(schedule.rewards_per_second, Uint128::new(5));
+        assert_eq!(schedule.segments.len(), 1);
+        assert_eq!(
+            stake_rewards.distributions().unwrap()[0].period_finish(),
+            Some(app.block_info().time.plus_seconds(100))
+        );
+    }
+
+    #[test]
+    fn test_fund_cw20() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+
+        let stake = mock_stake(&app, &owner);
+        let cw20 = mock_cw20(&app, &owner);
+
+        let stake_rewards = CodeId::store_code(&app)
+            .instantiate(
+                stake.to_string(),
+                RewardAsset::Cw20(cw20.clone()),
+                flat_schedule(app.block_info().time, 100, 1000),
+                None,
+                UndistributedMode::Withdraw,
+                None,
+                0,
+                Accounting::Push,
+                None,
+                Some(Uint128::new(1000)),
+            )
+            .call(&owner)
+            .unwrap();
+
+        let fund_msg = to_json_binary(&ReceiveMsg::Fund {
+            extra_duration_sec: None,
+        })
+        .unwrap();
+
+        // Cw20 rewards are only funded through the token
+        assert!(stake_rewards.fund(None).call(&owner).is_err());
+        assert!(stake_rewards
+            .receive(owner.to_string(), Uint128::new(1000), fund_msg.clone())
+            .call(&owner)
+            .is_err());
+
+        stake_rewards
+            .receive(owner.to_string(), Uint128::new(1000), fund_msg)
+            .call(&cw20)
+            .unwrap();
+
+        assert_eq!(
+            stake_rewards
+                .schedule(RewardAsset::Cw20(cw20.clone()))
+                .unwrap()
+                .rewards_per_second,
+            Uint128::new(20)
+        );
+    }
+
+    #[test]
+    fn test_instantiate_cw20() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+
+        let stake = mock_stake(&app, &owner);
+        let cw20 = mock_cw20(&app, &owner);
+        let code_id = CodeId::store_code(&app);
+
+        let instantiate = |scheduled_amount: u128, cw20_amount: Option<u128>| {
+            code_id
+                .instantiate(
+                    stake.to_string(),
+                    RewardAsset::Cw20(cw20.clone()),
+                    flat_schedule(app.block_info().time, 100, scheduled_amount),
+                    None,
+                    UndistributedMode::Withdraw,
+                    None,
+                    0,
+                    Accounting::Push,
+                    None,
+                    cw20_amount.map(Uint128::new),
+                )
+                .call(&owner)
+        };
+
+        // The sent amount must be given, match the schedule and be held by the contract
+        assert!(instantiate(600, None).is_err());
+        assert!(instantiate(600, Some(500)).is_err());
+        assert!(instantiate(600, Some(700)).is_err());
+        assert!(instantiate(2000, Some(2000)).is_err());
+
+        let stake_rewards = instantiate(600, Some(600)).unwrap();
+
+        // Tokens beyond the sent amount are not counted as funded
+        let balance_sheet = stake_rewards
+            .balance_sheet(RewardAsset::Cw20(cw20.clone()))
+            .unwrap();
+        assert_eq!(balance_sheet.funded, Uint128::new(600));
+        assert_eq!(balance_sheet.remaining, Uint128::new(600));
+        assert_eq!(balance_sheet.balance, Uint128::new(1000));
+        assert_eq!(balance_sheet.dust, Uint128::new(400));
+
+        // Further cw20 reward assets are added by the funder sending them
+        let cw20_2 = mock_cw20(&app, &owner);
+        let add_msg = to_json_binary(&ReceiveMsg::AddRewardAsset {
+            schedule: flat_schedule(app.block_info().time, 100, 500),
+            curve: None,
+        })
+        .unwrap();
+
+        assert!(stake_rewards
+            .add_reward_asset(
+                RewardAsset::Cw20(cw20_2.clone()),
+                flat_schedule(app.block_info().time, 100, 500),
+                None,
+            )
+            .call(&owner)
+            .is_err());
+        stake_rewards
+            .add_reward_token(cw20_2.to_string())
+            .call(&owner)
+            .unwrap();
+        let err = stake_rewards
+            .receive(stake.to_string(), Uint128::new(500), add_msg.clone())
+            .call(&cw20_2)
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CommonError(CommonError::Unauthorized(
+                "only the funder can add reward assets".to_string()
+            ))
+        );
+
+        stake_rewards
+            .receive(owner.to_string(), Uint128::new(500), add_msg)
+            .call(&cw20_2)
+            .unwrap();
+        let balance_sheet = stake_rewards
+            .balance_sheet(RewardAsset::Cw20(cw20_2))
+            .unwrap();
+        assert_eq!(balance_sheet.funded, Uint128::new(500));
+        assert_eq!(balance_sheet.dust, Uint128::new(500));
+    }
+
+    #[test]
+    fn test_reward_tokens() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+        let user1 = "user1".into_addr();
+
+        let stake = mock_stake(&app, &owner);
+        let cw20 = mock_cw20(&app, &owner);
+        let unlisted_cw20 = mock_cw20(&app, &owner);
+
+        let stake_rewards = CodeId::store_code(&app)
+            .instantiate(
+                stake.to_string(),
+                RewardAsset::Cw20(cw20.clone()),
+                flat_schedule(app.block_info().time, 100, 1000),
+                None,
+                UndistributedMode::Withdraw,
+                None,
+                0,
+                Accounting::Push,
+                None,
+                Some(Uint128::new(1000)),
+            )
+            .call(&owner)
+            .unwrap();
+
+        let add_msg = to_json_binary(&ReceiveMsg::AddRewardAsset {
+            schedule: flat_schedule(app.block_info().time, 100, 500),
+            curve: None,
+        })
+        .unwrap();
+
+        // A token cannot add itself by reporting the funder as the sender
+        let err = stake_rewards
+            .receive(owner.to_string(), Uint128::new(500), add_msg.clone())
+            .call(&unlisted_cw20)
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CommonError(CommonError::Unauthorized(
+                "sender is not an approved reward token".to_string()
+            ))
+        );
+        assert_eq!(stake_rewards.distributions().unwrap().len(), 1);
+
+        // Only the funder approves reward tokens, once
+        let err = stake_rewards
+            .add_reward_token(unlisted_cw20.to_string())
+            .call(&user1)
+            .unwrap_err();
+        assert_eq!(
+            err,
+            ContractError::CommonError(CommonError::Unauthorized(
+                "only the funder can manage reward tokens".to_string()
+            ))
+        );
+        stake_rewards
+            .add_reward_token(unlisted_cw20.to_string())
+            .call(&owner)
+            .unwrap();
+        assert!(stake_rewards
+            .add_reward_token(unlisted_cw20.to_string())
+            .call(&owner)
+            .is_err());
+        assert_eq!(
+            stake_rewards.reward_tokens().unwrap(),
+            vec![unlisted_cw20.clone()]
+        );
+
+        // Removed approvals no longer let the token add itself
+        assert!(stake_rewards
+            .remove_reward_token(unlisted_cw20.to_string())
+            .call(&user1)
+            .is_err());
+        stake_rewards
+            .remove_reward_token(unlisted_cw20.to_string())
+            .call(&owner)
+            .unwrap();
+        assert!(stake_rewards.reward_tokens().unwrap().is_empty());
+        assert!(stake_rewards
+            .receive(owner.to_string(), Uint128::new(500), add_msg.clone())
+            .call(&unlisted_cw20)
+            .is_err());
+
+        stake_rewards
+            .add_reward_token(unlisted_cw20.to_string())
+            .call(&owner)
+            .unwrap();
+        stake_rewards
+            .receive(owner.to_string(), Uint128::new(500), add_msg)
+            .call(&unlisted_cw20)
+            .unwrap();
+        assert_eq!(stake_rewards.distributions().unwrap().len(), 2);
     }
 }
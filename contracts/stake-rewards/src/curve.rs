@@ -0,0 +1,300 @@
+use cosmwasm_schema::cw_serde;
+use cosmwasm_std::{ensure, Isqrt, Timestamp, Uint128, Uint256};
+use std::{cmp::min, convert::TryInto, fmt::Display};
+use uju_cw2_common::error::CommonError;
+
+use crate::error::ContractError;
+
+/// Bits of the fraction of a half-life resolved by the exponential curve
+const FRACTION_BITS: u32 = 64;
+
+#[cw_serde]
+pub enum Decay {
+    /// The rate falls linearly to zero over the duration
+    Linear { duration_sec: u64 },
+    /// The rate halves every half-life, the amount is approached asymptotically
+    Exponential { half_life_sec: u64 },
+}
+
+/// A closed-form emission curve, emitting `amount` in total from `start`
+#[cw_serde]
+pub struct EmissionCurve {
+    pub start: Timestamp,
+    pub amount: Uint128,
+    pub decay: Decay,
+    /// Emissions stop early at the cutoff, e.g. in emergency mode
+    pub cutoff: Option<Timestamp>,
+}
+
+impl Display for EmissionCurve {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.decay {
+            Decay::Linear { duration_sec } => write!(
+                f,
+                "linear:{}:{}:{}",
+                self.start.seconds(),
+                duration_sec,
+                self.amount
+            )?,
+            Decay::Exponential { half_life_sec } => write!(
+                f,
+                "exponential:{}:{}:{}",
+                self.start.seconds(),
+                half_life_sec,
+                self.amount
+            )?,
+        }
+        if let Some(cutoff) = self.cutoff {
+            write!(f, ":{}", cutoff.seconds())?;
+        }
+        Ok(())
+    }
+}
+
+impl EmissionCurve {
+    pub fn validate(&self, now: Timestamp) -> Result<(), ContractError> {
+        ensure!(
+            self.start >= now,
+            CommonError::InvalidInput("curve start must be at least current time".to_string())
+        );
+        ensure!(
+            !self.amount.is_zero(),
+            CommonError::InvalidInput("curve amount must be greater than zero".to_string())
+        );
+
+        let period_sec = match self.decay {
+            Decay::Linear { duration_sec } => duration_sec,
+            Decay::Exponential { half_life_sec } => half_life_sec,
+        };
+        ensure!(
+            period_sec > 0,
+            CommonError::InvalidInput("curve duration must be greater than zero".to_string())
+        );
+
+        if let Some(cutoff) = self.cutoff {
+            ensure!(
+                cutoff > self.start,
+                CommonError::InvalidInput("curve cutoff must be after its start".to_string())
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Rewards emitted by the curve up to the given time. Never decreases over time and never
+    /// exceeds the amount.
+    pub fn emitted_at(&self, time: Timestamp) -> Result<Uint128, ContractError> {
+        let time = self.cutoff.map_or(time, |cutoff| min(cutoff, time));
+        if time <= self.start {
+            return Ok(Uint128::zero());
+        }
+
+        let elapsed_sec = time.seconds() - self.start.seconds();
+        match self.decay {
+            Decay::Linear { duration_sec } => {
+                linear_emitted(self.amount, elapsed_sec, duration_sec)
+            }
+            Decay::Exponential { half_life_sec } => {
+                exponential_emitted(self.amount, elapsed_sec, half_life_sec)
+            }
+        }
+    }
+
+    /// Rewards emitted by the curve between two times
+    pub fn emissions_between(
+        &self,
+        from: Timestamp,
+        to: Timestamp,
+    ) -> Result<Uint128, ContractError> {
+        if to <= from {
+            return Ok(Uint128::zero());
+        }
+
+        Ok(self.emitted_at(to)?.checked_sub(self.emitted_at(from)?)?)
+    }
+
+    /// Rewards emitted over the second starting at the given time
+    pub fn rewards_per_second_at(&self, time: Timestamp) -> Result<Uint128, ContractError> {
+        self.emissions_between(time, time.plus_seconds(1))
+    }
+}
+
+/// `amount * x * (2 - x)` with `x` the elapsed fraction of the duration, the integral of a
+/// rate falling linearly to zero
+fn linear_emitted(
+    amount: Uint128,
+    elapsed_sec: u64,
+    duration_sec: u64,
+) -> Result<Uint128, ContractError> {
+    if elapsed_sec >= duration_sec {
+        return Ok(amount);
+    }
+
+    let elapsed = Uint256::from(elapsed_sec);
+    let duration = Uint256::from(duration_sec);
+    let area = elapsed.checked_mul(
+        duration
+            .checked_mul(Uint256::from(2u8))?
+            .checked_sub(elapsed)?,
+    )?;
+
+    Ok(Uint256::from(amount)
+        .checked_mul(area)?
+        .checked_div(duration.checked_mul(duration)?)?
+        .try_into()?)
+}
+
+/// `amount * (1 - 2^(-elapsed / half_life))`. The remaining factor is computed in 128 bit
+/// fixed point as the product of `2^(-2^-k)` over the set bits of the fraction of a half-life,
+/// each root being the square root of the previous one, then halved for every whole
+/// half-life. Flooring errors stay far below the step between neighbouring fractions, so
+/// emissions never decrease.
+fn exponential_emitted(
+    amount: Uint128,
+    elapsed_sec: u64,
+    half_life_sec: u64,
+) -> Result<Uint128, ContractError> {
+    let half_lives = elapsed_sec / half_life_sec;
+    if half_lives >= 128 {
+        return Ok(amount);
+    }
+
+    let mut fraction =
+        (u128::from(elapsed_sec % half_life_sec) << FRACTION_BITS) / u128::from(half_life_sec);
+
+    let one = Uint256::one() << 128;
+    let mut factor = one;
+    let mut root = one >> 1;
+    for bit in (0..FRACTION_BITS).rev() {
+        if fraction == 0 {
+            break;
+        }
+
+        root = root.checked_mul(one)?.isqrt();
+        if (fraction >> bit) & 1 == 1 {
+            factor = factor.checked_mul(root)? >> 128;
+            fraction ^= 1 << bit;
+        }
+    }
+
+    let remaining: Uint128 =
+        (Uint256::from(amount).checked_mul(factor)? >> (128 + half_lives as u32)).try_into()?;
+
+    Ok(amount.checked_sub(remaining)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn curve(amount: u128, decay: Decay) -> EmissionCurve {
+        EmissionCurve {
+            start: Timestamp::from_seconds(1000),
+            amount: Uint128::new(amount),
+            decay,
+            cutoff: None,
+        }
+    }
+
+    fn at(elapsed_sec: u64) -> Timestamp {
+        Timestamp::from_seconds(1000 + elapsed_sec)
+    }
+
+    fn decay_strategy() -> impl Strategy<Value = Decay> {
+        prop_oneof![
+            (1u64..100_000_000).prop_map(|duration_sec| Decay::Linear { duration_sec }),
+            (1u64..100_000_000).prop_map(|half_life_sec| Decay::Exponential { half_life_sec }),
+        ]
+    }
+
+    #[test]
+    fn test_linear() {
+        let curve = curve(1000, Decay::Linear { duration_sec: 100 });
+
+        assert_eq!(curve.emitted_at(at(0)).unwrap(), Uint128::zero());
+        assert_eq!(curve.emitted_at(at(50)).unwrap(), Uint128::new(750));
+        assert_eq!(curve.emitted_at(at(100)).unwrap(), Uint128::new(1000));
+        assert_eq!(curve.emitted_at(at(200)).unwrap(), Uint128::new(1000));
+
+        assert_eq!(
+            curve.rewards_per_second_at(at(0)).unwrap(),
+            Uint128::new(19)
+        );
+        assert_eq!(curve.rewards_per_second_at(at(99)).unwrap(), Uint128::one());
+    }
+
+    #[test]
+    fn test_exponential() {
+        let curve = curve(1000, Decay::Exponential { half_life_sec: 100 });
+
+        assert_eq!(curve.emitted_at(at(0)).unwrap(), Uint128::zero());
+        assert_eq!(curve.emitted_at(at(50)).unwrap(), Uint128::new(293));
+        assert_eq!(curve.emitted_at(at(100)).unwrap(), Uint128::new(500));
+        assert_eq!(curve.emitted_at(at(200)).unwrap(), Uint128::new(750));
+        assert_eq!(curve.emitted_at(at(100 * 128)).unwrap(), Uint128::new(1000));
+    }
+
+    #[test]
+    fn test_cutoff() {
+        let mut curve = curve(1000, Decay::Linear { duration_sec: 100 });
+        curve.cutoff = Some(at(50));
+
+        assert_eq!(curve.emitted_at(at(100)).unwrap(), Uint128::new(750));
+        assert_eq!(
+            curve.emissions_between(at(50), at(100)).unwrap(),
+            Uint128::zero()
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn emissions_never_exceed_amount(
+            amount in 1u128..=u128::MAX,
+            decay in decay_strategy(),
+            mut checkpoints in prop::collection::vec(0u64..10_000_000_000, 1..50),
+        ) {
+            let curve = curve(amount, decay);
+            checkpoints.sort_unstable();
+
+            let mut distributed = Uint128::zero();
+            let mut last = at(0);
+            for checkpoint in checkpoints {
+                distributed += curve.emissions_between(last, at(checkpoint)).unwrap();
+                last = at(checkpoint);
+            }
+
+            prop_assert!(distributed <= curve.amount);
+            prop_assert_eq!(distributed, curve.emitted_at(last).unwrap());
+        }
+
+        #[test]
+        fn emissions_never_decrease(
+            amount in 1u128..=u128::MAX,
+            decay in decay_strategy(),
+            elapsed_sec in 0u64..10_000_000_000,
+        ) {
+            let curve = curve(amount, decay);
+
+            prop_assert!(
+                curve.emitted_at(at(elapsed_sec)).unwrap()
+                    <= curve.emitted_at(at(elapsed_sec + 1)).unwrap()
+            );
+        }
+
+        #[test]
+        fn exponential_matches_closed_form(
+            amount in 1_000_000u128..1_000_000_000_000_000_000_000,
+            half_life_sec in 1u64..100_000_000,
+            elapsed_sec in 0u64..1_000_000_000,
+        ) {
+            let curve = curve(amount, Decay::Exponential { half_life_sec });
+
+            let emitted = curve.emitted_at(at(elapsed_sec)).unwrap().u128() as f64;
+            let expected =
+                amount as f64 * (1.0 - (-(elapsed_sec as f64) / half_life_sec as f64).exp2());
+
+            prop_assert!((emitted - expected).abs() <= amount as f64 * 1e-9 + 1.0);
+        }
+    }
+}
@@ -23,6 +23,12 @@ pub enum ContractError {
     #[error("{0}")]
     ConversionOverflowError(#[from] ConversionOverflowError),
 
+    #[error("RewardAssetNotFound")]
+    RewardAssetNotFound,
+
     #[error("UserRewardNotFound")]
     UserRewardNotFound,
+
+    #[error("Paused")]
+    Paused,
 }
@@ -1,7 +1,9 @@
-use cosmwasm_std::{attr, Event};
+use cosmwasm_std::{attr, Addr, Event, Uint128};
 use std::vec;
 
-use crate::state::{Config, CumulativeRewards, UserReward};
+use crate::state::{
+    Config, CumulativeRewards, Distribution, RewardAsset, Status, UserReward, VestingAccount,
+};
 
 pub struct ConfigEvent<'a> {
     pub ty: &'a str,
@@ -12,38 +14,80 @@ impl<'a> From<ConfigEvent<'a>> for Event {
     fn from(ce: ConfigEvent) -> Self {
         Event::new(ce.ty.to_string()).add_attributes(vec![
             attr("stake", ce.config.stake.to_string()),
-            attr("reward_asset", ce.config.reward_asset.to_string()),
-            attr("period_start", ce.config.period_start.to_string()),
-            attr("duration_sec", ce.config.duration_sec.to_string()),
-            attr("period_finish", ce.config.period_finish.to_string()),
+            attr("accounting", ce.config.accounting.to_string()),
             attr(
-                "rewards_per_second",
-                ce.config.rewards_per_second.to_string(),
+                "vesting",
+                ce.config
+                    .vesting
+                    .as_ref()
+                    .map_or("none".to_string(), |vesting| vesting.to_string()),
+            ),
+            attr("funder", ce.config.funder.to_string()),
+            attr(
+                "undistributed_mode",
+                ce.config.undistributed_mode.to_string(),
+            ),
+            attr("grace_period_sec", ce.config.grace_period_sec.to_string()),
+        ])
+    }
+}
+
+pub struct DistributionEvent<'a> {
+    pub ty: &'a str,
+    pub distribution: &'a Distribution,
+}
+
+impl<'a> From<DistributionEvent<'a>> for Event {
+    fn from(de: DistributionEvent) -> Self {
+        Event::new(de.ty.to_string()).add_attributes(vec![
+            attr("reward_asset", de.distribution.reward_asset.to_string()),
+            attr(
+                "schedule",
+                de.distribution
+                    .schedule
+                    .iter()
+                    .map(|segment| segment.to_string())
+                    .collect::<Vec<String>>()
+                    .join(","),
+            ),
+            attr(
+                "curve",
+                de.distribution
+                    .curve
+                    .as_ref()
+                    .map_or("none".to_string(), |curve| curve.to_string()),
             ),
         ])
     }
 }
 
 pub struct UpdateRewardsEvent<'a> {
+    pub reward_asset: &'a RewardAsset,
     pub rewards: &'a CumulativeRewards,
 }
 
 impl<'a> From<UpdateRewardsEvent<'a>> for Event {
     fn from(ure: UpdateRewardsEvent) -> Self {
-        Event::new("update-rewards".to_string()).add_attributes(vec![attr(
-            "rewards_per_token",
-            ure.rewards.rewards_per_token.to_string(),
-        )])
+        Event::new("update-rewards".to_string()).add_attributes(vec![
+            attr("reward_asset", ure.reward_asset.to_string()),
+            attr(
+                "rewards_per_token",
+                ure.rewards.rewards_per_token.to_string(),
+            ),
+            attr("undistributed", ure.rewards.undistributed.to_string()),
+        ])
     }
 }
 
 pub struct UpdateUserRewardsEvent<'a> {
+    pub reward_asset: &'a RewardAsset,
     pub user_reward: &'a UserReward,
 }
 
 impl<'a> From<UpdateUserRewardsEvent<'a>> for Event {
     fn from(uure: UpdateUserRewardsEvent) -> Self {
         Event::new("update-user-rewards".to_string()).add_attributes(vec![
+            attr("reward_asset", uure.reward_asset.to_string()),
             attr(
                 "rewards_checkpoint",
                 uure.user_reward.rewards_checkpoint.to_string(),
@@ -59,3 +103,140 @@ impl<'a> From<UpdateUserRewardsEvent<'a>> for Event {
         ])
     }
 }
+
+pub struct FundEvent<'a> {
+    pub reward_asset: &'a RewardAsset,
+    pub funder: &'a Addr,
+    pub amount: Uint128,
+}
+
+impl<'a> From<FundEvent<'a>> for Event {
+    fn from(fe: FundEvent) -> Self {
+        Event::new("fund-rewards".to_string()).add_attributes(vec![
+            attr("reward_asset", fe.reward_asset.to_string()),
+            attr("funder", fe.funder.to_string()),
+            attr("amount", fe.amount.to_string()),
+        ])
+    }
+}
+
+pub struct WithdrawUndistributedEvent<'a> {
+    pub reward_asset: &'a RewardAsset,
+    pub recipient: &'a Addr,
+    pub amount: Uint128,
+}
+
+impl<'a> From<WithdrawUndistributedEvent<'a>> for Event {
+    fn from(wue: WithdrawUndistributedEvent) -> Self {
+        Event::new("withdraw-undistributed".to_string()).add_attributes(vec![
+            attr("reward_asset", wue.reward_asset.to_string()),
+            attr("recipient", wue.recipient.to_string()),
+            attr("amount", wue.amount.to_string()),
+        ])
+    }
+}
+
+pub struct SweepDustEvent<'a> {
+    pub reward_asset: &'a RewardAsset,
+    pub recipient: &'a Addr,
+    pub amount: Uint128,
+}
+
+impl<'a> From<SweepDustEvent<'a>> for Event {
+    fn from(sde: SweepDustEvent) -> Self {
+        Event::new("sweep-dust".to_string()).add_attributes(vec![
+            attr("reward_asset", sde.reward_asset.to_string()),
+            attr("recipient", sde.recipient.to_string()),
+            attr("amount", sde.amount.to_string()),
+        ])
+    }
+}
+
+pub struct ReclaimEvent<'a> {
+    pub reward_asset: &'a RewardAsset,
+    pub recipient: &'a Addr,
+    pub amount: Uint128,
+    /// Forfeited by the inactive stakers
+    pub forfeited: Uint128,
+    /// Still owed, vesting or left to emit to stakers
+    pub liabilities: Uint128,
+}
+
+impl<'a> From<ReclaimEvent<'a>> for Event {
+    fn from(re: ReclaimEvent) -> Self {
+        Event::new("reclaim".to_string()).add_attributes(vec![
+            attr("reward_asset", re.reward_asset.to_string()),
+            attr("recipient", re.recipient.to_string()),
+            attr("amount", re.amount.to_string()),
+            attr("forfeited", re.forfeited.to_string()),
+            attr("liabilities", re.liabilities.to_string()),
+        ])
+    }
+}
+
+pub struct VestRewardsEvent<'a> {
+    pub reward_asset: &'a RewardAsset,
+    pub recipient: &'a Addr,
+    pub amount: Uint128,
+    pub vesting_account: &'a VestingAccount,
+}
+
+impl<'a> From<VestRewardsEvent<'a>> for Event {
+    fn from(vre: VestRewardsEvent) -> Self {
+        Event::new("vest-rewards".to_string()).add_attributes(vec![
+            attr("reward_asset", vre.reward_asset.to_string()),
+            attr("recipient", vre.recipient.to_string()),
+            attr("amount", vre.amount.to_string()),
+            attr(
+                "tranches",
+                vre.vesting_account
+                    .tranches
+                    .iter()
+                    .map(|tranche| format!("{}:{}", tranche.start.seconds(), tranche.amount))
+                    .collect::<Vec<_>>()
+                    .join(","),
+            ),
+        ])
+    }
+}
+
+pub struct WithdrawVestedEvent<'a> {
+    pub reward_asset: &'a RewardAsset,
+    pub recipient: &'a Addr,
+    pub amount: Uint128,
+}
+
+impl<'a> From<WithdrawVestedEvent<'a>> for Event {
+    fn from(wve: WithdrawVestedEvent) -> Self {
+        Event::new("withdraw-vested".to_string()).add_attributes(vec![
+            attr("reward_asset", wve.reward_asset.to_string()),
+            attr("recipient", wve.recipient.to_string()),
+            attr("amount", wve.amount.to_string()),
+        ])
+    }
+}
+
+pub struct RewardTokenEvent<'a> {
+    pub ty: &'a str,
+    pub token: &'a Addr,
+}
+
+impl<'a> From<RewardTokenEvent<'a>> for Event {
+    fn from(rte: RewardTokenEvent) -> Self {
+        Event::new(rte.ty.to_string()).add_attribute("token", rte.token.to_string())
+    }
+}
+
+pub struct StatusEvent<'a> {
+    pub ty: &'a str,
+    pub status: &'a Status,
+}
+
+impl<'a> From<StatusEvent<'a>> for Event {
+    fn from(se: StatusEvent) -> Self {
+        Event::new(se.ty.to_string()).add_attributes(vec![
+            attr("paused", se.status.paused.to_string()),
+            attr("emergency_mode", se.status.emergency_mode.to_string()),
+        ])
+    }
+}
@@ -1,4 +1,8 @@
+// The generated message constructors take every instantiation parameter
+#[allow(clippy::too_many_arguments)]
 pub mod contract;
+pub mod curve;
 pub mod error;
 pub mod events;
+pub mod msg;
 pub mod state;
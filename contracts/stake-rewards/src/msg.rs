@@ -0,0 +1,25 @@
+use cosmwasm_schema::cw_serde;
+
+use crate::{curve::EmissionCurve, state::EmissionSegment};
+
+/// Queries sent to the stake contract
+#[cw_serde]
+pub enum StakeQueryMsg {
+    /// The total that stake changes are reported against, returned as `Uint128`
+    RewardsTotalStaked {},
+    /// The staker's amount as last reported in a stake change, returned as `Uint128`
+    RewardsStakedAmount { address: String },
+}
+
+/// Messages attached to cw20 tokens sent to the contract
+#[cw_serde]
+pub enum ReceiveMsg {
+    Fund {
+        extra_duration_sec: Option<u64>,
+    },
+    /// Adds the sent token as a reward asset, funded with exactly the sent amount
+    AddRewardAsset {
+        schedule: Vec<EmissionSegment>,
+        curve: Option<EmissionCurve>,
+    },
+}
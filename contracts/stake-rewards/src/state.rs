@@ -1,12 +1,28 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Addr, Env, Timestamp, Uint128, Uint256};
+use cosmwasm_std::{
+    coins, ensure, to_json_binary, Addr, BankMsg, CosmosMsg, Env, QuerierWrapper, StdResult,
+    Timestamp, Uint128, Uint256, WasmMsg,
+};
+use cw20::{BalanceResponse, Cw20ExecuteMsg, Cw20QueryMsg};
 use std::{
     cmp::{max, min},
     convert::TryInto,
     fmt::Display,
 };
+use uju_cw2_common::error::CommonError;
+
+use crate::{curve::EmissionCurve, error::ContractError};
+
+pub const MAX_SCHEDULE_SEGMENTS: usize = 50;
 
-use crate::error::ContractError;
+/// Bounds the work done on every stake change
+pub const MAX_REWARD_ASSETS: usize = 10;
+
+/// Bounds the stake queries of a reclaim
+pub const MAX_RECLAIM_ADDRESSES: usize = 30;
+
+/// Bounds the tranches still vesting per vesting account
+pub const MAX_VESTING_TRANCHES: usize = 10;
 
 #[cw_serde]
 pub enum RewardAsset {
@@ -23,60 +39,450 @@ impl Display for RewardAsset {
     }
 }
 
+impl RewardAsset {
+    pub fn query_balance(&self, querier: QuerierWrapper, address: &Addr) -> StdResult<Uint128> {
+        Ok(match self {
+            RewardAsset::Native(denom) => querier.query_balance(address, denom)?.amount,
+            RewardAsset::Cw20(cw20) => {
+                let balance_response: BalanceResponse = querier.query_wasm_smart(
+                    cw20.to_string(),
+                    &Cw20QueryMsg::Balance {
+                        address: address.to_string(),
+                    },
+                )?;
+                balance_response.balance
+            }
+        })
+    }
+
+    pub fn send_message(&self, recipient: &Addr, amount: Uint128) -> StdResult<CosmosMsg> {
+        Ok(match self {
+            RewardAsset::Native(denom) => BankMsg::Send {
+                to_address: recipient.to_string(),
+                amount: coins(amount.u128(), denom),
+            }
+            .into(),
+            RewardAsset::Cw20(cw20) => WasmMsg::Execute {
+                contract_addr: cw20.to_string(),
+                msg: to_json_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: recipient.to_string(),
+                    amount,
+                })?,
+                funds: vec![],
+            }
+            .into(),
+        })
+    }
+}
+
+/// What happens to the rewards emitted while nothing is staked
+#[cw_serde]
+pub enum UndistributedMode {
+    /// Re-emitted over the rest of the schedule, or along with the next funding once it ended
+    Rollover,
+    /// Left for the funder to withdraw
+    Withdraw,
+}
+
+impl Display for UndistributedMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UndistributedMode::Rollover => write!(f, "rollover"),
+            UndistributedMode::Withdraw => write!(f, "withdraw"),
+        }
+    }
+}
+
+/// How much a segment emits, a total amount is spread evenly over the segment
+#[cw_serde]
+pub enum Emission {
+    RewardsPerSecond(Uint128),
+    Amount(Uint128),
+}
+
+/// A segment of the emission schedule as provided on instantiation
+#[cw_serde]
+pub struct EmissionSegment {
+    pub start: Timestamp,
+    pub end: Timestamp,
+    pub emission: Emission,
+}
+
+#[cw_serde]
+pub struct ScheduleSegment {
+    pub start: Timestamp,
+    pub end: Timestamp,
+    pub rewards_per_second: Uint128,
+}
+
+impl Display for ScheduleSegment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}-{}:{}",
+            self.start.seconds(),
+            self.end.seconds(),
+            self.rewards_per_second
+        )
+    }
+}
+
+impl ScheduleSegment {
+    /// Rewards emitted by the segment between two times
+    pub fn emissions_between(
+        &self,
+        from: Timestamp,
+        to: Timestamp,
+    ) -> Result<Uint128, ContractError> {
+        let from = max(from, self.start);
+        let to = min(to, self.end);
+        if to <= from {
+            return Ok(Uint128::zero());
+        }
+
+        Ok(self
+            .rewards_per_second
+            .checked_mul(Uint128::from(to.seconds() - from.seconds()))?)
+    }
+}
+
+/// Validates the segments, which must be ordered and must not overlap, and converts them
+/// to rates. Returns the schedule along with the total it emits.
+pub fn validate_schedule(
+    now: Timestamp,
+    segments: Vec<EmissionSegment>,
+) -> Result<(Vec<ScheduleSegment>, Uint128), ContractError> {
+    ensure!(
+        segments.len() <= MAX_SCHEDULE_SEGMENTS,
+        CommonError::InvalidInput(format!(
+            "schedule must have at most {} segments",
+            MAX_SCHEDULE_SEGMENTS
+        ))
+    );
+
+    let mut schedule: Vec<ScheduleSegment> = vec![];
+    let mut total = Uint128::zero();
+
+    for segment in segments {
+        ensure!(
+            segment.start >= now,
+            CommonError::InvalidInput("segment start must be at least current time".to_string())
+        );
+        ensure!(
+            segment.end > segment.start,
+            CommonError::InvalidInput("segment must end after it starts".to_string())
+        );
+        ensure!(
+            schedule
+                .last()
+                .is_none_or(|previous| segment.start >= previous.end),
+            CommonError::InvalidInput(
+                "schedule segments must be ordered and must not overlap".to_string()
+            )
+        );
+
+        let duration_sec = Uint128::from(segment.end.seconds() - segment.start.seconds());
+        let rewards_per_second = match segment.emission {
+            Emission::RewardsPerSecond(rewards_per_second) => rewards_per_second,
+            Emission::Amount(amount) => amount.checked_div(duration_sec)?,
+        };
+        ensure!(
+            !rewards_per_second.is_zero(),
+            CommonError::InvalidInput("reward rate must be greater than zero".to_string())
+        );
+
+        total = total.checked_add(rewards_per_second.checked_mul(duration_sec)?)?;
+        schedule.push(ScheduleSegment {
+            start: segment.start,
+            end: segment.end,
+            rewards_per_second,
+        });
+    }
+
+    Ok((schedule, total))
+}
+
+fn ensure_schedule_len(len: usize) -> Result<(), ContractError> {
+    ensure!(
+        len > 0 && len <= MAX_SCHEDULE_SEGMENTS,
+        CommonError::InvalidInput(format!(
+            "schedule must have between 1 and {} segments",
+            MAX_SCHEDULE_SEGMENTS
+        ))
+    );
+    Ok(())
+}
+
+/// Where the staked amounts of claims come from
+#[cw_serde]
+pub enum Accounting {
+    /// Reported by the stake contract, the only one allowed to claim
+    Push,
+    /// Queried from the stake contract, allowing stakers to claim directly
+    Pull,
+}
+
+impl Display for Accounting {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Accounting::Push => write!(f, "push"),
+            Accounting::Pull => write!(f, "pull"),
+        }
+    }
+}
+
+/// Claimed rewards vest linearly over the duration, nothing vests before the cliff
+#[cw_serde]
+pub struct Vesting {
+    pub duration_sec: u64,
+    pub cliff_sec: Option<u64>,
+}
+
+impl Display for Vesting {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}",
+            self.duration_sec,
+            self.cliff_sec.unwrap_or_default()
+        )
+    }
+}
+
+impl Vesting {
+    pub fn validate(&self) -> Result<(), ContractError> {
+        ensure!(
+            self.duration_sec > 0,
+            CommonError::InvalidInput("vesting duration must be greater than zero".to_string())
+        );
+        ensure!(
+            self.cliff_sec
+                .is_none_or(|cliff_sec| cliff_sec <= self.duration_sec),
+            CommonError::InvalidInput("vesting cliff must not exceed its duration".to_string())
+        );
+        Ok(())
+    }
+}
+
 #[cw_serde]
 pub struct Config {
     pub stake: Addr,
+    pub accounting: Accounting,
+    /// Claimed rewards are paid out right away without it
+    pub vesting: Option<Vesting>,
+    /// Can add reward assets, withdraw undistributed rewards and reclaim the rest once the
+    /// grace period passed
+    pub funder: Addr,
+    pub undistributed_mode: UndistributedMode,
+    /// Time stakers have to claim after a schedule finished
+    pub grace_period_sec: u64,
+}
+
+/// The emissions of one reward asset
+#[cw_serde]
+pub struct Distribution {
     pub reward_asset: RewardAsset,
-    pub period_start: Timestamp,
-    pub duration_sec: u64,
-    pub period_finish: Timestamp,
-    pub rewards_per_second: Uint128,
+    /// Ordered, non-overlapping emission segments, gaps emit nothing
+    pub schedule: Vec<ScheduleSegment>,
+    /// Decaying emissions on top of the schedule
+    pub curve: Option<EmissionCurve>,
 }
 
-impl Config {
-    pub fn first_reward_time(&self, last_update: Timestamp) -> Timestamp {
-        max(self.period_start, last_update)
+impl Distribution {
+    pub fn period_start(&self) -> Option<Timestamp> {
+        self.schedule.first().map(|segment| segment.start)
+    }
+
+    pub fn period_finish(&self) -> Option<Timestamp> {
+        self.schedule.last().map(|segment| segment.end)
+    }
+
+    /// The period added rewards are spread over: the rest of the schedule, extended by the
+    /// extra duration. A finished schedule restarts from the current time.
+    pub fn funding_window(
+        &self,
+        now: Timestamp,
+        extra_duration_sec: u64,
+    ) -> (Timestamp, Timestamp) {
+        let from = self.period_start().map_or(now, |start| max(start, now));
+        let finish = self
+            .period_finish()
+            .map_or(from, |finish| max(finish, from));
+        (from, finish.plus_seconds(extra_duration_sec))
+    }
+
+    pub fn rewards_per_second_at(&self, time: Timestamp) -> Uint128 {
+        self.schedule
+            .iter()
+            .find(|segment| segment.start <= time && time < segment.end)
+            .map_or(Uint128::zero(), |segment| segment.rewards_per_second)
+    }
+
+    /// Rewards emitted across every segment overlapping the interval and by the curve
+    pub fn emissions_between(
+        &self,
+        from: Timestamp,
+        to: Timestamp,
+    ) -> Result<Uint128, ContractError> {
+        let curve_emissions = match &self.curve {
+            Some(curve) => curve.emissions_between(from, to)?,
+            None => Uint128::zero(),
+        };
+
+        self.schedule
+            .iter()
+            .try_fold(curve_emissions, |total, segment| {
+                Ok(total.checked_add(segment.emissions_between(from, to)?)?)
+            })
+    }
+
+    /// Adds a flat rate on top of the schedule between two times, filling any gap
+    pub fn add_rewards_per_second(
+        &mut self,
+        from: Timestamp,
+        to: Timestamp,
+        rewards_per_second: Uint128,
+    ) -> Result<(), ContractError> {
+        let mut boundaries = self
+            .schedule
+            .iter()
+            .flat_map(|segment| [segment.start, segment.end])
+            .chain([from, to])
+            .collect::<Vec<Timestamp>>();
+        boundaries.sort();
+        boundaries.dedup();
+
+        let mut schedule: Vec<ScheduleSegment> = vec![];
+        for window in boundaries.windows(2) {
+            let (start, end) = (window[0], window[1]);
+
+            let mut segment_rewards_per_second = self.rewards_per_second_at(start);
+            if from <= start && end <= to {
+                segment_rewards_per_second =
+                    segment_rewards_per_second.checked_add(rewards_per_second)?;
+            }
+            if segment_rewards_per_second.is_zero() {
+                continue;
+            }
+
+            // Merge contiguous segments emitting at the same rate
+            match schedule.last_mut() {
+                Some(previous)
+                    if previous.end == start
+                        && previous.rewards_per_second == segment_rewards_per_second =>
+                {
+                    previous.end = end;
+                }
+                _ => schedule.push(ScheduleSegment {
+                    start,
+                    end,
+                    rewards_per_second: segment_rewards_per_second,
+                }),
+            }
+        }
+
+        ensure_schedule_len(schedule.len())?;
+        self.schedule = schedule;
+
+        Ok(())
+    }
+
+    /// Drops the emissions after the given time
+    pub fn truncate(&mut self, at: Timestamp) {
+        self.schedule.retain(|segment| segment.start < at);
+        if let Some(last) = self.schedule.last_mut() {
+            last.end = min(last.end, at);
+        }
+        if let Some(curve) = &mut self.curve {
+            curve.cutoff = Some(curve.cutoff.map_or(at, |cutoff| min(cutoff, at)));
+        }
+    }
+
+    /// Rewards left to emit after the given time
+    pub fn remaining_emissions(&self, after: Timestamp) -> Result<Uint128, ContractError> {
+        self.emissions_between(after, Timestamp::from_nanos(u64::MAX))
     }
 
-    pub fn last_reward_time(&self, env: &Env) -> Timestamp {
-        min(env.block.time, self.period_finish)
+    /// Drops the segments that ended, once rewards have been checkpointed past them
+    pub fn prune(&mut self, before: Timestamp) {
+        self.schedule.retain(|segment| segment.end > before);
     }
 }
 
+#[cw_serde]
+pub struct AssetUserReward {
+    pub reward_asset: RewardAsset,
+    pub user_reward: UserReward,
+}
+
+#[cw_serde]
+pub struct ScheduleResponse {
+    pub rewards_per_second: Uint128,
+    /// The current and upcoming segments
+    pub segments: Vec<ScheduleSegment>,
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub struct Status {
+    pub paused: bool,
+    pub emergency_mode: bool,
+}
+
 #[cw_serde]
 pub struct CumulativeRewards {
     pub rewards_per_token: Uint256,
     pub last_update: Timestamp,
+    /// Emitted while nothing was staked
+    pub undistributed: Uint128,
 }
 
 impl CumulativeRewards {
     pub fn calc_rewards_per_token(
         &self,
         env: &Env,
-        config: &Config,
+        distribution: &Distribution,
         total_staked: Uint128,
     ) -> Result<Uint256, ContractError> {
         if total_staked == Uint128::zero() {
             return Ok(self.rewards_per_token);
         }
 
-        let first_reward_time = config.first_reward_time(self.last_update);
-        let last_reward_time = config.last_reward_time(env);
-
-        if last_reward_time.seconds() <= first_reward_time.seconds() {
+        let emissions = distribution.emissions_between(self.last_update, env.block.time)?;
+        if emissions.is_zero() {
             return Ok(self.rewards_per_token);
         }
 
-        let time_diff = last_reward_time.minus_seconds(first_reward_time.seconds());
-
-        let additional_reward_per_token = config
-            .rewards_per_second
-            .full_mul(Uint128::from(time_diff.seconds()))
+        let additional_reward_per_token = Uint256::from(emissions)
             .checked_mul(scale_factor())?
             .checked_div(Uint256::from(total_staked))?;
 
         Ok(self.rewards_per_token + additional_reward_per_token)
     }
+
+    /// Accrues the emissions since the last update, which are left undistributed while
+    /// nothing is staked. Returns the rewards credited to stakers, scaled.
+    pub fn checkpoint(
+        &mut self,
+        env: &Env,
+        distribution: &Distribution,
+        total_staked: Uint128,
+    ) -> Result<Uint256, ContractError> {
+        let mut credited = Uint256::zero();
+        if total_staked.is_zero() {
+            let emissions = distribution.emissions_between(self.last_update, env.block.time)?;
+            self.undistributed = self.undistributed.checked_add(emissions)?;
+        } else {
+            let rewards_per_token = self.calc_rewards_per_token(env, distribution, total_staked)?;
+            credited = rewards_per_token
+                .checked_sub(self.rewards_per_token)?
+                .checked_mul(Uint256::from(total_staked))?;
+            self.rewards_per_token = rewards_per_token;
+        }
+        self.last_update = env.block.time;
+
+        Ok(credited)
+    }
 }
 
 #[cw_serde]
@@ -84,6 +490,7 @@ pub struct UserReward {
     pub rewards_checkpoint: Uint256,
     pub pending_rewards: Uint128,
     pub claimed_rewards: Uint128,
+    pub last_update: Option<Timestamp>,
 }
 
 impl Default for UserReward {
@@ -92,6 +499,7 @@ impl Default for UserReward {
             rewards_checkpoint: Uint256::zero(),
             pending_rewards: Uint128::zero(),
             claimed_rewards: Uint128::zero(),
+            last_update: None,
         }
     }
 }
@@ -102,17 +510,32 @@ impl UserReward {
         rewards_per_token: Uint256,
         stake_amount: Uint128,
     ) -> Result<Self, ContractError> {
+        let accrued: Uint128 = rewards_per_token
+            .checked_sub(self.rewards_checkpoint)?
+            .checked_mul(Uint256::from(stake_amount))?
+            .checked_div(scale_factor())?
+            .try_into()?;
+
         Ok(Self {
             rewards_checkpoint: rewards_per_token,
-            pending_rewards: rewards_per_token
-                .checked_sub(self.rewards_checkpoint)?
-                .checked_mul(Uint256::from(stake_amount))?
-                .checked_div(scale_factor())?
-                .try_into()?,
+            pending_rewards: self.pending_rewards.checked_add(accrued)?,
             claimed_rewards: self.claimed_rewards,
+            last_update: self.last_update,
         })
     }
 
+    /// Rewards lost by flooring the next user reward, scaled
+    pub fn rounding(
+        &self,
+        rewards_per_token: Uint256,
+        stake_amount: Uint128,
+    ) -> Result<Uint256, ContractError> {
+        Ok(rewards_per_token
+            .checked_sub(self.rewards_checkpoint)?
+            .checked_mul(Uint256::from(stake_amount))?
+            .checked_rem(scale_factor())?)
+    }
+
     pub fn claim_rewards(&mut self) -> Result<Uint128, ContractError> {
         let claim_amount = self.pending_rewards;
         self.claimed_rewards = self.claimed_rewards.checked_add(claim_amount)?;
@@ -121,6 +544,160 @@ impl UserReward {
     }
 }
 
+/// Rewards claimed together, vesting from their claim
+#[cw_serde]
+pub struct VestingTranche {
+    pub start: Timestamp,
+    pub amount: Uint128,
+}
+
+impl VestingTranche {
+    pub fn vested_at(&self, vesting: &Vesting, time: Timestamp) -> Result<Uint128, ContractError> {
+        let elapsed_sec = time.seconds().saturating_sub(self.start.seconds());
+        Ok(if elapsed_sec < vesting.cliff_sec.unwrap_or_default() {
+            Uint128::zero()
+        } else if elapsed_sec >= vesting.duration_sec {
+            self.amount
+        } else {
+            Uint256::from(self.amount)
+                .checked_mul(Uint256::from(elapsed_sec))?
+                .checked_div(Uint256::from(vesting.duration_sec))?
+                .try_into()?
+        })
+    }
+}
+
+/// Rewards a staker claimed in one reward asset, each claim vesting on its own
+#[cw_serde]
+#[derive(Default)]
+pub struct VestingAccount {
+    /// Still vesting, ordered by start
+    pub tranches: Vec<VestingTranche>,
+    /// Of the fully vested tranches that were dropped
+    pub vested: Uint128,
+    pub withdrawn: Uint128,
+}
+
+impl VestingAccount {
+    /// Rewards vested up to the given time, including those withdrawn
+    pub fn vested_at(&self, vesting: &Vesting, time: Timestamp) -> Result<Uint128, ContractError> {
+        self.tranches
+            .iter()
+            .try_fold(self.vested, |vested, tranche| {
+                Ok(vested.checked_add(tranche.vested_at(vesting, time)?)?)
+            })
+    }
+
+    pub fn locked_at(&self, vesting: &Vesting, time: Timestamp) -> Result<Uint128, ContractError> {
+        let total = self
+            .tranches
+            .iter()
+            .try_fold(self.vested, |total, tranche| {
+                total.checked_add(tranche.amount)
+            })?;
+        Ok(total.checked_sub(self.vested_at(vesting, time)?)?)
+    }
+
+    /// Locks the claimed rewards in a new tranche. To bound the tranches, claims within
+    /// `duration_sec / MAX_VESTING_TRANCHES` of the last tranche's start join it.
+    pub fn add(
+        &mut self,
+        vesting: &Vesting,
+        time: Timestamp,
+        amount: Uint128,
+    ) -> Result<(), ContractError> {
+        let (vested, vesting_tranches): (Vec<_>, Vec<_>) = self
+            .tranches
+            .drain(..)
+            .partition(|tranche| time.seconds() >= tranche.start.seconds() + vesting.duration_sec);
+        for tranche in vested {
+            self.vested = self.vested.checked_add(tranche.amount)?;
+        }
+        self.tranches = vesting_tranches;
+        if amount.is_zero() {
+            return Ok(());
+        }
+
+        let merge_sec = vesting.duration_sec / MAX_VESTING_TRANCHES as u64;
+        match self.tranches.last_mut() {
+            Some(tranche) if time.seconds() < tranche.start.seconds() + merge_sec => {
+                tranche.amount = tranche.amount.checked_add(amount)?;
+            }
+            _ => self.tranches.push(VestingTranche {
+                start: time,
+                amount,
+            }),
+        }
+        Ok(())
+    }
+
+    /// Releases the rewards vested and not withdrawn yet
+    pub fn withdraw(
+        &mut self,
+        vesting: &Vesting,
+        time: Timestamp,
+    ) -> Result<Uint128, ContractError> {
+        let vested = self.vested_at(vesting, time)?;
+        let amount = vested.checked_sub(self.withdrawn)?;
+        self.withdrawn = vested;
+        Ok(amount)
+    }
+}
+
+#[cw_serde]
+pub struct VestingResponse {
+    /// Vested so far, including the withdrawn rewards
+    pub vested: Uint128,
+    pub locked: Uint128,
+    pub withdrawn: Uint128,
+}
+
+/// Running totals reconciling the funding with the rewards paid out
+#[cw_serde]
+#[derive(Default)]
+pub struct Ledger {
+    /// Received through instantiation, top ups and funding
+    pub funded: Uint128,
+    /// Credited to stakers through the rewards per token, scaled
+    pub credited: Uint256,
+    /// Lost by flooring the rewards of stakers, scaled
+    pub rounding: Uint256,
+    pub claimed: Uint128,
+    /// Claimed into vesting accounts and not withdrawn yet
+    pub vesting: Uint128,
+    /// Undistributed rewards withdrawn by the funder
+    pub withdrawn: Uint128,
+    /// Dust sent to the funder
+    pub swept: Uint128,
+    /// Owed to inactive stakers when reclaimed
+    pub forfeited: Uint128,
+    /// Sent to the funder by reclaims
+    pub reclaimed: Uint128,
+}
+
+#[cw_serde]
+pub struct BalanceSheet {
+    pub funded: Uint128,
+    /// Emitted or left to emit, the rest of the funding is dust
+    pub distributable: Uint128,
+    pub claimed: Uint128,
+    /// Claimed but still held in vesting accounts
+    pub vesting: Uint128,
+    pub withdrawn: Uint128,
+    pub swept: Uint128,
+    pub forfeited: Uint128,
+    pub reclaimed: Uint128,
+    /// Credited to stakers but not claimed or forfeited yet, rounded up for stakers not checkpointed since
+    pub owed: Uint128,
+    /// Left to emit by the schedule and the curve
+    pub remaining: Uint128,
+    pub undistributed: Uint128,
+    /// Actual token balance of the contract
+    pub balance: Uint128,
+    /// Balance beyond what is owed, vesting, remaining and undistributed
+    pub dust: Uint128,
+}
+
 pub fn scale_factor() -> Uint256 {
     Uint256::from(10u8).pow(39)
 }
@@ -1,24 +1,27 @@
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{to_json_binary, Addr, Response, StdResult, WasmMsg};
+use cosmwasm_std::{ensure, to_json_binary, Addr, Response, StdResult, Storage, WasmMsg};
 use cw2::set_contract_version;
 use cw_storage_plus::{Item, Map};
-use cw_utils::nonpayable;
+use cw_utils::{maybe_addr, nonpayable, Expiration};
 use nft_vault::{
-    contract::sv::InstantiateMsg as NftVaultInstantiateMsg, state::Config as NftVaultConfig,
+    contract::sv::InstantiateMsg as NftVaultInstantiateMsg,
+    ownership::{Ownership, OwnershipEvent, OwnershipInfo},
+    roles::{Role, RoleEvent, Roles},
+    state::Config as NftVaultConfig,
 };
 use sylvia::{
     contract,
-    types::{ExecCtx, InstantiateCtx, QueryCtx},
+    types::{ExecCtx, InstantiateCtx, QueryCtx, SudoCtx},
 };
 use uju_cw2_common::{
-    admin::only_contract_admin,
+    address::address_or,
     instantiate::{generate_instantiate_2_addr, generate_salt},
 };
 use uju_index_query::{QueryOptions, QueryOptionsInternal};
 
 use crate::{
     error::ContractError,
-    events::{ConfigEvent, VaultEvent},
+    events::{ConfigEvent, PausedEvent, VaultEvent},
 };
 
 pub const CONTRACT_NAME: &str = env!("CARGO_PKG_NAME");
@@ -33,6 +36,9 @@ pub struct Config {
 pub struct StakeVaultFactory {
     pub config: Item<Config>,
     pub vaults: Map<u64, Addr>,
+    pub ownership: Ownership,
+    pub roles: Roles,
+    pub paused: Item<bool>,
 }
 
 #[cfg(not(feature = "library"))]
@@ -46,6 +52,9 @@ impl StakeVaultFactory {
         Self {
             config: Item::new("C"),
             vaults: Map::new("N"),
+            ownership: Ownership::new("O"),
+            roles: Roles::new("L"),
+            paused: Item::new("X"),
         }
     }
 
@@ -55,21 +64,32 @@ impl StakeVaultFactory {
         ctx: InstantiateCtx,
         vault_code_id: u64,
         rewards_code_id: u64,
+        owner: Option<String>,
     ) -> Result<Response, ContractError> {
         nonpayable(&ctx.info)?;
 
         set_contract_version(ctx.deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
 
+        let owner = address_or(&ctx.info.sender, maybe_addr(ctx.deps.api, owner)?.as_ref());
+        let ownership = self.ownership.initialize(ctx.deps.storage, &owner)?;
+
+        self.paused.save(ctx.deps.storage, &false)?;
+
         let config = &Config {
             vault_code_id,
             rewards_code_id,
         };
         self.config.save(ctx.deps.storage, &config)?;
 
-        let response = Response::new().add_event(ConfigEvent {
-            ty: "set-config",
-            config: &config,
-        });
+        let response = Response::new()
+            .add_event(ConfigEvent {
+                ty: "set-config",
+                config: &config,
+            })
+            .add_event(OwnershipEvent {
+                ty: "set-ownership",
+                ownership: &ownership,
+            });
 
         Ok(response)
     }
@@ -82,7 +102,12 @@ impl StakeVaultFactory {
         rewards_code_id: Option<u64>,
     ) -> Result<Response, ContractError> {
         nonpayable(&ctx.info)?;
-        only_contract_admin(&ctx.deps.querier, &ctx.info, &ctx.env)?;
+        self.roles.assert_owner_or_role(
+            ctx.deps.storage,
+            &self.ownership,
+            &ctx.info.sender,
+            Role::ConfigManager,
+        )?;
 
         let mut config = self.config.load(ctx.deps.storage)?;
 
@@ -111,12 +136,24 @@ impl StakeVaultFactory {
         vault_label: String,
         collections: Vec<String>,
         unstaking_duration_sec: u64,
+        vault_owner: Option<String>,
     ) -> Result<Response, ContractError> {
         nonpayable(&ctx.info)?;
-        only_contract_admin(&ctx.deps.querier, &ctx.info, &ctx.env)?;
+        ensure!(!self.paused.load(ctx.deps.storage)?, ContractError::Paused);
+        self.roles.assert_owner_or_role(
+            ctx.deps.storage,
+            &self.ownership,
+            &ctx.info.sender,
+            Role::VaultCreator,
+        )?;
 
         let config = self.config.load(ctx.deps.storage)?;
 
+        let vault_owner = address_or(
+            &ctx.info.sender,
+            maybe_addr(ctx.deps.api, vault_owner)?.as_ref(),
+        );
+
         let last_vault_entry = self.vaults.last(ctx.deps.storage)?;
         let next_index = last_vault_entry.map_or(0u64, |(idx, _)| idx + 1);
 
@@ -144,7 +181,18 @@ impl StakeVaultFactory {
                     rewards_code_id: config.rewards_code_id,
                     collections,
                     unstaking_duration_sec,
+                    receipt_code_id: None,
+                    derivative_code_id: None,
+                    warmup_duration_sec: 0,
+                    loyalty_tiers: vec![],
+                    collection_unstaking_durations: vec![],
+                    stake_fee: None,
+                    unstake_fee: None,
+                    sale_fee: None,
+                    boost_collections: vec![],
+                    max_boosts: 0,
                 },
+                owner: Some(vault_owner.to_string()),
             })?,
             funds: vec![],
             salt,
@@ -160,6 +208,176 @@ impl StakeVaultFactory {
         Ok(response)
     }
 
+    #[sv::msg(exec)]
+    pub fn grant_role(
+        &self,
+        ctx: ExecCtx,
+        address: String,
+        role: Role,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.ownership
+            .assert_owner(ctx.deps.storage, &ctx.info.sender)?;
+
+        let address = ctx.deps.api.addr_validate(&address)?;
+        self.roles.grant(ctx.deps.storage, &address, role)?;
+
+        Ok(Response::new().add_event(RoleEvent {
+            ty: "grant-role",
+            address: &address,
+            role,
+        }))
+    }
+
+    #[sv::msg(exec)]
+    pub fn revoke_role(
+        &self,
+        ctx: ExecCtx,
+        address: String,
+        role: Role,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.ownership
+            .assert_owner(ctx.deps.storage, &ctx.info.sender)?;
+
+        let address = ctx.deps.api.addr_validate(&address)?;
+        self.roles.revoke(ctx.deps.storage, &address, role)?;
+
+        Ok(Response::new().add_event(RoleEvent {
+            ty: "revoke-role",
+            address: &address,
+            role,
+        }))
+    }
+
+    #[sv::msg(exec)]
+    pub fn transfer_ownership(
+        &self,
+        ctx: ExecCtx,
+        new_owner: String,
+        expiry: Option<Expiration>,
+    ) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let new_owner = ctx.deps.api.addr_validate(&new_owner)?;
+        let ownership = self.ownership.transfer_ownership(
+            ctx.deps.storage,
+            &ctx.env.block,
+            &ctx.info.sender,
+            new_owner,
+            expiry,
+        )?;
+
+        Ok(Response::new().add_event(OwnershipEvent {
+            ty: "transfer-ownership",
+            ownership: &ownership,
+        }))
+    }
+
+    #[sv::msg(exec)]
+    pub fn accept_ownership(&self, ctx: ExecCtx) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let ownership =
+            self.ownership
+                .accept_ownership(ctx.deps.storage, &ctx.env.block, &ctx.info.sender)?;
+
+        Ok(Response::new().add_event(OwnershipEvent {
+            ty: "accept-ownership",
+            ownership: &ownership,
+        }))
+    }
+
+    #[sv::msg(exec)]
+    pub fn renounce_ownership(&self, ctx: ExecCtx) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+
+        let ownership = self
+            .ownership
+            .renounce_ownership(ctx.deps.storage, &ctx.info.sender)?;
+
+        Ok(Response::new().add_event(OwnershipEvent {
+            ty: "renounce-ownership",
+            ownership: &ownership,
+        }))
+    }
+
+    #[sv::msg(query)]
+    pub fn roles(
+        &self,
+        ctx: QueryCtx,
+        query_options: QueryOptions<String>,
+    ) -> StdResult<Vec<(Addr, Vec<Role>)>> {
+        let QueryOptionsInternal {
+            limit,
+            order,
+            min,
+            max,
+        } = query_options.unpack(
+            &|offset| ctx.deps.api.addr_validate(offset).unwrap(),
+            None,
+            None,
+        );
+
+        self.roles.range(ctx.deps.storage, min, max, order, limit)
+    }
+
+    #[sv::msg(query)]
+    pub fn paused(&self, ctx: QueryCtx) -> StdResult<bool> {
+        self.paused.load(ctx.deps.storage)
+    }
+
+    #[sv::msg(query)]
+    pub fn ownership(&self, ctx: QueryCtx) -> StdResult<OwnershipInfo> {
+        self.ownership.load(ctx.deps.storage)
+    }
+
+    #[sv::msg(exec)]
+    pub fn pause(&self, ctx: ExecCtx) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.roles.assert_owner_or_role(
+            ctx.deps.storage,
+            &self.ownership,
+            &ctx.info.sender,
+            Role::Pauser,
+        )?;
+
+        self.update_paused(ctx.deps.storage, "pause", true)
+    }
+
+    #[sv::msg(exec)]
+    pub fn unpause(&self, ctx: ExecCtx) -> Result<Response, ContractError> {
+        nonpayable(&ctx.info)?;
+        self.roles.assert_owner_or_role(
+            ctx.deps.storage,
+            &self.ownership,
+            &ctx.info.sender,
+            Role::Pauser,
+        )?;
+
+        self.update_paused(ctx.deps.storage, "unpause", false)
+    }
+
+    #[sv::msg(sudo)]
+    pub fn set_paused(&self, ctx: SudoCtx, paused: bool) -> Result<Response, ContractError> {
+        self.update_paused(ctx.deps.storage, "sudo-set-paused", paused)
+    }
+
+    #[sv::msg(sudo)]
+    pub fn set_owner(
+        &self,
+        ctx: SudoCtx,
+        owner: Option<String>,
+    ) -> Result<Response, ContractError> {
+        let owner = maybe_addr(ctx.deps.api, owner)?;
+        let ownership = self.ownership.set_owner(ctx.deps.storage, owner)?;
+
+        Ok(Response::new().add_event(OwnershipEvent {
+            ty: "sudo-set-owner",
+            ownership: &ownership,
+        }))
+    }
+
     #[sv::msg(query)]
     pub fn vaults(
         &self,
@@ -182,4 +400,63 @@ impl StakeVaultFactory {
 
         Ok(results)
     }
+
+    pub fn update_paused(
+        &self,
+        storage: &mut dyn Storage,
+        ty: &str,
+        paused: bool,
+    ) -> Result<Response, ContractError> {
+        self.paused.save(storage, &paused)?;
+
+        Ok(Response::new().add_event(PausedEvent { ty, paused }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        contract::sv::mt::{CodeId, StakeVaultFactoryProxy},
+        error::ContractError,
+    };
+
+    use sylvia::{
+        cw_multi_test::{App as CwApp, IntoAddr},
+        multitest::App,
+    };
+
+    #[test]
+    fn test_sudo() {
+        let app: App<CwApp> = App::default();
+
+        let owner = "owner".into_addr();
+        let new_owner = "new_owner".into_addr();
+
+        let code_id = CodeId::store_code(&app);
+        let vault_factory = code_id.instantiate(0, 0, None).call(&owner).unwrap();
+
+        // Paused by governance
+        vault_factory.set_paused(true).unwrap();
+        assert!(vault_factory.paused().unwrap());
+
+        let err = vault_factory
+            .create_vault("vault".to_string(), vec![], 60, None)
+            .call(&owner)
+            .unwrap_err();
+        assert_eq!(err, ContractError::Paused);
+
+        vault_factory.set_paused(false).unwrap();
+        assert!(!vault_factory.paused().unwrap());
+
+        // Owner replaced by governance
+        vault_factory
+            .set_owner(Some(new_owner.to_string()))
+            .unwrap();
+        assert_eq!(
+            vault_factory.ownership().unwrap().owner,
+            Some(new_owner.clone())
+        );
+        assert!(vault_factory.pause().call(&owner).is_err());
+        vault_factory.pause().call(&new_owner).unwrap();
+    }
 }
@@ -16,4 +16,7 @@ pub enum ContractError {
 
     #[error("{0}")]
     CommonError(#[from] CommonError),
+
+    #[error("Paused")]
+    Paused,
 }
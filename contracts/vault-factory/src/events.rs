@@ -27,3 +27,14 @@ impl<'a> From<VaultEvent<'a>> for Event {
         Event::new(ve.ty.to_string()).add_attribute("address", ve.address.to_string())
     }
 }
+
+pub struct PausedEvent<'a> {
+    pub ty: &'a str,
+    pub paused: bool,
+}
+
+impl<'a> From<PausedEvent<'a>> for Event {
+    fn from(pe: PausedEvent) -> Self {
+        Event::new(pe.ty.to_string()).add_attribute("paused", pe.paused.to_string())
+    }
+}